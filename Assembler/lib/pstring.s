@@ -0,0 +1,42 @@
+// Packed strings: two characters per word, ended by a zero byte. .packed
+// puts the first character of each pair in the high byte of the word and
+// .packed_le in the low byte, so there is a routine for each order.
+// Include this file in the code section and call the routines with bln.
+// They take the address of the string in r0 and the index of a character
+// in r1 and return the character in r0. Only r0 and r1 are changed, so
+// a string is iterated by keeping its address and the index in other
+// registers and reading characters until one is 0
+
+// Character r1 of the .packed string at address r0
+pstr_char:
+    cmp r1 #2
+    blt pstr_char_word
+    add r0 r0 #1  // Two characters further per word
+    sub r1 r1 #2
+    jmp pstr_char
+pstr_char_word:
+    ldr r0 [r0]
+    cmp r1 #0
+    bne pstr_char_byte  // Odd characters are in the low byte
+    shr r0 r0 #8
+pstr_char_byte:
+    mov r1 #255
+    and r0 r0 r1
+    ret
+
+// Character r1 of the .packed_le string at address r0
+pstr_char_le:
+    cmp r1 #2
+    blt pstr_char_le_word
+    add r0 r0 #1
+    sub r1 r1 #2
+    jmp pstr_char_le
+pstr_char_le_word:
+    ldr r0 [r0]
+    cmp r1 #0
+    beq pstr_char_le_byte  // Even characters are in the low byte
+    shr r0 r0 #8
+pstr_char_le_byte:
+    mov r1 #255
+    and r0 r0 r1
+    ret
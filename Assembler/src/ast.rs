@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use crate::err_handler::LineError;
+use crate::expr::Expr;
+use crate::operands::Operand;
+use crate::symbols::Section;
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+// Statement: What a line of the source declares. Comments and empty
+// lines declare nothing and are left out of the program. Includes name a
+// file relative to the file they are written in:
+//     .include "<file>"    the lines of another source file
+//     .incbin "<file>"     the bytes of a file as data, two per word msb first
+// parse_program reads them, and an .incbin becomes the Data of its file.
+// parse_source leaves them as they are, taking no words
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Label(String),
+    Instruction(String, Vec<Operand>),
+    Data(Vec<u16>),
+    Directive(Directive),
+    Section(Section),
+    Include(String),
+    Incbin(String),
+    Definition(Definition)
+}
+
+// Directive: Statements that move the address of whatever follows them.
+// Gaps they leave are filled with zeros
+//     .org <expr>      continue at the given address
+//     .align <expr>    continue at the next multiple of the value
+//     .space <expr>    reserve the given number of words
+//     .word <expr>     a single word of the given value, in either section
+#[derive(Debug, Clone, PartialEq)]
+pub enum Directive {
+    Org(Expr),
+    Align(Expr),
+    Space(Expr),
+    Word(Expr)
+}
+
+// Definition: Statements that name numbers instead of placing anything.
+// A record lays out fields one after the other from offset 0:
+//     .struct <name>                   start the record
+//     .field <field> [<expr>]          a field of the given size (1 word)
+//     .ends                            end the record
+// and defines '<name>.<field>' as the offset of each field and
+// '<name>.size' as the size of the record. An enum numbers its members
+// from 0, or from the value one of them is given:
+//     .enum <name> <member> [= <expr>], ...
+// and defines '<name>.<member>' as the value of each. Sizes and values
+// may only use numbers and names that were already defined
+#[derive(Debug, Clone, PartialEq)]
+pub enum Definition {
+    Struct(String),
+    Field(String, Expr),
+    Ends,
+    Enum(String, Vec<(String, Option<Expr>)>)
+}
+
+impl Definition {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Definition::Struct(_)   => ".struct",
+            Definition::Field(_, _) => ".field",
+            Definition::Ends        => ".ends",
+            Definition::Enum(_, _)  => ".enum"
+        }
+    }
+}
+
+impl Directive {
+    pub const NAMES: [&'static str; 13] = [".section", ".org", ".align", ".space", ".word",
+                                           ".include", ".incbin", ".packed", ".packed_le",
+                                           ".struct", ".field", ".ends", ".enum"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Directive::Org(_)   => ".org",
+            Directive::Align(_) => ".align",
+            Directive::Space(_) => ".space",
+            Directive::Word(_)  => ".word"
+        }
+    }
+
+    pub fn expr(&self) -> &Expr {
+        match self {
+            Directive::Org(e) | Directive::Align(e) | Directive::Space(e) |
+            Directive::Word(e) => e
+        }
+    }
+}
+
+// Node: A statement together with where it was found. Line numbers start
+// at 0 like the ones in LineError, and columns at 1. Section is the
+// section the statement belongs to, None before the first declaration.
+// The address and size in words are filled in by the layout pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub stmt: Statement,
+    pub line: usize,
+    pub column: usize,
+    pub section: Option<Section>,
+    pub addr: u16,
+    pub size: usize
+}
+
+// Source File: A file whose lines are part of the program, stored in the
+// source of the program from first_line on
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    pub path: String,
+    pub first_line: usize
+}
+
+// Program: The source file parsed once into nodes, in source order. Every
+// pass of the assembler (layout, encoding, warnings, debug information)
+// works on this representation instead of reading the file again.
+// The lines of the main file come first in the source, followed by those
+// of every included file in the order they were read, so line numbers of
+// the main file are the same as in the file itself. Files holds the main
+// file and the included sources, binaries the files of .incbin, and
+// constants the names given to numbers by definitions
+#[derive(Debug, Default)]
+pub struct Program {
+    pub file: String,
+    pub source: Vec<String>,
+    pub nodes: Vec<Node>,
+    pub files: Vec<SourceFile>,
+    pub binaries: Vec<String>,
+    pub constants: HashMap<String, u16>
+}
+
+// ****************************** PROGRAM VIEWS **************************** //
+
+impl Program {
+    // Nodes inside the given section
+    pub fn in_section(&self, section: Section) -> impl Iterator<Item = &Node> {
+        self.nodes.iter().filter(move |n| n.section == Some(section))
+    }
+
+    // Instructions of the code section as (mnemonic, operands, line number)
+    pub fn instructions(&self) -> impl Iterator<Item = (&str, &[Operand], usize)> {
+        self.in_section(Section::Code).filter_map(|n| match &n.stmt {
+            Statement::Instruction(m, args) => Some((m.as_str(), args.as_slice(), n.line)),
+            _ => None
+        })
+    }
+
+    // Labels declared in the given section as (name, line number)
+    pub fn labels_in(&self, section: Section) -> impl Iterator<Item = (&str, usize)> {
+        self.in_section(section).filter_map(|n| match &n.stmt {
+            Statement::Label(l) => Some((l.as_str(), n.line)),
+            _ => None
+        })
+    }
+
+    // Labels of both sections as (name, line number)
+    pub fn labels(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.nodes.iter().filter(|n| n.section.is_some()).filter_map(|n| match &n.stmt {
+            Statement::Label(l) => Some((l.as_str(), n.line)),
+            _ => None
+        })
+    }
+
+    // Addresses taken by a section once laid out, None if it was not
+    // declared
+    pub fn section_range(&self, section: Section) -> Option<Range<usize>> {
+        let first = self.in_section(section).next()?;
+        let last = self.in_section(section).last()?;
+        Some(first.addr as usize..last.addr as usize + last.size)
+    }
+
+    // Index of the file a line of the source belongs to, and the line
+    // number within that file
+    pub fn locate(&self, line: usize) -> (usize, usize) {
+        match self.files.iter().rposition(|f| f.first_line <= line) {
+            Some(idx) => (idx, line - self.files[idx].first_line),
+            None => (0, line)
+        }
+    }
+
+    pub fn file_name(&self, idx: usize) -> &str {
+        self.files.get(idx).map_or(&self.file, |f| &f.path)
+    }
+
+    // Line of the source as shown in reports, starting at 1. Lines of
+    // included files are written as file:line
+    pub fn line_name(&self, line: usize) -> String {
+        match self.locate(line) {
+            (0, line) => (line + 1).to_string(),
+            (idx, line) => format!("{}:{}", self.file_name(idx), line + 1)
+        }
+    }
+
+    // Error as shown to the user: errors on a line of an included file
+    // name that file and the line within it
+    pub fn locate_error(&self, e: LineError) -> LineError {
+        match e.line().map(|n| self.locate(n)) {
+            Some((idx, line)) if idx > 0 => {
+                LineError::InFile(self.file_name(idx).to_string(), line, Box::new(e))
+            }
+            _ => e
+        }
+    }
+
+    // Label declared right before the first instruction
+    pub fn entry_label(&self) -> Option<&str> {
+        let mut entry = None;
+        for n in self.in_section(Section::Code) {
+            match &n.stmt {
+                Statement::Label(l) => entry = Some(l.as_str()),
+                Statement::Instruction(..) => break,
+                _ => ()
+            }
+        }
+        entry
+    }
+}
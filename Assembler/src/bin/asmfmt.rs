@@ -0,0 +1,66 @@
+use std::env;
+use std::fs::{read_to_string, write};
+use std::process::exit;
+use assembler::cli::CLI;
+use assembler::err_handler::error_handler;
+use assembler::format::format_source;
+
+// Formats assembly source files in place:
+//     asmfmt [--check] file.s...
+// With --check no file is written. Files that are not formatted are listed
+// and the exit code is 1, so CI can reject them
+fn main() {
+    let mut check = false;
+    let mut files: Vec<String> = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--check" => check = true,
+            flag if flag.starts_with('-') => {
+                CLI::print_error(&format!("Unknown flag '{}'", flag));
+                exit(1);
+            }
+            file => files.push(file.to_string())
+        }
+    }
+    if files.is_empty() {
+        CLI::print_error("Must pass in at least one file as argument, and optionally --check");
+        exit(1);
+    }
+
+    let mut failed = false;
+    for file in &files {
+        let text = match read_to_string(file) {
+            Ok(text) => text,
+            Err(e) => {
+                CLI::print_error(&format!("Could not read {}: {}", file, e));
+                failed = true;
+                continue;
+            }
+        };
+        // Source that does not assemble, or that formatting would change the
+        // meaning of, is never touched
+        let formatted = match format_source(file, &text) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                error_handler(&e, file);
+                failed = true;
+                continue;
+            }
+        };
+        if formatted == text {
+            continue;
+        }
+        if check {
+            let line = text.lines().zip(formatted.lines())
+                .position(|(a, b)| a != b)
+                .unwrap_or_else(|| text.lines().count().min(formatted.lines().count()));
+            println!("{} is not formatted (first difference on line {})", file, line + 1);
+            failed = true;
+        } else {
+            write(file, formatted).expect("Could not write formatted file");
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
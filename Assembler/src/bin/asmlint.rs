@@ -0,0 +1,74 @@
+use std::env;
+use std::io::sink;
+use std::process::exit;
+use assembler::cli::{warning_kind, CLI};
+use assembler::err_handler::error_handler;
+use assembler::lint::lint_program;
+use assembler::parser::{assemble_program, parse_program, parse_symbols};
+use assembler::symbols::Section;
+use assembler::warnings::{check_program, warning_handler, Warning, WarningConfig, WarningKind};
+
+// Checks assembly source files against the conventions of the team:
+//     asmlint [-W<name>] [-Wno-<name>] file.s...
+// Every warning category of the assembler is enabled, lints included.
+// Files are assembled first, so errors are reported like the assembler
+// does. The exit code is 1 if anything was reported
+fn main() {
+    let mut config = WarningConfig::default();
+    WarningKind::ALL.into_iter().for_each(|k| config.enable(k));
+    let mut files: Vec<String> = Vec::new();
+    for arg in env::args().skip(1) {
+        let kind = match arg.as_str() {
+            flag if flag.starts_with("-Wno-") => warning_kind(&flag[5..]).map(|k| config.disable(k)),
+            flag if flag.starts_with("-W") => warning_kind(&flag[2..]).map(|k| config.enable(k)),
+            flag if flag.starts_with('-') => Err(format!("Unknown flag '{}'", flag)),
+            file => {
+                files.push(file.to_string());
+                Ok(())
+            }
+        };
+        if let Err(msg) = kind {
+            CLI::print_error(&msg);
+            exit(1);
+        }
+    }
+    if files.is_empty() {
+        CLI::print_error("Must pass in at least one file as argument, and optionally flags");
+        exit(1);
+    }
+
+    let mut failed = false;
+    for file in &files {
+        let mut program = match parse_program(file) {
+            Ok(program) => program,
+            Err(e) => {
+                error_handler(&e, file);
+                failed = true;
+                continue;
+            }
+        };
+        let result = parse_symbols(&mut program)
+            .and_then(|symbols| assemble_program(&program, &symbols, &mut sink()).map(|_| symbols));
+        let symbols = match result {
+            Ok(symbols) => symbols,
+            Err(e) => {
+                error_handler(&program.locate_error(e), file);
+                failed = true;
+                continue;
+            }
+        };
+
+        // Execution starts at the start of the code section
+        let entry = program.section_range(Section::Code).map_or(0, |c| c.start as u16);
+        let mut warnings = check_program(&program, &config);
+        warnings.extend(lint_program(&program, &symbols, entry, &config));
+        warnings.sort_by_key(Warning::line);
+        for w in &warnings {
+            warning_handler(w, &program, false);
+        }
+        failed |= !warnings.is_empty();
+    }
+    if failed {
+        exit(1);
+    }
+}
@@ -0,0 +1,169 @@
+use std::path::Path;
+use colored::Colorize;
+use objformat::{Format, MEMORY_DEPTH};
+use objformat::build::BUILD_ID_MAX;
+use crate::warnings::{WarningConfig, WarningKind};
+
+// Output file name when none is given, with the extension of the format
+const DEFAULT_OUT_NAME: &str = "out";
+
+pub struct CLI {
+    pub file_path: String,
+    pub out_file: String,
+    pub map_file: Option<String>,
+    pub xref_file: Option<String>,
+    pub stack_file: Option<String>,
+    pub listing_file: Option<String>,
+    pub dep_file: Option<String>,
+    pub format: Format,
+    pub depth: usize,
+    pub entry: Option<String>,
+    pub build_id: Option<String>,
+    pub strip: bool,
+    pub debug_info: bool,
+    pub optimize: bool,
+    pub warnings: WarningConfig
+}
+
+impl CLI {
+    // Parses the command line arguments:
+    //     assembler [-o out_file] [-m map_file] [--xref <file>] [--stack <file>]
+    //               [--listing <file>] [-MD] [-MF <file>] [--format <format>]
+    //               [--depth <words>] [--entry <label>] [--build-id <id>]
+    //               [--strip] [-g] [-O] [-W<name>] [-Wno-<name>] [-Wall] [-w]
+    //               [-Werror] file.s
+    // -MD writes the dependency file next to the output file with the
+    // extension .d, and -MF to the given file instead. --build-id writes
+    // build information after the end of the program, in every format
+    // Returns an error message for unknown flags or warning names
+    pub fn new(args: Vec<String>) -> Result<CLI, String> {
+        let mut file_path: Option<String> = None;
+        let mut out_file: Option<String> = None;
+        let mut dependencies = false;
+        let mut a = CLI {
+            file_path: String::new(),
+            out_file: String::new(),
+            map_file: None,
+            xref_file: None,
+            stack_file: None,
+            listing_file: None,
+            dep_file: None,
+            format: Format::Exe,
+            depth: MEMORY_DEPTH,
+            entry: None,
+            build_id: None,
+            strip: false,
+            debug_info: false,
+            optimize: false,
+            warnings: WarningConfig::default()
+        };
+
+        let mut args = args.into_iter().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" => {
+                    out_file = Some(args.next().ok_or("Missing file name after -o")?);
+                }
+                "-m" => {
+                    a.map_file = Some(args.next().ok_or("Missing file name after -m")?);
+                }
+                "--xref" => {
+                    a.xref_file = Some(args.next().ok_or("Missing file name after --xref")?);
+                }
+                "--stack" => {
+                    a.stack_file = Some(args.next().ok_or("Missing file name after --stack")?);
+                }
+                "--listing" => {
+                    a.listing_file = Some(args.next().ok_or("Missing file name after --listing")?);
+                }
+                "-MD" => dependencies = true,
+                "-MF" => {
+                    a.dep_file = Some(args.next().ok_or("Missing file name after -MF")?);
+                }
+                "--format" => {
+                    let name = args.next().ok_or("Missing format name after --format")?;
+                    a.format = output_format(&name)?;
+                }
+                "--depth" => {
+                    let depth = args.next().ok_or("Missing number of words after --depth")?;
+                    a.depth = memory_depth(&depth)?;
+                }
+                "--entry" => {
+                    a.entry = Some(args.next().ok_or("Missing label after --entry")?);
+                }
+                "--build-id" => {
+                    let id = args.next().ok_or("Missing build ID after --build-id")?;
+                    if id.is_empty() || id.len() > BUILD_ID_MAX {
+                        return Err(format!("Invalid build ID '{}'. It must be 1 to {} bytes long",
+                                           id.escape_default(), BUILD_ID_MAX));
+                    }
+                    a.build_id = Some(id);
+                }
+                "--strip" => a.strip = true,
+                "-g" => a.debug_info = true,
+                "-O" => a.optimize = true,
+                "-Werror" => a.warnings.werror = true,
+                "-Wall" => WarningKind::ALL.into_iter().for_each(|k| a.warnings.enable(k)),
+                "-w" => a.warnings.disable_all(),
+                flag if flag.starts_with("-Wno-") => {
+                    a.warnings.disable(warning_kind(&flag[5..])?);
+                }
+                flag if flag.starts_with("-W") => {
+                    a.warnings.enable(warning_kind(&flag[2..])?);
+                }
+                flag if flag.starts_with('-') => {
+                    return Err(format!("Unknown flag '{}'", flag));
+                }
+                _ if file_path.is_some() => {
+                    return Err("Must pass in only one file as argument".to_string());
+                }
+                file => file_path = Some(file.to_string())
+            }
+        }
+
+        match file_path {
+            Some(f) => {
+                a.file_path = f;
+                a.out_file = out_file.unwrap_or_else(|| {
+                    format!("{}.{}", DEFAULT_OUT_NAME, a.format.extension())
+                });
+                if dependencies && a.dep_file.is_none() {
+                    a.dep_file = Some(Path::new(&a.out_file).with_extension("d").display().to_string());
+                }
+                Ok(a)
+            }
+            None => Err("Must pass in one file as argument, and optionally \
+                         pass flags".to_string())
+        }
+    }
+
+    pub fn print_error(msg: &str) {
+        println!("{} {}", "Error".red(), msg);
+    }
+}
+
+pub fn warning_kind(name: &str) -> Result<WarningKind, String> {
+    WarningKind::from_name(name).ok_or_else(|| {
+        let names: Vec<&str> = WarningKind::ALL.iter().map(|k| k.name()).collect();
+        format!("Unknown warning '{}'. Valid warnings are: {}", name, names.join(", "))
+    })
+}
+
+fn output_format(name: &str) -> Result<Format, String> {
+    Format::from_name(name).ok_or_else(|| {
+        let names: Vec<&str> = Format::ALL.iter().map(|f| f.name()).collect();
+        format!("Unknown output format '{}'. Valid formats are: {}", name, names.join(", "))
+    })
+}
+
+fn memory_depth(depth: &str) -> Result<usize, String> {
+    let parsed = match depth.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => depth.parse::<usize>()
+    };
+    match parsed {
+        Ok(d) if d > 0 => Ok(d),
+        _ => Err(format!("Invalid memory depth '{}'. Depth must be a positive number \
+                          of words", depth))
+    }
+}
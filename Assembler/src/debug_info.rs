@@ -0,0 +1,96 @@
+use std::fs;
+use objformat::debug::{DataObject, DebugInfo, LineEntry, Scope};
+use crate::ast::{Directive, Program, Statement};
+use crate::symbols::{Section, Symbols};
+
+// ************************* DEBUG INFO GENERATION ************************* //
+
+// Builds the debug information of an assembled program (-g). Instructions
+// are mapped to the file, line and column of their mnemonic, code labels
+// become scopes running up to the next code label, and data labels become
+// data objects holding every data line up to the next label
+pub fn build_debug_info(program: &Program, syms: &Symbols) -> DebugInfo {
+    let files = (0..program.files.len().max(1))
+        .map(|idx| {
+            let file = program.file_name(idx);
+            fs::canonicalize(file).map(|p| p.display().to_string())
+                                  .unwrap_or_else(|_| file.to_string())
+        })
+        .collect();
+
+    let lines = program.in_section(Section::Code)
+        .filter(|n| matches!(n.stmt, Statement::Instruction(..)))
+        .map(|n| {
+            let (file, line) = program.locate(n.line);
+            LineEntry { addr: n.addr, file, line: line + 1, column: n.column }
+        })
+        .collect();
+
+    DebugInfo {
+        files,
+        lines,
+        scopes: code_scopes(program, syms),
+        objects: data_objects(program, syms)
+    }
+}
+
+// ************************ PRIVATE HELPER FUNCTIONS *********************** //
+
+fn code_scopes(program: &Program, syms: &Symbols) -> Vec<Scope> {
+    let labels: Vec<(&str, u16)> = program.labels_in(Section::Code)
+        .map(|(l, _)| (l, syms.labels[l]))
+        .collect();
+    let code_end = program.section_range(Section::Code).map_or(0, |r| r.end as u16);
+    let mut scopes = Vec::new();
+    for (i, (label, start)) in labels.iter().enumerate() {
+        let end = labels.get(i + 1).map_or(code_end, |(_, a)| *a);
+        // Labels right before another label hold no code of their own
+        if end > *start {
+            scopes.push(Scope { label: label.to_string(), start: *start, end });
+        }
+    }
+    scopes
+}
+
+// Every data label owns the data and space declared after it, up to the
+// next label
+fn data_objects(program: &Program, syms: &Symbols) -> Vec<DataObject> {
+    let mut objects: Vec<DataObject> = Vec::new();
+    for node in program.in_section(Section::Data) {
+        match (&node.stmt, objects.last_mut()) {
+            (Statement::Label(l), _) => {
+                objects.push(DataObject { label: l.clone(), addr: syms.labels[l], words: 0 });
+            }
+            (Statement::Data(_) | Statement::Directive(Directive::Space(_) | Directive::Word(_)),
+             Some(object)) => {
+                object.words += node.size;
+            }
+            _ => ()
+        }
+    }
+    objects
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_program, parse_symbols};
+
+    #[test]
+    // [Test File 1] Sums an array in the data section
+    fn debug_info_file1() {
+        let mut program = parse_program("test/file1.s").unwrap();
+        let syms = parse_symbols(&mut program).unwrap();
+        let info = build_debug_info(&program, &syms);
+
+        assert!(info.files[0].ends_with("file1.s"));
+        assert_eq!(info.lines.len(), 13);
+        assert_eq!(info.lines[0], LineEntry { addr: 0, file: 0, line: 4, column: 5 });
+        let scopes: Vec<(&str, u16, u16)> = info.scopes.iter()
+            .map(|s| (s.label.as_str(), s.start, s.end))
+            .collect();
+        assert_eq!(scopes, [("start", 0, 4), ("loop", 4, 11), ("end_loop", 11, 13)]);
+        assert_eq!(info.objects, [DataObject { label: "arr".to_string(), addr: 13, words: 5 }]);
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use crate::ast::{Definition, Program, Statement};
+use crate::err_handler::LineError;
+use crate::expr::{parse_expr, Expr};
+use crate::operands::is_identifier;
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+// Record being defined: the .struct has been read but not its .ends yet.
+// Size is the offset of the next field
+#[derive(Debug)]
+pub struct OpenRecord {
+    name: String,
+    size: u16,
+    line: usize
+}
+
+// ******************************* PARSING ********************************* //
+
+// Parses the arguments of .struct, .field, .ends and .enum
+pub fn parsed_definition(name: &str, args: &str, line_num: usize) -> Result<Statement, LineError> {
+    let args = args.trim();
+    let wrong_args = || LineError::WrongArgs(name.to_string(), line_num);
+    let identifier = |s: &str| match is_identifier(s) {
+        true => Ok(s.to_string()),
+        false => Err(LineError::Unrecognized(s.to_string(), line_num))
+    };
+
+    let definition = match name {
+        ".struct" if args.is_empty() => return Err(wrong_args()),
+        ".struct" => Definition::Struct(identifier(args)?),
+        ".field" => {
+            let (field, size) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+            if field.is_empty() {
+                return Err(wrong_args());
+            }
+            let size = match size.trim() {
+                "" => Expr::Number(1),
+                size => parse_expr(size, line_num)?
+            };
+            Definition::Field(identifier(field)?, size)
+        }
+        ".ends" if args.is_empty() => Definition::Ends,
+        ".ends" => return Err(wrong_args()),
+        _ => {
+            let (enum_name, members) = args.split_once(char::is_whitespace).ok_or_else(wrong_args)?;
+            let members = members.split(',')
+                .map(|m| match m.split_once('=') {
+                    Some((member, value)) => {
+                        Ok((identifier(member.trim())?, Some(parse_expr(value, line_num)?)))
+                    }
+                    None => Ok((identifier(m.trim())?, None))
+                })
+                .collect::<Result<_, _>>()?;
+            Definition::Enum(identifier(enum_name)?, members)
+        }
+    };
+    Ok(Statement::Definition(definition))
+}
+
+// ******************************* DEFINING ******************************** //
+
+// Defines the constants of the definition in the last node of the program,
+// if it is one. Sizes and values are evaluated right away, with the
+// constants defined so far
+pub fn define(program: &mut Program, record: &mut Option<OpenRecord>) -> Result<(), LineError> {
+    let node = program.nodes.last().expect("Node was just parsed");
+    let line = node.line;
+    let Statement::Definition(definition) = &node.stmt else { return Ok(()) };
+    let constants = &mut program.constants;
+    let value = |name: &str, expr: &Expr, constants: &_| {
+        let value = expr.eval(constants, line)?;
+        u16::try_from(value).map_err(|_| LineError::DirectiveValue(name.to_string(), value, line))
+    };
+
+    match (definition, record.as_mut()) {
+        (Definition::Struct(name), None) => {
+            *record = Some(OpenRecord { name: name.clone(), size: 0, line });
+        }
+        (Definition::Field(field, size), Some(open)) => {
+            let size = value(".field", size, constants)?;
+            let offset = open.size;
+            open.size = offset.checked_add(size).ok_or_else(|| {
+                LineError::DirectiveValue(".field".to_string(), offset as i64 + size as i64, line)
+            })?;
+            insert(constants, format!("{}.{}", open.name, field), offset, line)?;
+        }
+        (Definition::Ends, Some(open)) => {
+            insert(constants, format!("{}.size", open.name), open.size, line)?;
+            *record = None;
+        }
+        (Definition::Enum(name, members), None) => {
+            let mut next = 0;
+            for (member, expr) in members {
+                let member_value = match expr {
+                    Some(e) => value(".enum", e, constants)?,
+                    None => u16::try_from(next).map_err(|_| {
+                        LineError::DirectiveValue(".enum".to_string(), next, line)
+                    })?
+                };
+                insert(constants, format!("{}.{}", name, member), member_value, line)?;
+                next = member_value as i64 + 1;
+            }
+        }
+        (d, _) => return Err(LineError::BadDefinition(d.name().to_string(), line))
+    }
+    Ok(())
+}
+
+// A record still open once every line was read
+pub fn check_closed(record: &Option<OpenRecord>) -> Result<(), LineError> {
+    match record {
+        Some(open) => Err(LineError::UnclosedStruct(open.name.clone(), open.line)),
+        None => Ok(())
+    }
+}
+
+fn insert(constants: &mut HashMap<String, u16>, name: String, value: u16, line: usize)
+-> Result<(), LineError> {
+    match constants.insert(name, value) {
+        Some(_) => Err(LineError::LabelMultiple(line)),
+        None => Ok(())
+    }
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operands::Operand;
+    use crate::parser::{parse_program, parse_source, parse_symbols};
+    use crate::symbols::Section;
+
+    #[test]
+    fn records_and_enums() {
+        let program = parse_source("test.s", ".struct node\n.field value\n.field name 2 * 2\n\
+                                              .field next\n.ends\n\
+                                              .enum color red, green = node.size, blue\n").unwrap();
+        let constant = |name: &str| program.constants[name];
+        assert_eq!([constant("node.value"), constant("node.name"), constant("node.next")],
+                   [0, 1, 5]);
+        assert_eq!(constant("node.size"), 6);
+        assert_eq!([constant("color.red"), constant("color.green"), constant("color.blue")],
+                   [0, 6, 7]);
+    }
+
+    #[test]
+    // Fields are read at their offsets and the list takes three records
+    // [Test File 5]
+    fn records_file5() {
+        let mut program = parse_program("test/file5.s").unwrap();
+        let syms = parse_symbols(&mut program).unwrap();
+        let (_, args, _) = program.instructions().find(|(m, _, _)| *m == "ldr").unwrap();
+        assert_eq!(args[1], Operand::IndirectOffset(2, 0));
+        let (_, args, _) = program.instructions().filter(|(m, _, _)| *m == "strr").nth(1).unwrap();
+        assert_eq!(args[1], Operand::IndirectOffset(2, 1));
+        assert_eq!(program.section_range(Section::Data), Some(syms.labels["list"] as usize..28));
+    }
+
+    #[test]
+    fn definition_errors() {
+        let error = |source: &str| parse_source("test.s", source).unwrap_err();
+        assert!(matches!(error(".field x\n"), LineError::BadDefinition(d, 0) if d == ".field"));
+        assert!(matches!(error(".struct a\n.struct b\n"), LineError::BadDefinition(_, 1)));
+        assert!(matches!(error("halt\n.struct a\n.field x\n"),
+                         LineError::UnclosedStruct(s, 1) if s == "a"));
+        assert!(matches!(error(".struct a\n.field x\n.field x\n.ends\n"),
+                         LineError::LabelMultiple(2)));
+        assert!(matches!(error(".enum e a = -1\n"), LineError::DirectiveValue(_, -1, 0)));
+        assert!(matches!(error(".enum e a = later\n"), LineError::UnknownLabel(_, _, 0)));
+    }
+}
@@ -0,0 +1,59 @@
+use crate::ast::Program;
+
+// ***************************** DEPENDENCIES ****************************** //
+
+// DEPENDENCY RULE: A make rule for the output file with every file it was
+// assembled from as prerequisites (-MD/-MF): the main source, the sources
+// it includes and the files of .incbin, each named once, as they were read
+pub fn dependency_rule(target: &str, program: &Program) -> String {
+    let mut rule = format!("{}:", make_escape(target));
+    for file in source_files(program) {
+        rule.push_str(" \\\n  ");
+        rule.push_str(&make_escape(file));
+    }
+    rule.push('\n');
+    rule
+}
+
+// Every file the program was assembled from, sources first and then the
+// files of .incbin, each named once
+pub fn source_files(program: &Program) -> Vec<&str> {
+    let mut files: Vec<&str> = Vec::new();
+    let sources = (0..program.files.len().max(1)).map(|idx| program.file_name(idx));
+    for file in sources.chain(program.binaries.iter().map(|b| b.as_str())) {
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    files
+}
+
+// Characters make would otherwise read as separators, variables or comments
+fn make_escape(path: &str) -> String {
+    let mut escaped = String::new();
+    for c in path.chars() {
+        match c {
+            ' ' | '#' => escaped.push('\\'),
+            '$' => escaped.push('$'),
+            _ => ()
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program;
+
+    #[test]
+    // [Test File 3] Includes a source twice and a binary file
+    fn dependencies_file3() {
+        let program = parse_program("test/file3.s").unwrap();
+        assert_eq!(dependency_rule("out dir/out.exe", &program),
+                   "out\\ dir/out.exe: \\\n  test/file3.s \\\n  test/include/sum.s \\\n  \
+                    test/include/table.bin\n");
+    }
+}
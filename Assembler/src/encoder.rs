@@ -1,522 +1,248 @@
-use std::collections::HashMap;
-use once_cell::sync::Lazy;
+use isa::{InstrDef, OperandKind, RegUsage, MAX_REG_LIST};
+use isa::registers::REG_ALIASES;
 use crate::symbols::Symbols;
 use crate::err_handler::LineError;
-
-// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
-
-// Instruction Type: Categorizes the types of instructions by the number and 
-// bit sizes of the fields of the instruction.
-// This Enum must be passed as parameter to the encode function to determine
-// how the instruction will be encoded.
-// Please refer to the CPU or Assembler Reference Manuals
-enum InstructionType {
-    T1(u8, u8, u8), 
-    T2(u8, u8, u8, u8), 
-    T3(u8, u16), 
-    T4(u8, u8, u8, u8), // Fifth field unused
-    T5(u8, u8, u8, u8)  // Fifth field unused
-}
-const UNUSED: u8 = 0x00; // Default for the Unused field in InstructionType
-const BYTE: u8 = 8;
-
-// Callback function returned to the parser on mnemonic matches to encode
-// complete instruction as a byte pair
-type EncodeCallback = fn(Vec<String>, &Symbols, usize) -> Result<[u8; 2], LineError>;
-
-// Look-up table for Mnemonics and corresponding encoder function
-pub static MNEMONICS: Lazy<HashMap<&str, EncodeCallback>> = Lazy::new(|| {
-    let mut m = HashMap::new();
-    m.insert("mov", mov as EncodeCallback);
-    m.insert("lda", lda as EncodeCallback);
-    m.insert("ldr", ldr as EncodeCallback);
-    m.insert("stra", stra as EncodeCallback);
-    m.insert("strr", strr as EncodeCallback);
-    m.insert("push", push as EncodeCallback);
-    m.insert("pop", pop as EncodeCallback);
-    m.insert("add", add as EncodeCallback);
-    m.insert("sub", sub as EncodeCallback);
-    m.insert("shl", shl as EncodeCallback);
-    m.insert("shr", shr as EncodeCallback);
-    m.insert("and", and as EncodeCallback);
-    m.insert("or", or as EncodeCallback);
-    m.insert("not", not as EncodeCallback);
-    m.insert("jmp", jmp as EncodeCallback);
-    m.insert("bln", bln as EncodeCallback);
-    m.insert("ret", ret as EncodeCallback);
-    m.insert("cmp", cmp as EncodeCallback);
-    m.insert("beq", beq as EncodeCallback);
-    m.insert("bne", bne as EncodeCallback);
-    m.insert("bgt", bgt as EncodeCallback);
-    m.insert("bgtu", bgtu as EncodeCallback);
-    m.insert("blt", blt as EncodeCallback);
-    m.insert("bltu", bltu as EncodeCallback);
-    m.insert("halt", halt as EncodeCallback);
-    m
-});
-
-// Look-up table for existing Registers and their addresses
-pub static REGISTERS: Lazy<HashMap<&str, u8>> = Lazy::new(|| {
-    let mut m = HashMap::new();
-    m.insert("r0", 0);
-    m.insert("r1", 1);
-    m.insert("r2", 2);
-    m.insert("r3", 3);
-    m.insert("r4", 4);
-    m.insert("r5", 5);
-    m.insert("r6", 6);
-    m.insert("r7", 7);
-    m.insert("fp", 4);
-    m.insert("sp", 5);
-    m.insert("lr", 6);
-    m.insert("mbr", 7);
-    m
-});
+use crate::operands::Operand;
+use crate::suggestions::closest_match;
 
 // ************************ PRIVATE HELPER FUNCTIONS *********************** //
 
-// Encodes the passed in values by their InstructionType as specified
-// by the reference manual.
-fn encode(instr: InstructionType) -> [u8; 2] {
-    let opcode_shift  = 11;
-    let reg_pos0_shift = 8;
-    let reg_pos1_shift = 5;
-    match instr {
-        InstructionType::T1(op,r,c) => {
-            // TODO: Fix shift overflow. Must treat vars first as u16 then cast
-            let msb = {
-                let x = ((op as u16) << opcode_shift) | ((r as u16) << reg_pos0_shift);
-                (x >> BYTE) as u8
-            };
-            [msb, c]
-        }
-        InstructionType::T2(op,rp0,rp1,f1) => {
-            let msb = {
-                let x = ((op as u16) << opcode_shift) | ((rp0 as u16) << reg_pos0_shift); 
-                (x >> BYTE) as u8
-            };
-            let lsb = (((rp1 as u16) << reg_pos1_shift) | (f1 as u16)) as u8;
-            [msb, lsb]
-        }
-        InstructionType::T3(op,f2) => {
-            let instr16bit: u16 = ((op as u16) << opcode_shift) | f2 ;
-            let msb = (instr16bit >> 8) as u8;
-            let lsb = instr16bit as u8;
-            [msb, lsb]
-        }
-        InstructionType::T4(op,rp0,rp1,rp2) => {
-            let reg_pos2_shift = 2;
-            let msb = {
-                let x = ((op as u16) << opcode_shift) | ((rp0 as u16) << reg_pos0_shift);
-                (x >> BYTE) as u8 
-            };
-            let lsb = ((rp1 as u16) << reg_pos1_shift) | ((rp2 as u16) << reg_pos2_shift);
-            [msb, lsb as u8]
-        }
-        InstructionType::T5(op,rp0,rp1,c) => {
-            let imm_shift = 1;
-            let msb = {
-                let x = ((op as u16) << opcode_shift) | ((rp0 as u16) << reg_pos0_shift);
-                (x >> BYTE) as u8 
-            };
-            let lsb = (((rp1 as u16) << reg_pos1_shift) | ((c as u16) << imm_shift)) as u8;
-            [msb, lsb]
-        }
+// Description of what an operand of the given kind must be, used in
+// addressing mode errors
+fn expected_mode(kind: &OperandKind) -> &'static str {
+    match kind {
+        OperandKind::Reg(_) | OperandKind::RegList => "a register",
+        OperandKind::Imm(_) => "an immediate value",
+        OperandKind::Addr(..) => "a register-indirect address such as [r2], [r2, #3] or &r2",
+        OperandKind::Label(_) => "a label"
     }
 }
 
-// Check if a given string matches the name of a valid register
-// in the REGISTER look-up table
-fn get_valid_reg(r: &str, line_num: usize) -> Result<u8, LineError> {
-    match REGISTERS.get(r) {
-        Some(val) => Ok(*val),
-        None => Err(LineError::Unrecognized(String::from(r), line_num))
-    }
+// Whether an operand is written in an addressing mode the operand kind
+// accepts. Names that are not registers parse as labels, so they are
+// accepted where a register is expected and reported as unknown registers
+fn mode_matches(kind: &OperandKind, op: &Operand) -> bool {
+    matches!((kind, op),
+        (OperandKind::Reg(_) | OperandKind::RegList, Operand::Register(_) | Operand::Label(_)) |
+        (OperandKind::Imm(_), Operand::Immediate(_)) |
+        (OperandKind::Addr(..), Operand::Indirect(_) | Operand::IndirectOffset(..)) |
+        (OperandKind::Label(_), Operand::Label(_)))
 }
 
-// Check if a given string can be converted to a valid integer
-fn get_valid_imm(c: &str, line_num: usize) -> Result<u8, LineError> {
-    if c.starts_with("#") {
-        let imm = match c[1..].parse::<i8>() {
-            Ok(val) => Ok(val),
-            Err(_) => Err(LineError::Unrecognized(String::from(c), line_num))
-        }? as u8;
-        return Ok(imm);
-    } else {
-        Err(LineError::StartWithHash(line_num))
+// Kind of the operand at position idx of an instruction definition
+fn kind_at(def: &InstrDef, idx: usize) -> &OperandKind {
+    match def.operands {
+        [OperandKind::RegList] => &OperandKind::RegList,
+        kinds => &kinds[idx]
     }
 }
 
-// Check if a given label exists as valid in symbol table
-fn get_valid_label(label: &str, syms: &Symbols, line_num: usize) 
--> Result<u16, LineError> {
-    match syms.labels.get(label) {
-        Some(l) => Ok(*l),
-        None => Err(LineError::Unrecognized(String::from(label), line_num))
+// Whether an instruction definition takes the given number of operands
+fn takes_args(def: &InstrDef, len: usize) -> bool {
+    match def.operands {
+        [OperandKind::RegList] => (1..=MAX_REG_LIST).contains(&len),
+        kinds => kinds.len() == len
     }
 }
 
-// Calls error handler in incorrect number of arguments for a given operation, 
-// where f can be a function or closure that returns true is the length is valid
-// or false if the length is invalid
-fn check_args_len(f: impl Fn() -> bool, op: &str, line_num: usize) 
--> Result<(), LineError> {
-    if !f() {
-        Err(LineError::WrongArgs(op.to_string(), line_num))
-    } else {
-        Ok(())
+// Builds the error for an operand written in an addressing mode the
+// mnemonic does not accept, listing every mode its variants accept
+fn wrong_mode(defs: &[&InstrDef], idx: usize, found: &Operand, line_num: usize) -> LineError {
+    let mut expected: Vec<&str> = Vec::new();
+    for def in defs {
+        let e = expected_mode(kind_at(def, idx));
+        if !expected.contains(&e) {
+            expected.push(e);
+        }
     }
+    LineError::WrongMode(defs[0].mnemonic.to_string(), idx + 1, expected.join(" or "),
+                         found.mode_name(), line_num)
 }
 
-// ********************* MNEMONIC ASSEMBLING FUNCTIONS ********************* //
-
-// MOV Operation can be one of two variants: Immediate or with Registers
-// The kind of variant is determined here by the type of the second argument
-// Immediate variant is of T1 and Register variant is of T2 with f1: Unused
-pub fn mov(args: Vec<String>, _ : &Symbols, line_num: usize) -> 
-Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 2, "mov", line_num)?;
-
-    let reg_dst = get_valid_reg(&args[0], line_num)?; 
-    
-    // Determine kind of operation
-    if args[1].starts_with("#") {
-        // Operation is Move Immediate
-        let opcode: u8 = 0x00;
-        let constant: u8 = get_valid_imm(&args[1], line_num)?;
-        Ok(encode(InstructionType::T1(opcode, reg_dst, constant)))
-    } else if let Some(val) = REGISTERS.get(&args[1].as_str()) {
-        // Operation is Move with Registers
-        let opcode: u8 = 0x01;
-        let reg_src: u8 = *val;
-        Ok(encode(InstructionType::T2(opcode, reg_dst, reg_src, UNUSED)))
-    } else {
-        // Unrecognized second argument
-        Err(LineError::Unrecognized(args[1].clone(), line_num))
-    }
+// Builds the error for a name used where a register was expected. Names
+// that are not registers parse as labels, so suggest the closest register
+pub fn unknown_register(name: &str, line_num: usize) -> LineError {
+    let hint = closest_match(name, REG_ALIASES.iter().map(|(n, _)| *n));
+    LineError::UnknownRegister(name.to_string(), hint, line_num)
 }
 
-// LDA: Instruction belongs to T3
-pub fn lda(args: Vec<String>, syms: &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 1, "lda", line_num)?;
-    let opcode = 0x02;
-    let label = get_valid_label(&args[0], &syms, line_num)?;
-    Ok(encode(InstructionType::T3(opcode, label)))
-} 
-
-// LDR: Instruction belongs to T2 where f1 is type Offset
-pub fn ldr(args: Vec<String>, _ : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 2 || args.len() == 3 , "ldr", line_num)?;
-
-    let mut offset: u8 = 0; // Default offset to 0
-    let reg_dst = get_valid_reg(&args[0], line_num)?;
-    if args[1].starts_with("&") {
-        // Eliminate the & sign before checking if reg name is valid
-        let reg_adr = get_valid_reg(&args[1][1..], line_num)?;  
-         
-        if args.len() == 3 {
-            offset = get_valid_imm(&args[2], line_num)?;
-        }
-
-        let opcode = 0x03;
-        Ok(encode(InstructionType::T2(opcode, reg_dst, reg_adr, offset)))
-        
+// Check that a value fits in the field given by range and return it as
+// the bits stored in the instruction
+fn check_imm_range(val: i32, range: (i32, i32), line_num: usize)
+-> Result<u16, LineError> {
+    if val < range.0 || val > range.1 {
+        Err(LineError::ImmRange(val, range.0, range.1, line_num))
     } else {
-        Err(LineError::StartWithAmp(line_num))
+        Ok(val as u16)
     }
 }
 
-// STRA: Instruction belongs to T3
-pub fn stra(args: Vec<String>, syms: &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 1, "stra", line_num)?;
-    let opcode = 0x04;
-    let label = get_valid_label(&args[0], &syms, line_num)?;
-    Ok(encode(InstructionType::T3(opcode, label)))
-}
-
-// STRR: Instruction belongs to T2 where f1 is type Offset
-pub fn strr(args: Vec<String>, _ : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 2 || args.len() == 3 , "strr", line_num)?;
-
-    let mut offset: u8 = 0; // Default offset to 0
-    let reg_dst = get_valid_reg(&args[0], line_num)?;
-    if args[1].starts_with("&") {
-        // Eliminate the & sign before checking if reg name is valid
-        let reg_adr = get_valid_reg(&args[1][1..], line_num)?;  
-         
-        if args.len() == 3 {
-            if args[2].starts_with("#") {
-                offset = get_valid_imm(&args[2], line_num)?;
-            } 
+// Check that a label exists in the symbol table and that its address fits
+// in the field given by range, and return the address
+fn get_valid_label(label: &str, range: (i32, i32), syms: &Symbols, line_num: usize)
+-> Result<u16, LineError> {
+    match syms.labels.get(label) {
+        Some(l) if *l as i32 > range.1 => {
+            Err(LineError::LabelRange(label.to_string(), *l, range.1, line_num))
+        }
+        Some(l) => Ok(*l),
+        None => {
+            let hint = closest_match(label, syms.labels.keys().map(|k| k.as_str()));
+            Err(LineError::UnknownLabel(label.to_string(), hint, line_num))
         }
-
-        let opcode = 0x05;
-        Ok(encode(InstructionType::T2(opcode, reg_dst, reg_adr, offset)))
-        
-    } else {
-        Err(LineError::StartWithAmp(line_num))
     }
 }
 
-// PUSH: Instruction belongs to T4 
-pub fn push(args: Vec<String>, _ : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() >=1 && args.len() <= 3 ,"push/pop operation", 
-                                                                line_num)?;
-    let opcode = 0x06;
-    let reg_a = get_valid_reg(&args[0], line_num)?; // Reg A mandatory
-    let mut reg_b = 0; // Reg B defaults to 0
-    let mut reg_c = 0; // Reg C defaults to 0
-
-    match args.len() {
-        2 => reg_b = get_valid_reg(&args[1], line_num)?,
-        3 => {
-            reg_b = get_valid_reg(&args[1], line_num)?;
-            reg_c = get_valid_reg(&args[2], line_num)?;
+// Validates the operand at position idx against the kind the instruction
+// definition expects there and returns its value for the encoder
+fn get_valid_operand(defs: &[&InstrDef], def: &InstrDef, args: &[Operand], idx: usize,
+                     syms: &Symbols, line_num: usize) -> Result<isa::Operand, LineError> {
+    let kind = kind_at(def, idx);
+    match (kind, &args[idx]) {
+        (OperandKind::Reg(_) | OperandKind::RegList, Operand::Register(r)) => {
+            Ok(isa::Operand::Register(*r))
+        }
+        (OperandKind::Reg(_) | OperandKind::RegList, Operand::Label(name)) => {
+            Err(unknown_register(name, line_num))
+        }
+        (OperandKind::Imm(f), Operand::Immediate(val)) => {
+            Ok(isa::Operand::Immediate(check_imm_range(*val, f.range(), line_num)?))
         }
-        _ => ()
+        (OperandKind::Addr(_, _), Operand::Indirect(r)) => Ok(isa::Operand::Indirect(*r, 0)),
+        (OperandKind::Addr(_, o), Operand::IndirectOffset(r, ofst)) => {
+            Ok(isa::Operand::Indirect(*r, check_imm_range(*ofst, o.range(), line_num)?))
+        }
+        (OperandKind::Label(f), Operand::Label(label)) => {
+            Ok(isa::Operand::Address(get_valid_label(label, f.range(), syms, line_num)?))
+        }
+        (_, other) => Err(wrong_mode(defs, idx, other, line_num))
     }
-    Ok(encode(InstructionType::T4(opcode, reg_a, reg_b, reg_c)))
 }
 
-// POP: Instruction belongs to T4 
-// Pop instruction does exactly the same as Push instruction. They only differ
-// by the opcode. Here, we implement by summing 1 to the opcode field of the
-// result given by the encoding of the push instruction
-pub fn pop(args: Vec<String>, _ : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    let mut bytes = push(args, &Symbols::new(), line_num)?;
-    bytes[0] |= 0b0000_1000; // Sum 1 (00110 becomes 00111)
-    Ok(bytes)
-}
-
-// ADD Operation can be one of two variants: Immediate or with Registers
-// The kind of variant is determined here by the type of the third argument
-// Immediate variant is of T2 (f: Constant) and Register variant is of T4
-pub fn add(args: Vec<String>, _ : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 3, "add", line_num)?;
+// ************************* INSTRUCTION ENCODING ************************** //
 
-    let reg_dst = get_valid_reg(&args[0], line_num)?; 
-    let reg_a = get_valid_reg(&args[1], line_num)?;
-
-    // Determine kind of operation
-    if args[2].starts_with("#") {
-        // Operation is Add Immediate
-        let opcode: u8 = 0x08;
-        let constant: u8 = get_valid_imm(&args[2], line_num)?;
-        Ok(encode(InstructionType::T2(opcode, reg_dst, reg_a, constant)))
-    } else if let Some(val) = REGISTERS.get(&args[2].as_str()) {
-        // Operation is Move with Registers
-        let opcode: u8 = 0x09;
-        let reg_b: u8 = *val;
-        Ok(encode(InstructionType::T4(opcode, reg_dst, reg_a, reg_b)))
-    } else {
-        // Unrecognized third argument
-        Err(LineError::Unrecognized(args[2].clone(), line_num))
+// Encodes an instruction into two bytes [msb, lsb]. Mnemonics with more
+// than one variant (mov, add, sub and cmp take an immediate or a register)
+// are resolved by the addressing modes of the operands, and every operand
+// is checked against the field it is stored in. Returns None if the
+// mnemonic does not exist
+pub fn encode_instruction(mnemonic: &str, args: &[Operand], syms: &Symbols, line_num: usize)
+-> Option<Result<[u8; 2], LineError>> {
+    let defs: Vec<&InstrDef> = isa::defs_for(mnemonic).collect();
+    if defs.is_empty() {
+        return None;
     }
+    Some(encode_with(&defs, args, syms, line_num))
 }
 
-// SUB Operation can be one of two variants: Immediate or with Registers
-// The kind of variant is determined here by the type of the third argument
-// Immediate variant is of T2 (f: Constant) and Register variant is of T4
-pub fn sub(args: Vec<String>, _ : &Symbols, line_num: usize) 
+fn encode_with(defs: &[&'static InstrDef], args: &[Operand], syms: &Symbols, line_num: usize)
 -> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 3, "sub", line_num)?;
-
-    let reg_dst = get_valid_reg(&args[0], line_num)?; 
-    let reg_a = get_valid_reg(&args[1], line_num)?;
-
-    // Determine kind of operation
-    if args[2].starts_with("#") {
-        // Operation is Add Immediate
-        let opcode: u8 = 0x0A;
-        let constant: u8 = get_valid_imm(&args[2], line_num)?;
-        Ok(encode(InstructionType::T2(opcode, reg_dst, reg_a, constant)))
-    } else if let Some(val) = REGISTERS.get(&args[2].as_str()) {
-        // Operation is Move with Registers
-        let opcode: u8 = 0x0B;
-        let reg_b: u8 = *val;
-        Ok(encode(InstructionType::T4(opcode, reg_dst, reg_a, reg_b)))
-    } else {
-        // Unrecognized third argument
-        Err(LineError::Unrecognized(args[2].clone(), line_num))
+    let candidates: Vec<&InstrDef> = defs.iter().copied()
+        .filter(|d| takes_args(d, args.len()))
+        .collect();
+    if candidates.is_empty() {
+        return Err(LineError::WrongArgs(defs[0].mnemonic.to_string(), line_num));
     }
-}
 
-// SHL: Instruction of type T5
-pub fn shl(args: Vec<String>, _ : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 3, "shift", line_num)?;
+    // If no variant accepts the addressing modes given, the first variant
+    // reports the operand that does not fit
+    let def = matching_def(&candidates, args).unwrap_or(candidates[0]);
 
-    let reg_dst = get_valid_reg(&args[0], line_num)?; 
-    let reg_src = get_valid_reg(&args[1], line_num)?;
-
-    if args[2].starts_with("#") {
-        // Operation is Add Immediate
-        let opcode: u8 = 0x0C;
-        let constant: u8 = get_valid_imm(&args[2], line_num)?;
-        Ok(encode(InstructionType::T5(opcode, reg_dst, reg_src, constant)))
-    } else {
-        // Immediate does not start with hash sign
-        Err(LineError::StartWithHash(line_num))
+    let mut values = Vec::new();
+    for idx in 0..args.len() {
+        values.push(get_valid_operand(&candidates, def, args, idx, syms, line_num)?);
     }
-}
-
-// SHR: Instruction belongs to T5
-// SHR instruction can be implemented the same as SHR instruction. They only 
-// differ by the opcode. Here, we implement by summing 1 to the opcode field of the
-// result given by the encoding of the shr instruction
-pub fn shr(args: Vec<String>, _ : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    let mut bytes = shl(args, &Symbols::new(), line_num)?;
-    bytes[0] |= 0b0000_1000; // Sum 1 (01100 becomes 01101)
-    Ok(bytes)
-}
-
-// AND: Instruction belongs to T4 
-pub fn and(args: Vec<String>, _ : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 3 ,"logical operation", line_num)?;
-    let opcode = 0x0E;
-    let reg_dst = get_valid_reg(&args[0], line_num)?;
-    let reg_a = get_valid_reg(&args[1], line_num)?; 
-    let reg_b = get_valid_reg(&args[2], line_num)?;
-    Ok(encode(InstructionType::T4(opcode, reg_dst, reg_a, reg_b)))
-}
-
-// OR: Instruction belongs to T4. Implementede by adding one to the opcode
-// field of the encoding result of AND operation
-pub fn or(args: Vec<String>, _ : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    let mut bytes = and(args, &Symbols::new(), line_num)?;
-    bytes[0] |= 0b0000_1000; // Sum 1 (01110 becomes 01111)
-    Ok(bytes)
-}
-
-// NOT: Instruction belongs to T2
-pub fn not(args: Vec<String>, _ : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 2, "not", line_num)?;
-    let opcode = 0x10;
-    let reg_dst = get_valid_reg(&args[0], line_num)?;
-    let reg_src = get_valid_reg(&args[1], line_num)?;
-    Ok(encode(InstructionType::T2(opcode, reg_dst, reg_src, UNUSED)))
-}
-
-// JMP: Instruction belongs to T3
-pub fn jmp(args: Vec<String>, syms : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 1, "jmp", line_num)?;
-    let opcode = 0x11;
-    let label = get_valid_label(&args[0], syms, line_num)?;
-    Ok(encode(InstructionType::T3(opcode, label)))
-}
-
-// BLN: Instruction belongs to T3
-pub fn bln(args: Vec<String>, syms : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 1, "bln", line_num)?;
-    let opcode = 0x12;
-    let label = get_valid_label(&args[0], syms, line_num)?;
-    Ok(encode(InstructionType::T3(opcode, label)))
-}
-
-// RET: Instruction belongs to T3
-pub fn ret(args: Vec<String>, _ : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 0, "ret", line_num)?;
-    let opcode = 0x13;
-    Ok(encode(InstructionType::T3(opcode, UNUSED as u16)))
-}
-
-// CMP: Instruction may be one of two variants.
-// Immediate variant is of T1 and Register variant of T2 (f1: Unused)
-pub fn cmp(args: Vec<String>, _ : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 2, "cmp", line_num)?;
-
-    let reg_a = get_valid_reg(&args[0], line_num)?; 
-    
-    // Determine kind of operation
-    if args[1].starts_with("#") {
-        // Operation is Move Immediate
-        let opcode: u8 = 0x14;
-        let constant: u8 = get_valid_imm(&args[1], line_num)?;
-        Ok(encode(InstructionType::T1(opcode, reg_a, constant)))
-    } else if let Some(val) = REGISTERS.get(&args[1].as_str()) {
-        // Operation is Move with Registers
-        let opcode: u8 = 0x15;
-        let reg_b: u8 = *val;
-        Ok(encode(InstructionType::T2(opcode, reg_a, reg_b, UNUSED)))
-    } else {
-        // Unrecognized second argument
-        Err(LineError::Unrecognized(args[1].clone(), line_num))
+    if def.operands == [OperandKind::RegList] {
+        let regs = values.iter().map(|v| match v {
+            isa::Operand::Register(r) => *r,
+            _ => unreachable!("Register lists only hold registers")
+        }).collect();
+        values = vec![isa::Operand::RegList(regs)];
+    }
+    Ok(isa::encode(def, &values).to_be_bytes())
+}
+
+// First variant whose operand kinds accept every addressing mode given
+fn matching_def(defs: &[&'static InstrDef], args: &[Operand]) -> Option<&'static InstrDef> {
+    defs.iter().copied()
+        .filter(|d| takes_args(d, args.len()))
+        .find(|d| args.iter().enumerate().all(|(i, op)| mode_matches(kind_at(d, i), op)))
+}
+
+// **************************** INSTRUCTION INFO *************************** //
+
+// Variant of a mnemonic an instruction with these operands is encoded as,
+// None if the instruction would not assemble
+pub fn select_def(mnemonic: &str, args: &[Operand]) -> Option<&'static InstrDef> {
+    let defs: Vec<&InstrDef> = isa::defs_for(mnemonic).collect();
+    matching_def(&defs, args)
+}
+
+// Registers an instruction reads and writes, including the ones it uses
+// implicitly. None if the instruction would not assemble
+pub fn reg_usage(mnemonic: &str, args: &[Operand]) -> Option<RegUsage> {
+    let def = select_def(mnemonic, args)?;
+    // Only registers matter here, so other values are left as 0
+    let ops: Vec<isa::Operand> = args.iter().map(|op| match op {
+        Operand::Register(r) => isa::Operand::Register(*r),
+        Operand::Indirect(r) | Operand::IndirectOffset(r, _) => isa::Operand::Indirect(*r, 0),
+        Operand::Immediate(_) => isa::Operand::Immediate(0),
+        Operand::Label(_) => isa::Operand::Address(0)
+    }).collect();
+    let ops = match def.operands {
+        [OperandKind::RegList] => vec![isa::Operand::RegList(ops.iter().map(|o| match o {
+            isa::Operand::Register(r) => *r,
+            _ => unreachable!("Register lists only hold registers")
+        }).collect())],
+        _ => ops
+    };
+    Some(isa::reg_usage(def, &ops))
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operands::parse_operands;
+
+    fn encode(line: &str) -> Result<[u8; 2], LineError> {
+        let (m, args) = line.split_once(' ').unwrap_or((line, ""));
+        encode_instruction(m, &parse_operands(args, 0).unwrap(), &Symbols::new(), 0).unwrap()
     }
-}
-
-// BEQ: Instruction belongs to T3
-pub fn beq(args: Vec<String>, syms : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 1, "beq", line_num)?;
-    let opcode = 0x16;
-    let label = get_valid_label(&args[0], syms, line_num)?;
-    Ok(encode(InstructionType::T3(opcode, label)))
-}
-
-// BNE: Instruction belongs to T3
-pub fn bne(args: Vec<String>, syms : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 1, "bne", line_num)?;
-    let opcode = 0x17;
-    let label = get_valid_label(&args[0], syms, line_num)?;
-    Ok(encode(InstructionType::T3(opcode, label)))
-}
-
-// BGT: Instruction belongs to T3
-pub fn bgt(args: Vec<String>, syms : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 1, "bgt", line_num)?;
-    let opcode = 0x18;
-    let label = get_valid_label(&args[0], syms, line_num)?;
-    Ok(encode(InstructionType::T3(opcode, label)))
-}
 
-// BGTU: Instruction belongs to T3
-pub fn bgtu(args: Vec<String>, syms : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 1, "bgtu", line_num)?;
-    let opcode = 0x19;
-    let label = get_valid_label(&args[0], syms, line_num)?;
-    Ok(encode(InstructionType::T3(opcode, label)))
-}
+    #[test]
+    // The addressing mode of the operands selects the variant of a mnemonic
+    fn variant_selection() {
+        assert_eq!(encode("mov r1 #-1").unwrap(), [0x01, 0xFF]);
+        assert_eq!(encode("mov r1 r2").unwrap(), [0x09, 0x40]);
+        assert_eq!(encode("add r1 r1 #5").unwrap(), [0x41, 0x25]);
+        assert_eq!(encode("add r1 r1 r2").unwrap(), [0x49, 0x28]);
+        assert_eq!(encode("shr r1 r1 #15").unwrap(), [0x69, 0x3E]);
+        assert!(encode_instruction("mvo", &[], &Symbols::new(), 0).is_none());
+    }
 
-// BLT: Instruction belongs to T3
-pub fn blt(args: Vec<String>, syms : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 1, "blt", line_num)?;
-    let opcode = 0x1A;
-    let label = get_valid_label(&args[0], syms, line_num)?;
-    Ok(encode(InstructionType::T3(opcode, label)))
-}
+    #[test]
+    fn operand_errors() {
+        assert!(matches!(encode("mov r1 [r2]"),
+                         Err(LineError::WrongMode(_, 2, e, _, 0))
+                         if e == "an immediate value or a register"));
+        assert!(matches!(encode("mov r1 foo"), Err(LineError::UnknownRegister(..))));
+        assert!(matches!(encode("shl r1 r1 #16"), Err(LineError::ImmRange(16, 0, 15, 0))));
+        assert!(matches!(encode("push r1 r2 r3 r4"), Err(LineError::WrongArgs(..))));
+        assert!(matches!(encode("halt r1"), Err(LineError::WrongArgs(..))));
+    }
 
-// BLTU: Instruction belongs to T3
-pub fn bltu(args: Vec<String>, syms : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 1, "bltu", line_num)?;
-    let opcode = 0x1B;
-    let label = get_valid_label(&args[0], syms, line_num)?;
-    Ok(encode(InstructionType::T3(opcode, label)))
+    #[test]
+    // Label addresses must fit in the 11 bits of the label field rather than
+    // wrap to a lower address
+    fn label_range() {
+        let mut syms = Symbols::new();
+        syms.labels.insert("near".to_string(), 0x7FF);
+        syms.labels.insert("far".to_string(), 0x900);
+        let args = |l: &str| parse_operands(l, 0).unwrap();
+        assert_eq!(encode_instruction("jmp", &args("near"), &syms, 3).unwrap().unwrap(),
+                   [0x8F, 0xFF]);
+        assert!(matches!(encode_instruction("jmp", &args("far"), &syms, 3).unwrap(),
+                         Err(LineError::LabelRange(l, 0x900, 0x7FF, 3)) if l == "far"));
+    }
 }
-
-// HALT: Instruction belongs to T3
-pub fn halt(args: Vec<String>, _ : &Symbols, line_num: usize) 
--> Result<[u8; 2], LineError> {
-    check_args_len(|| args.len() == 0, "halt", line_num)?;
-    let opcode = 0x1C;
-    Ok(encode(InstructionType::T3(opcode, UNUSED as u16)))
-}
\ No newline at end of file
@@ -9,69 +9,212 @@ pub enum LineError {
     SectionMismatch(usize),
     OnlyDataSection,
     NoSectionDecl,
-    StartWithAmp(usize),
     StartWithHash(usize),
     WrongSection(String, usize),
     WrongArgs(String, usize),
     LabelWhitespace(String, usize),
     LabelMoreColon(String, usize),
-    Unrecognized(String, usize)
+    WrongMode(String, usize, String, &'static str, usize),
+    BadOperand(String, usize),
+    ImmRange(i32, i32, i32, usize),
+    LabelRange(String, u16, i32, usize),
+    UnknownMnemonic(String, Option<String>, usize),
+    UnknownRegister(String, Option<String>, usize),
+    UnknownLabel(String, Option<String>, usize),
+    UnknownDirective(String, Option<String>, usize),
+    BadExpression(String, usize),
+    DivisionByZero(usize),
+    DirectiveValue(String, i64, usize),
+    OrgBackwards(u16, u16, usize),
+    AddressOverflow(usize),
+    LayoutCycle(String, usize),
+    IncludeRead(String, String, usize),
+    IncludeCycle(String, usize),
+    BadEscape(String, usize),
+    UnclosedString(String, usize),
+    UnclosedComment(usize),
+    BadDefinition(String, usize),
+    UnclosedStruct(String, usize),
+    Unrecognized(String, usize),
+    FormatChanged(usize),
+    InFile(String, usize, Box<LineError>) // Error on a line of an included file
 }
 
-// Error Handler: Takes a LineError enum and panics while displaying
-// a corresponding message to the screen
-pub fn error_handler(e: &LineError, file_name: &str) -> () {
-    let header = format!("\n{} in file {}\n", "Syntax Error".red().bold(), 
-                        file_name.red());
-    match e {
-        LineError::LabelMultiple(n) => {
-            println!("{}Can not declare multiple labels with the same name\n
-                       Line Number: {}\n", header, n + 1);
-        }
-        LineError::SectionMismatch(n) => {
-            println!("{}Can only declare instructions in Code Section and data \
-                      elements in Data Section\n Line Number: {}\n", header, n + 1);
-        }
-        LineError::OnlyDataSection => {
-            println!("{}Can not assemble program with only a data\
-                        section\n", header);
-        }
-        LineError::NoSectionDecl => {
-            println!("{}Need to declare at least a Code section to\
-                        assemble\n", header);
-        }
-        LineError::StartWithAmp(n) => {
-            println!("{}Registers used as addresses must be prefixed with '{}'\n
-                    Line Number: {}\n", header, "&".bold(), n + 1);
-        }
-        LineError::StartWithHash(n) => {
-            println!("{}Immediate values must be prefixed with '{}'\n
-                        Line Number: {}", header, "#".bold(), n + 1);
-        }
-        LineError::WrongSection(msg, n) => {
-            println!("{}Did not recognize '{}'. Sections may only be {} or {}\n
-                    Line Number: {}\n", 
-                    header, msg.bold(), "code".bold(), "data".bold(), n + 1);
-        }
-        LineError::WrongArgs(msg, n) => {
-            println!("{}Invalid number of arguments in {}\nLine Number: {}",
-                        header, msg.bold(), n + 1);
-        }
-        LineError::LabelWhitespace(msg, n) => {
-            println!("{}Label name must be alone in a line and \
-                        without any whitespaces in between:\n'{}'\n\
-                        Please do not use '{}' if you did not intend to \
-                        declare a label\nLine Number: {}\n", 
-                        header, msg.bold(), ":".red().bold(), n + 1);
-        }
-        LineError::LabelMoreColon(msg, n) => {
-            println!("{}Label name must only contain one '{}' in \
-                        its declaration: '{}'\nLine Number: {}\n", 
-                        header, ":".red().bold(), msg.bold(), n + 1);
+// Formats the optional "did you mean" hint attached to unknown names
+fn did_you_mean(suggestion: &Option<String>, kind: &str) -> String {
+    match suggestion {
+        Some(s) => format!(", did you mean {}'{}'?", kind, s.bold()),
+        None => String::new()
+    }
+}
+
+impl LineError {
+    // Line the error was found on, starting at 0, within the file it was
+    // found in. Errors about the program as a whole have none
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            LineError::OnlyDataSection | LineError::NoSectionDecl => None,
+            LineError::LabelMultiple(n) | LineError::SectionMismatch(n) |
+            LineError::StartWithHash(n) | LineError::WrongSection(_, n) |
+            LineError::WrongArgs(_, n) | LineError::LabelWhitespace(_, n) |
+            LineError::LabelMoreColon(_, n) | LineError::WrongMode(_, _, _, _, n) |
+            LineError::BadOperand(_, n) | LineError::ImmRange(_, _, _, n) |
+            LineError::LabelRange(_, _, _, n) |
+            LineError::UnknownMnemonic(_, _, n) | LineError::UnknownRegister(_, _, n) |
+            LineError::UnknownLabel(_, _, n) | LineError::UnknownDirective(_, _, n) |
+            LineError::BadExpression(_, n) | LineError::DivisionByZero(n) |
+            LineError::DirectiveValue(_, _, n) | LineError::OrgBackwards(_, _, n) |
+            LineError::AddressOverflow(n) | LineError::LayoutCycle(_, n) |
+            LineError::IncludeRead(_, _, n) | LineError::IncludeCycle(_, n) |
+            LineError::BadEscape(_, n) | LineError::UnclosedString(_, n) |
+            LineError::UnclosedComment(n) | LineError::BadDefinition(_, n) |
+            LineError::UnclosedStruct(_, n) | LineError::Unrecognized(_, n) |
+            LineError::FormatChanged(n) | LineError::InFile(_, n, _) => Some(*n)
         }
-        LineError::Unrecognized(msg, n) => {
-            println!("{}Did not recognize '{}'\nLine Number: {}\n", 
-                        header, msg.bold(), n + 1);
+    }
+
+    // Description of the error, without the file and line it was found on
+    pub fn message(&self) -> String {
+        match self {
+            LineError::LabelMultiple(_) => {
+                "Can not declare multiple labels with the same name".to_string()
+            }
+            LineError::SectionMismatch(_) => {
+                "Can only declare instructions in Code Section and data elements in \
+                 Data Section".to_string()
+            }
+            LineError::OnlyDataSection => {
+                "Can not assemble program with only a data section".to_string()
+            }
+            LineError::NoSectionDecl => {
+                "Need to declare at least a Code section to assemble".to_string()
+            }
+            LineError::StartWithHash(_) => {
+                format!("Immediate values must be prefixed with '{}'", "#".bold())
+            }
+            LineError::WrongSection(msg, _) => {
+                format!("Did not recognize '{}'. Sections may only be {} or {}",
+                        msg.bold(), "code".bold(), "data".bold())
+            }
+            LineError::WrongArgs(msg, _) => {
+                format!("Invalid number of arguments in {}", msg.bold())
+            }
+            LineError::LabelWhitespace(msg, _) => {
+                format!("Label name must be alone in a line and without any whitespaces \
+                         in between:\n'{}'\nPlease do not use '{}' if you did not intend \
+                         to declare a label", msg.bold(), ":".red().bold())
+            }
+            LineError::LabelMoreColon(msg, _) => {
+                format!("Label name must only contain one '{}' in its declaration: '{}'",
+                        ":".red().bold(), msg.bold())
+            }
+            LineError::WrongMode(op, pos, expected, found, _) => {
+                format!("Operand {} of '{}' must be {}, but found {}",
+                        pos, op.bold(), expected.bold(), found)
+            }
+            LineError::BadOperand(msg, _) => {
+                format!("Malformed operand '{}'. Operands must be written as \
+                         {}, {}, {}, {} or {}", msg.bold(), "#imm".bold(), "reg".bold(),
+                        "[reg]".bold(), "[reg, #imm]".bold(), "label".bold())
+            }
+            LineError::ImmRange(val, min, max, _) => {
+                format!("Immediate value {} does not fit in the instruction. \
+                         Valid range is {} to {}", val.to_string().bold(), min, max)
+            }
+            LineError::LabelRange(label, addr, max, _) => {
+                format!("Label '{}' at 0x{:03X} is out of reach of the instruction. \
+                         Labels must be at most 0x{:03X}", label.bold(), addr, max)
+            }
+            LineError::UnknownMnemonic(msg, hint, _) => {
+                format!("Did not recognize mnemonic '{}'{}", msg.bold(), did_you_mean(hint, ""))
+            }
+            LineError::UnknownRegister(msg, hint, _) => {
+                format!("Did not recognize register '{}'{}", msg.bold(), did_you_mean(hint, ""))
+            }
+            LineError::UnknownLabel(msg, hint, _) => {
+                format!("Label '{}' was never declared{}", msg.bold(),
+                        did_you_mean(hint, "label "))
+            }
+            LineError::UnknownDirective(msg, hint, _) => {
+                format!("Did not recognize directive '{}'{}", msg.bold(), did_you_mean(hint, ""))
+            }
+            LineError::BadExpression(msg, _) => {
+                format!("Malformed expression '{}'. Expressions are made of numbers, \
+                         labels, {} and parentheses", msg.bold(), "+ - * /".bold())
+            }
+            LineError::DivisionByZero(_) => "Division by zero in expression".to_string(),
+            LineError::DirectiveValue(dir, val, _) => {
+                let valid = match dir.as_str() {
+                    ".align" => "a number of words from 1 to 65535",
+                    ".space" | ".field" => "a number of words from 0 to 65535",
+                    ".enum" => "a value from 0 to 65535",
+                    _        => "an address from 0x0000 to 0xFFFF"
+                };
+                format!("Invalid value {} for '{}', it must be {}",
+                        val.to_string().bold(), dir.bold(), valid)
+            }
+            LineError::OrgBackwards(target, curr, _) => {
+                format!("'{}' can not move back to address 0x{:04X}, the program \
+                         already reached 0x{:04X}", ".org".bold(), target, curr)
+            }
+            LineError::AddressOverflow(_) => {
+                "Program does not fit in the 16-bit address space".to_string()
+            }
+            LineError::LayoutCycle(labels, _) => {
+                format!("Addresses never settle: the size of this line depends on \
+                         '{}', which moves with it", labels.bold())
+            }
+            LineError::IncludeRead(file, err, _) => {
+                format!("Can not read included file '{}': {}", file.bold(), err)
+            }
+            LineError::IncludeCycle(file, _) => {
+                format!("'{}' is already being included, files can not include themselves",
+                        file.bold())
+            }
+            LineError::BadEscape(esc, _) => {
+                format!("Invalid escape sequence '{}'. Strings may use {}, {}, {}, {}, {}, \
+                         {}, {} and {}", esc.bold(), "\\n".bold(), "\\t".bold(),
+                        "\\r".bold(), "\\0".bold(), "\\e".bold(), "\\\\".bold(),
+                        "\\\"".bold(), "\\xHH".bold())
+            }
+            LineError::UnclosedString(msg, _) => {
+                format!("String {} is missing its closing '{}'", msg.bold(), "\"".bold())
+            }
+            LineError::UnclosedComment(_) => {
+                format!("Comment started with '{}' is never closed with '{}'",
+                        "/*".bold(), "*/".bold())
+            }
+            LineError::BadDefinition(dir, _) => {
+                let rule = match dir.as_str() {
+                    ".field" | ".ends" => "may only be used after '.struct'",
+                    _ => "can not be used before the '.ends' of the previous '.struct'"
+                };
+                format!("'{}' {}", dir.bold(), rule)
+            }
+            LineError::UnclosedStruct(name, _) => {
+                format!("Record '{}' is never closed with '{}'", name.bold(), ".ends".bold())
+            }
+            LineError::Unrecognized(msg, _) => format!("Did not recognize '{}'", msg.bold()),
+            LineError::FormatChanged(_) => {
+                "Formatting would change what this line assembles to, so the file was left \
+                 as it is".to_string()
+            }
+            LineError::InFile(_, _, e) => e.message()
         }
     }
-}
\ No newline at end of file
+}
+
+// Error Handler: Takes a LineError enum and displays a corresponding
+// message to the screen
+pub fn error_handler(e: &LineError, file_name: &str) {
+    let file_name = match e {
+        LineError::InFile(file, _, _) => file,
+        _ => file_name
+    };
+    let header = format!("\n{} in file {}\n", "Syntax Error".red().bold(), 
+                        file_name.red());
+    match e.line() {
+        Some(n) => println!("{}{}\nLine Number: {}\n", header, e.message(), n + 1),
+        None => println!("{}{}\n", header, e.message())
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use crate::err_handler::LineError;
+use crate::operands::{is_identifier, parse_number};
+use crate::suggestions::closest_match;
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+// Expression: Arguments of directives such as '.org' may be arithmetic on
+// numbers and labels, e.g. 'table + 2 * (end - start)'. Operators are
+// + - * / with the usual precedence, unary minus and parentheses. Numbers
+// are written like immediates, without the '#'
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(i64),
+    Label(String),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, BinOp, Box<Expr>)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(char)
+}
+
+// ******************************** PARSING ******************************** //
+
+// Parses the text of an expression, reporting it whole if it is malformed
+pub fn parse_expr(text: &str, line_num: usize) -> Result<Expr, LineError> {
+    let bad_expr = || LineError::BadExpression(text.trim().to_string(), line_num);
+    let tokens = tokenize(text).ok_or_else(bad_expr)?;
+    let mut pos = 0;
+    let expr = sum(&tokens, &mut pos).ok_or_else(bad_expr)?;
+    match pos == tokens.len() {
+        true => Ok(expr),
+        false => Err(bad_expr())
+    }
+}
+
+fn tokenize(text: &str) -> Option<Vec<Token>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if "+-*/()".contains(c) {
+            tokens.push(Token::Op(c));
+            i += 1;
+        } else {
+            // Numbers and names run up to the next operator or whitespace
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || "_.".contains(chars[i])) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if word.starts_with(|c: char| c.is_ascii_digit()) {
+                tokens.push(Token::Number(parse_number(&word)? as i64));
+            } else if is_identifier(&word) {
+                tokens.push(Token::Name(word));
+            } else {
+                return None;
+            }
+        }
+    }
+    Some(tokens)
+}
+
+// sum := product (('+' | '-') product)*
+fn sum(tokens: &[Token], pos: &mut usize) -> Option<Expr> {
+    let mut lhs = product(tokens, pos)?;
+    while let Some(Token::Op(c @ ('+' | '-'))) = tokens.get(*pos) {
+        let op = if *c == '+' { BinOp::Add } else { BinOp::Sub };
+        *pos += 1;
+        lhs = Expr::Binary(Box::new(lhs), op, Box::new(product(tokens, pos)?));
+    }
+    Some(lhs)
+}
+
+// product := unary (('*' | '/') unary)*
+fn product(tokens: &[Token], pos: &mut usize) -> Option<Expr> {
+    let mut lhs = unary(tokens, pos)?;
+    while let Some(Token::Op(c @ ('*' | '/'))) = tokens.get(*pos) {
+        let op = if *c == '*' { BinOp::Mul } else { BinOp::Div };
+        *pos += 1;
+        lhs = Expr::Binary(Box::new(lhs), op, Box::new(unary(tokens, pos)?));
+    }
+    Some(lhs)
+}
+
+// unary := '-' unary | number | name | '(' sum ')'
+fn unary(tokens: &[Token], pos: &mut usize) -> Option<Expr> {
+    let token = tokens.get(*pos)?;
+    *pos += 1;
+    match token {
+        Token::Op('-') => Some(Expr::Neg(Box::new(unary(tokens, pos)?))),
+        Token::Number(n) => Some(Expr::Number(*n)),
+        Token::Name(name) => Some(Expr::Label(name.clone())),
+        Token::Op('(') => {
+            let inner = sum(tokens, pos)?;
+            match tokens.get(*pos) {
+                Some(Token::Op(')')) => {
+                    *pos += 1;
+                    Some(inner)
+                }
+                _ => None
+            }
+        }
+        Token::Op(_) => None
+    }
+}
+
+// ******************************* EVALUATION ****************************** //
+
+impl Expr {
+    // Value of the expression with the given label addresses
+    pub fn eval(&self, labels: &HashMap<String, u16>, line_num: usize) -> Result<i64, LineError> {
+        match self {
+            Expr::Number(n) => Ok(*n),
+            Expr::Label(l) => match labels.get(l) {
+                Some(addr) => Ok(*addr as i64),
+                None => {
+                    let hint = closest_match(l, labels.keys().map(|k| k.as_str()));
+                    Err(LineError::UnknownLabel(l.clone(), hint, line_num))
+                }
+            },
+            Expr::Neg(e) => Ok(-e.eval(labels, line_num)?),
+            Expr::Binary(lhs, op, rhs) => {
+                let (a, b) = (lhs.eval(labels, line_num)?, rhs.eval(labels, line_num)?);
+                match op {
+                    BinOp::Add => Ok(a.saturating_add(b)),
+                    BinOp::Sub => Ok(a.saturating_sub(b)),
+                    BinOp::Mul => Ok(a.saturating_mul(b)),
+                    BinOp::Div if b == 0 => Err(LineError::DivisionByZero(line_num)),
+                    BinOp::Div => Ok(a / b)
+                }
+            }
+        }
+    }
+
+    // The expression with every constant replaced by its value. Whatever
+    // is left are labels, known once the program is laid out
+    pub fn with_constants(self, constants: &HashMap<String, u16>) -> Expr {
+        match self {
+            Expr::Label(l) => match constants.get(&l) {
+                Some(value) => Expr::Number(*value as i64),
+                None => Expr::Label(l)
+            },
+            Expr::Neg(e) => Expr::Neg(Box::new(e.with_constants(constants))),
+            Expr::Binary(lhs, op, rhs) => Expr::Binary(Box::new(lhs.with_constants(constants)), op,
+                                                       Box::new(rhs.with_constants(constants))),
+            number => number
+        }
+    }
+
+    // Names of the labels the expression depends on
+    pub fn labels(&self) -> Vec<&str> {
+        match self {
+            Expr::Number(_) => Vec::new(),
+            Expr::Label(l) => vec![l.as_str()],
+            Expr::Neg(e) => e.labels(),
+            Expr::Binary(lhs, _, rhs) => {
+                let mut labels = lhs.labels();
+                labels.extend(rhs.labels());
+                labels
+            }
+        }
+    }
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str) -> Result<i64, LineError> {
+        let labels = HashMap::from([("start".to_string(), 4u16), ("end".to_string(), 10u16)]);
+        parse_expr(text, 0)?.eval(&labels, 0)
+    }
+
+    #[test]
+    fn precedence_and_labels() {
+        assert_eq!(eval("1 + 2 * 3").unwrap(), 7);
+        assert_eq!(eval("(1 + 2) * 3").unwrap(), 9);
+        assert_eq!(eval("end - start - 1").unwrap(), 5);
+        assert_eq!(eval("-0x10 / 4 + 0b11").unwrap(), -1);
+        assert_eq!(parse_expr("2 * (end - start)", 0).unwrap().labels(), ["end", "start"]);
+    }
+
+    #[test]
+    fn malformed_expressions() {
+        assert!(matches!(eval("1 +"), Err(LineError::BadExpression(_, 0))));
+        assert!(matches!(eval("(1 + 2"), Err(LineError::BadExpression(_, 0))));
+        assert!(matches!(eval("3 $ 4"), Err(LineError::BadExpression(_, 0))));
+        assert!(matches!(eval("1 / (end - end)"), Err(LineError::DivisionByZero(0))));
+        assert!(matches!(eval("strat"), Err(LineError::UnknownLabel(_, Some(_), 0))));
+    }
+}
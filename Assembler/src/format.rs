@@ -0,0 +1,220 @@
+use crate::ast::{Definition, Program, Statement};
+use crate::err_handler::LineError;
+use crate::operands::split_operands;
+use crate::lexer::{code_lines, comment_start};
+use crate::parser::parse_source;
+use crate::symbols::Section;
+
+// Indentation of instructions, data, directives and indented comments.
+// Labels and section declarations start at column 1
+const INDENT: &str = "    ";
+
+// Spaces between the longest line of a block and its trailing comments
+const COMMENT_GAP: usize = 2;
+
+// ******************************* FORMATTING ****************************** //
+
+// Formats source text in the canonical style. Every line is first parsed
+// by the assembler, so only source that assembles is formatted, and each
+// statement is written back from the text it was parsed from:
+//     - labels and '.section[code]'/'.section[data]' at column 1
+//     - everything else indented, with lowercase mnemonics and operands
+//       separated by a single space ('[r2, #3]' inside brackets)
+//     - trailing comments of consecutive lines aligned to one column
+//     - no trailing whitespace and at most one blank line in a row
+// Lines with any part of a /* */ comment are kept as they were written
+pub fn format_source(file: &str, text: &str) -> Result<String, LineError> {
+    let program = parse_source(file, text)?;
+    let lexed = code_lines(text, 0)?;
+
+    // Code and trailing comment of every line, and the line of the source
+    // each one comes from
+    let mut lines: Vec<(String, Option<&str>)> = Vec::new();
+    let mut origin: Vec<usize> = Vec::new();
+    let mut nodes = program.nodes.iter().peekable();
+    for (idx, line) in program.source.iter().enumerate() {
+        let text = line.trim();
+        let node = nodes.next_if(|n| n.line == idx);
+        let formatted = match node {
+            _ if lexed[idx].block_comment => (line.trim_end().to_string(), None),
+            Some(node) => format_statement(&node.stmt, text, idx)?,
+            // Whole-line comments keep whether they were indented
+            None if line.starts_with(char::is_whitespace) && !text.is_empty() => {
+                (format!("{}{}", INDENT, text), None)
+            }
+            None => (text.to_string(), None)
+        };
+        if formatted.0.is_empty() && lines.last().is_none_or(|(l, _)| l.is_empty()) {
+            continue;
+        }
+        lines.push(formatted);
+        origin.push(idx);
+    }
+    while lines.last().is_some_and(|(l, _)| l.is_empty()) {
+        lines.pop();
+    }
+
+    let out = align_comments(&lines);
+    check_unchanged(&program, file, &out, &origin)?;
+    Ok(out)
+}
+
+// Checks that the formatted text assembles to the same statements as the
+// program it was formatted from. Returns an error on the line of the
+// source where they first differ, given the source line of every line of
+// the formatted text
+fn check_unchanged(program: &Program, file: &str, out: &str, origin: &[usize])
+-> Result<(), LineError> {
+    let source_line = |line: usize| origin.get(line).copied().unwrap_or(line);
+    let formatted = parse_source(file, out)
+        .map_err(|e| LineError::FormatChanged(e.line().map_or(0, source_line)))?;
+    let mut old = program.nodes.iter();
+    let mut new = formatted.nodes.iter();
+    loop {
+        match (old.next(), new.next()) {
+            (None, None) => return Ok(()),
+            (Some(a), Some(b)) if (&a.stmt, a.section) == (&b.stmt, b.section) => (),
+            (Some(a), _) => return Err(LineError::FormatChanged(a.line)),
+            (None, Some(b)) => return Err(LineError::FormatChanged(source_line(b.line)))
+        }
+    }
+}
+
+// Formatted code of a statement and its trailing comment, from the
+// trimmed text it was parsed from
+fn format_statement<'a>(stmt: &Statement, text: &'a str, line_num: usize)
+-> Result<(String, Option<&'a str>), LineError> {
+    let (code, comment) = split_comment(text);
+    let formatted = match stmt {
+        Statement::Section(Section::Code) => ".section[code]".to_string(),
+        Statement::Section(Section::Data) => ".section[data]".to_string(),
+        Statement::Label(l) => format!("{}:", l),
+        Statement::Data(_) if code.starts_with('"') => format!("{}{}", INDENT, code),
+        Statement::Data(_) if code.starts_with('.') => {
+            let (name, string) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+            format!("{}{} {}", INDENT, name, string.trim())
+        }
+        Statement::Data(_) => {
+            let words: Vec<&str> = code.split(',').map(|w| w.trim()).collect();
+            format!("{}{}", INDENT, words.join(", "))
+        }
+        Statement::Directive(d) => {
+            format!("{}{} {}", INDENT, d.name(), single_spaced(&code[d.name().len()..]))
+        }
+        Statement::Instruction(m, _) => {
+            let args = code.split_once(char::is_whitespace).map_or("", |(_, a)| a);
+            let mut line = format!("{}{}", INDENT, m);
+            for token in split_operands(args, line_num)? {
+                line.push(' ');
+                line.push_str(&format_operand(&token));
+            }
+            line
+        }
+        Statement::Include(f) => format!("{}.include \"{}\"", INDENT, f),
+        Statement::Incbin(f) => format!("{}.incbin \"{}\"", INDENT, f),
+        Statement::Definition(Definition::Enum(name, _)) => {
+            let members = &code[".enum".len()..].trim_start()[name.len()..];
+            let members: Vec<String> = members.split(',')
+                .map(|m| m.split('=').map(single_spaced).collect::<Vec<_>>().join(" = "))
+                .collect();
+            format!("{}.enum {} {}", INDENT, name, members.join(", "))
+        }
+        Statement::Definition(_) => format!("{}{}", INDENT, single_spaced(code))
+    };
+    Ok((formatted, comment))
+}
+
+// Words of the text separated by single spaces
+fn single_spaced(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Bracketed operands are written as '[r2]' or '[r2, #3]'. Register names
+// are case sensitive, so every other operand is kept as written
+fn format_operand(token: &str) -> String {
+    match token.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        Some(inner) => {
+            let parts: Vec<&str> = inner.split(',').map(|p| p.trim()).collect();
+            format!("[{}]", parts.join(", "))
+        }
+        None => token.to_string()
+    }
+}
+
+// Splits a trimmed line into its code and its trailing comment, if any
+fn split_comment(text: &str) -> (&str, Option<&str>) {
+    match comment_start(text) {
+        Some(idx) => (text[..idx].trim_end(), Some(&text[idx..])),
+        None => (text, None)
+    }
+}
+
+// Joins the lines back into text. Trailing comments of consecutive lines
+// start at the same column, right after the longest of those lines
+fn align_comments(lines: &[(String, Option<&str>)]) -> String {
+    let mut out = String::new();
+    let mut block_start = 0;
+    for (i, (code, comment)) in lines.iter().enumerate() {
+        if comment.is_none() {
+            out.push_str(code);
+            out.push('\n');
+            continue;
+        }
+        if i == 0 || lines[i - 1].1.is_none() {
+            block_start = i;
+        }
+        let block_end = lines[i..].iter()
+            .position(|(_, c)| c.is_none())
+            .map_or(lines.len(), |p| i + p);
+        let column = lines[block_start..block_end].iter()
+            .map(|(c, _)| c.len())
+            .max()
+            .unwrap_or(0) + COMMENT_GAP;
+        out.push_str(&format!("{:column$}{}\n", code, comment.unwrap()));
+    }
+    out
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSY: &str = "\n// Header\n.section(code)\nstart:  \n  MOV r1 #0x1F//counter\n\
+                         \tldr r4,[r2 ,#1]   // load\n\n\n  add r1,r1,  #1\n.align   2 *  2 ;four\n\
+                         .enum  kind a,b=  2 *  3 ,c\n\
+                         .section[data]\narr:\n 1,2 , -3\n \"a \\\" // b\"   // quoted\n\n\
+                         /* kept\n   as is */  5,6 ;six\n";
+
+    #[test]
+    fn canonical_style() {
+        assert_eq!(format_source("test.s", MESSY).unwrap(),
+                   "// Header\n.section[code]\nstart:\n    mov r1 #0x1F     //counter\n\
+                    \x20   ldr r4 [r2, #1]  // load\n\n    add r1 r1 #1\n    .align 2 * 2  ;four\n\
+                    \x20   .enum kind a, b = 2 * 3, c\n\
+                    .section[data]\narr:\n    1, 2, -3\n    \"a \\\" // b\"  // quoted\n\n\
+                    /* kept\n   as is */  5,6 ;six\n");
+    }
+
+    #[test]
+    // Formatting formatted source changes nothing
+    fn idempotent() {
+        let once = format_source("test.s", MESSY).unwrap();
+        assert_eq!(format_source("test.s", &once).unwrap(), once);
+        let file1 = std::fs::read_to_string("test/file1.s").unwrap();
+        let once = format_source("test/file1.s", &file1).unwrap();
+        assert_eq!(format_source("test/file1.s", &once).unwrap(), once);
+    }
+
+    #[test]
+    // Output that would assemble differently is an error on the source line
+    fn changed_statements() {
+        let program = parse_source("test.s", ".section[code]\n\nmov r1 #1\nhalt\n").unwrap();
+        let check = |out: &str| check_unchanged(&program, "test.s", out, &[0, 2, 3]);
+        assert!(check(".section[code]\n    mov r1 #1\n    halt\n").is_ok());
+        assert!(matches!(check(".section[code]\n    mov r1 #2\n    halt\n"),
+                         Err(LineError::FormatChanged(2))));
+        assert!(matches!(check(".section[code]\n    mov r1 #1\n    hlt\n"),
+                         Err(LineError::FormatChanged(3))));
+    }
+}
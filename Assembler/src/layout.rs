@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use crate::ast::{Directive, Program, Statement};
+use crate::err_handler::LineError;
+
+// Passes after which addresses that still move are considered a cycle
+const MAX_PASSES: usize = 16;
+
+// Highest address of the 16-bit address space
+const ADDR_MAX: i64 = u16::MAX as i64;
+
+// ***************************** LAYOUT PASSES ***************************** //
+
+// LAYOUT: Assigns an address and a size to every node of the program and
+// returns the address of every label. Directives may depend on labels
+// declared after them, so the layout is repeated with the label addresses
+// of the previous pass until no address moves. Errors are only reported
+// from the pass the addresses settled on, as earlier passes may see
+// labels that are not yet in their place
+pub fn layout(program: &mut Program) -> Result<HashMap<String, u16>, LineError> {
+    // Every label starts at address 0
+    let mut labels: HashMap<String, u16> = program.nodes.iter()
+        .filter_map(|n| match &n.stmt {
+            Statement::Label(l) => Some((l.clone(), 0)),
+            _ => None
+        })
+        .collect();
+
+    let mut sizes: Vec<usize> = Vec::new();
+    for _ in 0..MAX_PASSES {
+        let (next, error) = layout_pass(program, &labels);
+        if next == labels {
+            return match error {
+                Some(e) => Err(e),
+                None => Ok(labels)
+            };
+        }
+        labels = next;
+        sizes = program.nodes.iter().map(|n| n.size).collect();
+    }
+
+    // Report the first directive whose size still changes between passes
+    layout_pass(program, &labels);
+    let moving = program.nodes.iter().zip(&sizes)
+        .find(|(n, size)| matches!(n.stmt, Statement::Directive(_)) && n.size != **size)
+        .or_else(|| program.nodes.iter().zip(&sizes)
+                           .find(|(n, _)| matches!(n.stmt, Statement::Directive(_))))
+        .map(|(n, _)| n)
+        .expect("Only directives change size between passes");
+    let Statement::Directive(d) = &moving.stmt else { unreachable!() };
+    Err(LineError::LayoutCycle(d.expr().labels().join(", "), moving.line))
+}
+
+// A single pass over the program with the given label addresses. Returns
+// the label addresses it found and the first error, if any
+fn layout_pass(program: &mut Program, labels: &HashMap<String, u16>)
+-> (HashMap<String, u16>, Option<LineError>) {
+    let mut next = HashMap::new();
+    let mut error = None;
+    let mut addr: i64 = 0;
+
+    for node in &mut program.nodes {
+        if addr > ADDR_MAX {
+            error.get_or_insert(LineError::AddressOverflow(node.line));
+        }
+        node.addr = addr as u16;
+        // Nothing outside a section is assembled
+        node.size = match (&node.stmt, node.section) {
+            (Statement::Label(l), _) => {
+                next.insert(l.clone(), addr as u16);
+                0
+            }
+            (_, None) => 0,
+            (Statement::Instruction(_, _), _) => 1,
+            (Statement::Data(d), _) => d.len(),
+            (Statement::Directive(d), _) => {
+                match directive_size(d, addr, labels, node.line) {
+                    Ok(size) => size,
+                    Err(e) => {
+                        error.get_or_insert(e);
+                        0
+                    }
+                }
+            }
+            (Statement::Section(_) | Statement::Include(_) | Statement::Incbin(_) |
+             Statement::Definition(_), _) => 0
+        };
+        addr += node.size as i64;
+    }
+    (next, error)
+}
+
+// Number of words a directive takes at address addr
+fn directive_size(d: &Directive, addr: i64, labels: &HashMap<String, u16>, line_num: usize)
+-> Result<usize, LineError> {
+    // The value of a word does not change the layout, so it may use any label
+    if let Directive::Word(_) = d {
+        return Ok(1);
+    }
+    let value = d.expr().eval(labels, line_num)?;
+    let bad_value = || LineError::DirectiveValue(d.name().to_string(), value, line_num);
+    match d {
+        Directive::Org(_) if !(0..=ADDR_MAX).contains(&value) => Err(bad_value()),
+        Directive::Org(_) if value < addr => {
+            Err(LineError::OrgBackwards(value as u16, addr as u16, line_num))
+        }
+        Directive::Org(_) => Ok((value - addr) as usize),
+        Directive::Align(_) if !(1..=ADDR_MAX).contains(&value) => Err(bad_value()),
+        Directive::Align(_) => Ok(((value - addr % value) % value) as usize),
+        Directive::Space(_) if !(0..=ADDR_MAX).contains(&value) => Err(bad_value()),
+        Directive::Space(_) => Ok(value as usize),
+        Directive::Word(_) => unreachable!("Words take one address")
+    }
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+
+    fn layout_of(source: &str) -> Result<HashMap<String, u16>, LineError> {
+        layout(&mut parse_source("test.s", source)?)
+    }
+
+    #[test]
+    fn directives() {
+        let labels = layout_of(".section[code]\nstart:\nhalt\n.align 4\nfour:\nhalt\n\
+                                .org 0x10\nsixteen:\n.section[data]\nbuf:\n.space 3\nend:\n")
+                     .unwrap();
+        assert_eq!(labels["start"], 0);
+        assert_eq!(labels["four"], 4);
+        assert_eq!(labels["sixteen"], 16);
+        assert_eq!(labels["buf"], 16);
+        assert_eq!(labels["end"], 19);
+    }
+
+    #[test]
+    // Sizes that depend on labels declared after them settle over passes
+    fn forward_references() {
+        let labels = layout_of(".section[code]\njmp main\n.space table_end - table\n\
+                                main:\nhalt\n.section[data]\ntable:\n1, 2, 3\ntable_end:\n")
+                     .unwrap();
+        assert_eq!(labels["main"], 4);
+        assert_eq!(labels["table"], 5);
+        assert_eq!(labels["table_end"], 8);
+    }
+
+    #[test]
+    fn layout_errors() {
+        assert!(matches!(layout_of(".section[code]\nhalt\nhalt\n.org 1\n"),
+                         Err(LineError::OrgBackwards(1, 2, 3))));
+        assert!(matches!(layout_of(".section[code]\n.align 0\n"),
+                         Err(LineError::DirectiveValue(_, 0, 1))));
+        assert!(matches!(layout_of(".section[code]\n.space 10 - after\nafter:\n"),
+                         Err(LineError::LayoutCycle(l, 1)) if l == "after"));
+        assert!(matches!(layout_of(".section[code]\n.space missing\n"),
+                         Err(LineError::UnknownLabel(_, _, 1))));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::err_handler::LineError;
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+// Code Line: What is left of a source line once its comments are removed.
+// Block comments are blanked with spaces, so the code keeps its columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeLine {
+    pub code: String,
+    pub block_comment: bool // Part of the line is inside a /* */ comment
+}
+
+// ********************************* LEXER ********************************* //
+
+// COMMENTS: Removes the comments of source text line by line, before the
+// lines are told apart. '//' and ';' comment out the rest of a line, and
+// '/*' everything up to the next '*/', which may be lines further down.
+// Comment characters inside string literals belong to the string. The
+// lines are numbered from first_line, for the error of a block comment
+// that is never closed
+pub fn code_lines(text: &str, first_line: usize) -> Result<Vec<CodeLine>, LineError> {
+    let mut lines = Vec::new();
+    let mut block_start = None;
+    for (idx, line) in text.lines().enumerate() {
+        let mut code = String::with_capacity(line.len());
+        let mut block_comment = block_start.is_some();
+        let (mut in_string, mut escaped, mut skip) = (false, false, false);
+        for (i, c) in line.char_indices() {
+            let rest = &line[i..];
+            if skip {
+                skip = false;
+                code.push(' ');
+            } else if block_start.is_some() {
+                if rest.starts_with("*/") {
+                    block_start = None;
+                    skip = true;
+                }
+                code.push_str(&" ".repeat(c.len_utf8()));
+            } else if in_string {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => in_string = false,
+                    _ => ()
+                }
+                code.push(c);
+            } else if rest.starts_with("/*") {
+                block_start = Some(first_line + idx);
+                block_comment = true;
+                skip = true;
+                code.push(' ');
+            } else if rest.starts_with("//") || c == ';' {
+                break;
+            } else {
+                in_string = c == '"';
+                code.push(c);
+            }
+        }
+        lines.push(CodeLine { code, block_comment });
+    }
+    match block_start {
+        Some(line) => Err(LineError::UnclosedComment(line)),
+        None => Ok(lines)
+    }
+}
+
+// Start of the trailing '//' or ';' comment of a line, if it has one
+pub fn comment_start(line: &str) -> Option<usize> {
+    [find_unquoted(line, "//"), find_unquoted(line, ";")].into_iter().flatten().min()
+}
+
+// First occurrence of a pattern outside the string literals of a line
+pub fn find_unquoted(line: &str, pattern: &str) -> Option<usize> {
+    let (mut in_string, mut escaped) = (false, false);
+    for (idx, c) in line.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => ()
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if line[idx..].starts_with(pattern) {
+            return Some(idx);
+        }
+    }
+    None
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Comments are gone but the code stays in its columns
+    fn removes_comments() {
+        let text = "mov r1 #1 ; one\n/* a: b\n   c */ add r1 r1 #1 // two\n\
+                    \"/* ; //\" /**/ ; \"\nhalt";
+        let code: Vec<_> = code_lines(text, 0).unwrap().into_iter()
+            .map(|l| (l.code, l.block_comment))
+            .collect();
+        assert_eq!(code, [
+            ("mov r1 #1 ".to_string(), false),
+            ("       ".to_string(), true),
+            ("        add r1 r1 #1 ".to_string(), true),
+            ("\"/* ; //\"      ".to_string(), true),
+            ("halt".to_string(), false)
+        ]);
+
+        assert!(matches!(code_lines("halt\n/* a\n/* b", 4), Err(LineError::UnclosedComment(5))));
+        assert_eq!(comment_start("\"a;b\" ; c // d"), Some(6));
+    }
+}
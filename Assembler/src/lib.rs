@@ -0,0 +1,21 @@
+pub mod encoder;
+pub mod ast;
+pub mod expr;
+pub mod layout;
+pub mod parser;
+pub mod lexer;
+pub mod definitions;
+pub mod err_handler;
+pub mod symbols;
+pub mod operands;
+pub mod suggestions;
+pub mod warnings;
+pub mod lint;
+pub mod debug_info;
+pub mod xref;
+pub mod stack;
+pub mod format;
+pub mod peephole;
+pub mod listing;
+pub mod deps;
+pub mod cli;
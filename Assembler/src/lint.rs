@@ -0,0 +1,253 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use isa::{Flow, InstrDef, Opcode, RegUsage};
+use isa::registers::{FP_PTR, LNR_PTR, MBR_PTR, SP_PTR};
+use crate::ast::{Program, Statement};
+use crate::encoder::{reg_usage, select_def};
+use crate::operands::Operand;
+use crate::symbols::{Section, Symbols};
+use crate::warnings::{Warning, WarningConfig};
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+// Calling convention: Arguments and results are passed in r0 and r1,
+// which a function may change freely, as well as mbr. The other registers
+// hold values of the caller, so a function that changes one of them must
+// push it first and pop it before returning. sp is only moved by push and
+// pop (checked by the special-reg warning)
+const CALLER_SAVED: [u8; 3] = [0, 1, MBR_PTR as u8];
+const CALLEE_SAVED: [u8; 3] = [2, 3, FP_PTR as u8];
+
+const SP: u8 = SP_PTR as u8;
+const LR: u8 = LNR_PTR as u8;
+
+// Instruction of the code section with the variant it is encoded as
+struct Instr<'a> {
+    line: usize,
+    def: &'static InstrDef,
+    args: &'a [Operand],
+    usage: RegUsage,
+    target: Option<u16>
+}
+
+type Code<'a> = BTreeMap<u16, Instr<'a>>;
+
+// ****************************** LINT CHECKS ****************************** //
+
+// Checks the conventions of the team on a program that assembles, walking
+// every path of execution from the entry point. Returns the warnings of the
+// enabled categories, sorted by line number
+pub fn lint_program(prog: &Program, syms: &Symbols, entry: u16, config: &WarningConfig)
+-> Vec<Warning> {
+    let code: Code = prog.in_section(Section::Code)
+        .filter_map(|n| match &n.stmt {
+            Statement::Instruction(m, args) => {
+                let instr = Instr {
+                    line: n.line,
+                    def: select_def(m, args)?,
+                    args,
+                    usage: reg_usage(m, args)?,
+                    target: args.iter().find_map(|a| match a {
+                        Operand::Label(l) => syms.labels.get(l).copied(),
+                        _ => None
+                    })
+                };
+                Some((n.addr, instr))
+            }
+            _ => None
+        })
+        .collect();
+
+    let mut warnings = Vec::new();
+    let reachable = reachable(&code, entry);
+    warnings.extend(unreachable_code(&code, &reachable));
+    warnings.extend(uninitialized_reads(&code, entry));
+    let functions: BTreeSet<u16> = reachable.iter()
+        .filter_map(|a| code[a].target.filter(|_| is_call(&code[a])))
+        .collect();
+    for function in functions {
+        let name = function_name(syms, function);
+        warnings.extend(unsaved_lr(&code, function, &name));
+        warnings.extend(callee_saved(&code, function, &name));
+    }
+
+    warnings.retain(|w| config.is_enabled(w.kind()));
+    warnings.sort_by_key(Warning::line);
+    warnings
+}
+
+// Addresses execution may continue at after an instruction, without
+// following calls into the function they call
+fn successors(addr: u16, instr: &Instr) -> Vec<u16> {
+    match (instr.def.flow, instr.target) {
+        (Flow::Return | Flow::Halt, _) => vec![],
+        (Flow::Jump, Some(t)) => vec![t],
+        (Flow::Branch, Some(t)) => vec![t, addr + 1],
+        _ => vec![addr + 1]
+    }
+}
+
+fn is_call(instr: &Instr) -> bool {
+    instr.def.flow == Flow::Call
+}
+
+// Instructions reached from the entry point, following calls
+fn reachable(code: &Code, entry: u16) -> BTreeSet<u16> {
+    let mut reached = BTreeSet::new();
+    let mut pending = vec![entry];
+    while let Some(addr) = pending.pop() {
+        let Some(instr) = code.get(&addr) else { continue };
+        if !reached.insert(addr) {
+            continue;
+        }
+        pending.extend(successors(addr, instr));
+        if let Some(t) = instr.target.filter(|_| is_call(instr)) {
+            pending.push(t);
+        }
+    }
+    reached
+}
+
+// First instruction of every run of instructions that is never reached
+fn unreachable_code(code: &Code, reachable: &BTreeSet<u16>) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let mut prev_reached = true;
+    for (addr, instr) in code {
+        let reached = reachable.contains(addr);
+        if !reached && prev_reached {
+            warnings.push(Warning::Unreachable(instr.line));
+        }
+        prev_reached = reached;
+    }
+    warnings
+}
+
+// Registers read on some path from the entry point before anything was
+// written to them. Functions called may set their results, so after a call
+// the caller-saved registers count as written. sp is left out, as it may
+// only be moved by push and pop
+fn uninitialized_reads(code: &Code, entry: u16) -> Vec<Warning> {
+    // Registers written on every path to each instruction
+    let mut written: HashMap<u16, u8> = HashMap::new();
+    let mut pending = vec![(entry, 1 << SP)];
+    while let Some((addr, regs)) = pending.pop() {
+        let Some(instr) = code.get(&addr) else { continue };
+        let regs = match written.get(&addr) {
+            Some(w) if *w & regs == *w => continue,
+            Some(w) => *w & regs,
+            None => regs
+        };
+        written.insert(addr, regs);
+
+        let mut after = instr.usage.writes.iter().fold(regs, |m, r| m | 1 << r);
+        if is_call(instr) {
+            after = CALLER_SAVED.iter().fold(after, |m, r| m | 1 << r);
+        }
+        pending.extend(successors(addr, instr).into_iter().map(|a| (a, after)));
+    }
+
+    let mut warnings = Vec::new();
+    for (addr, instr) in code {
+        let Some(regs) = written.get(addr) else { continue };
+        for r in &instr.usage.reads {
+            if regs & 1 << r == 0 {
+                warnings.push(Warning::UninitRead(*r, instr.line));
+            }
+        }
+    }
+    warnings
+}
+
+// Calls made by a function before it pushed lr, which the call overwrites
+fn unsaved_lr(code: &Code, function: u16, name: &str) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![(function, false)];
+    while let Some((addr, saved)) = pending.pop() {
+        let Some(instr) = code.get(&addr) else { continue };
+        if !visited.insert((addr, saved)) {
+            continue;
+        }
+        let has_lr = instr.args.contains(&Operand::Register(LR));
+        let saved = match instr.def.opcode {
+            Opcode::Push if has_lr => true,
+            Opcode::Pop if has_lr => false,
+            _ => saved
+        };
+        if is_call(instr) && !saved {
+            warnings.push(Warning::UnsavedLr(name.to_string(), instr.line));
+        }
+        pending.extend(successors(addr, instr).into_iter().map(|a| (a, saved)));
+    }
+    warnings
+}
+
+// Writes of a function to callee-saved registers it never pushes. Pops
+// restore the value of the caller and do not count
+fn callee_saved(code: &Code, function: u16, name: &str) -> Vec<Warning> {
+    let mut body = BTreeSet::new();
+    let mut pending = vec![function];
+    while let Some(addr) = pending.pop() {
+        let Some(instr) = code.get(&addr) else { continue };
+        if body.insert(addr) {
+            pending.extend(successors(addr, instr));
+        }
+    }
+
+    let instrs = || body.iter().map(|a| &code[a]);
+    let pushed: BTreeSet<u8> = instrs()
+        .filter(|i| i.def.opcode == Opcode::Push)
+        .flat_map(|i| i.usage.reads.iter().copied())
+        .collect();
+    let mut warned = BTreeSet::new();
+    instrs()
+        .filter(|i| i.def.opcode != Opcode::Pop)
+        .flat_map(|i| i.usage.writes.iter().map(move |r| (*r, i.line)))
+        .filter(|(r, _)| CALLEE_SAVED.contains(r) && !pushed.contains(r) && warned.insert(*r))
+        .map(|(r, line)| Warning::CalleeSaved(r, name.to_string(), line))
+        .collect()
+}
+
+// First label at the address of a function
+fn function_name(syms: &Symbols, addr: u16) -> String {
+    syms.labels.iter()
+        .filter(|(_, a)| **a == addr)
+        .map(|(l, _)| l.as_str())
+        .min()
+        .map_or(format!("sub_{:03x}", addr), String::from)
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_program, parse_source, parse_symbols};
+    use crate::warnings::WarningKind;
+
+    fn lint(prog: &mut Program) -> Vec<Warning> {
+        let syms = parse_symbols(prog).unwrap();
+        let mut config = WarningConfig::default();
+        WarningKind::ALL.into_iter().for_each(|k| config.enable(k));
+        lint_program(prog, &syms, 0, &config)
+    }
+
+    #[test]
+    fn conventions() {
+        let mut prog = parse_source("test.s", ".section[code]\nmain:\nmov r1 #1\n\
+                                    bln f\nadd r0 r0 r2\nhalt\nmov r0 #0\n\
+                                    f:\nmov r3 #2\nbln g\nret\n\
+                                    g:\npush r2\nmov r2 #0\npop r2\nret\n").unwrap();
+        assert_eq!(lint(&mut prog), vec![
+            Warning::UninitRead(2, 4),
+            Warning::Unreachable(6),
+            Warning::CalleeSaved(3, "f".to_string(), 8),
+            Warning::UnsavedLr("f".to_string(), 9)
+        ]);
+    }
+
+    #[test]
+    // [Test File 1] Follows every convention
+    fn clean_file1() {
+        let mut prog = parse_program("test/file1.s").unwrap();
+        assert!(lint(&mut prog).is_empty());
+    }
+}
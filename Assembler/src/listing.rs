@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use crate::ast::{Program, Statement};
+use crate::peephole::Optimization;
+
+// Words shown on each row of the listing. Longer data lines continue on
+// the rows below
+const WORDS_PER_ROW: usize = 3;
+
+// Width of the WORDS column: the words of a row and the spaces between them
+const WORDS_WIDTH: usize = WORDS_PER_ROW * 5 - 1;
+
+// ******************************** LISTING ******************************** //
+
+// LISTING: Every line of the source next to its line number, its address
+// and the words it was assembled to (--listing), with the lines of
+// included files after those of the main file. Takes the assembled words
+// from address 0. Lines the optimizer removed or changed keep their source
+// text and are followed by what was done to them
+pub fn listing(program: &Program, words: &[u16], optimizations: &[Optimization]) -> String {
+    let nodes: HashMap<usize, _> = program.nodes.iter().map(|n| (n.line, n)).collect();
+    let blank = " ".repeat(6 + 6 + WORDS_WIDTH + 2);
+
+    let mut out = format!("// Listing of {}\n\n", program.file);
+    writeln!(out, "{:6}{:6}{:width$}  SOURCE", "LINE", "ADDR", "WORDS", width = WORDS_WIDTH).unwrap();
+    for (idx, source) in program.source.iter().enumerate() {
+        // Lines of included files follow those of the main file
+        let (file, line) = program.locate(idx);
+        if file > 0 && line == 0 {
+            writeln!(out, "\n// Included file {}", program.file_name(file)).unwrap();
+        }
+        let (addr, assembled) = match nodes.get(&idx) {
+            Some(n) if n.section.is_some() && !matches!(n.stmt, Statement::Section(_)) => {
+                let assembled = match n.stmt {
+                    Statement::Instruction(..) | Statement::Data(_) => {
+                        let start = n.addr as usize;
+                        &words[start.min(words.len())..(start + n.size).min(words.len())]
+                    }
+                    _ => &[]
+                };
+                (format!("{:04X}", n.addr), assembled)
+            }
+            _ => (String::new(), &[][..])
+        };
+
+        let mut rows = assembled.chunks(WORDS_PER_ROW);
+        writeln!(out, "{:<6}{:6}{:width$}  {}", line + 1, addr, hex_words(rows.next().unwrap_or(&[])),
+                 source, width = WORDS_WIDTH).unwrap();
+        let mut row_addr = nodes.get(&idx).map_or(0, |n| n.addr as usize);
+        for row in rows {
+            row_addr += WORDS_PER_ROW;
+            writeln!(out, "{:6}{:04X}  {}", "", row_addr, hex_words(row)).unwrap();
+        }
+        for opt in optimizations.iter().filter(|o| o.line() == idx) {
+            writeln!(out, "{}// ^ {}", blank, opt.message()).unwrap();
+        }
+    }
+
+    if !optimizations.is_empty() {
+        let removed = optimizations.iter().filter(|o| o.removes()).count();
+        writeln!(out, "\n// Optimizer: {} removed, {} changed", removed,
+                 optimizations.len() - removed).unwrap();
+    }
+    out
+}
+
+fn hex_words(words: &[u16]) -> String {
+    words.iter().map(|w| format!("{:04X}", w)).collect::<Vec<_>>().join(" ")
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{assemble_program, parse_source, parse_symbols};
+    use crate::peephole::optimize;
+
+    #[test]
+    fn listing_with_optimizations() {
+        let source = ".section[code]\nstart:\n    mov r1 r1\n    halt\n.section[data]\n    1, 2, 3, 4\n";
+        let mut program = parse_source("test.s", source).unwrap();
+        let mut syms = parse_symbols(&mut program).unwrap();
+        let opts = optimize(&mut program, &mut syms).unwrap();
+        let mut bytes = Vec::new();
+        assemble_program(&program, &syms, &mut bytes).unwrap();
+        let words: Vec<u16> = bytes.chunks(2).map(|p| u16::from_be_bytes([p[0], p[1]])).collect();
+
+        assert_eq!(listing(&program, &words, &opts),
+            "// Listing of test.s\n\n\
+             LINE  ADDR  WORDS           SOURCE\n\
+             1                           .section[code]\n\
+             2     0000                  start:\n\
+             3                               mov r1 r1\n\
+             \x20                           // ^ removed, moves r1 onto itself\n\
+             4     0000  E000                halt\n\
+             5                           .section[data]\n\
+             6     0001  0001 0002 0003      1, 2, 3, 4\n\
+             \x20     0004  0004\n\
+             \n// Optimizer: 1 removed, 0 changed\n");
+    }
+}
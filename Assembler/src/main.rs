@@ -1,33 +1,228 @@
-pub mod encoder;
-pub mod parser;
-pub mod err_handler;
-pub mod symbols;
-use std::fs::remove_file;
-
-use err_handler::error_handler;
-use parser::*;
-
-// TODO: Add command line arguments to specify the names of
-// the input file, flags and name of the output file instead
-// of harcoding the names and paths
+use std::env;
+use std::fs::{read, write};
+use std::process::exit;
+use std::ops::Range;
+use std::path::Path;
+use objformat::{write_image, Image, Section, SectionKind};
+use objformat::debug::DebugInfo;
+use objformat::build::{crc16, source_hash, BuildInfo, BUILD_WORDS};
+
+use assembler::err_handler::error_handler;
+use assembler::parser::*;
+use assembler::symbols::Section as SourceSection;
+use assembler::warnings::{check_program, warning_handler, Warning};
+use assembler::lint::lint_program;
+use assembler::cli::CLI;
+use assembler::debug_info::build_debug_info;
+use assembler::xref::cross_reference;
+use assembler::stack::analyze_stack;
+use assembler::peephole::optimize;
+use assembler::listing::listing;
+use assembler::deps::{dependency_rule, source_files};
+use assembler::ast::Program;
+use assembler::symbols::Symbols;
+
 fn main() {
-    let file = "test/file1.s";
-    let out_file = "out.bin";
+    let cli = match CLI::new(env::args().collect()) {
+        Ok(cli) => cli,
+        Err(msg) => {
+            CLI::print_error(&msg);
+            exit(1);
+        }
+    };
+    let file = cli.file_path.as_str();
+    let out_file = cli.out_file.as_str();
+
+    // The source is read and parsed once. Every pass below works on the
+    // parsed program
+    let mut program = match parse_program(file) {
+        Ok(program) => program,
+        Err(e) => {
+            error_handler(&e, file);
+            exit(1);
+        }
+    };
 
     // First Pass of Assembly Process.
-    // Returns a symbol table for labels and ranges for Code and Data sections
-    // Function panics on syntax errors
-    let symbols = parse_symbols(&file);
-    if let Err(e) = &symbols {
-        error_handler(e, &file);
-        return;
-    }
-    let symbols = symbols.unwrap();
+    // Lays out the program and returns a symbol table for labels and ranges
+    // for Code and Data sections
+    let mut symbols = match parse_symbols(&mut program) {
+        Ok(symbols) => symbols,
+        Err(e) => {
+            error_handler(&program.locate_error(e), file);
+            exit(1);
+        }
+    };
+
+    // Execution starts at the label given with --entry, or at the start
+    // of the code section
+    let entry = entry_point(&cli, &program, &symbols);
 
     // Second Pass of Assembly Process.
-    // Parses instructions and encodes them into an output file
-    if let Err(e) = assemble_program(&file, symbols, out_file) {
-        remove_file(out_file).expect("Could not delete out file after error");
-        error_handler(&e, &file);
+    // Parses instructions and encodes them into the program image
+    let mut image: Vec<u8> = Vec::new();
+    if let Err(e) = assemble_program(&program, &symbols, &mut image) {
+        error_handler(&program.locate_error(e), file);
+        exit(1);
+    }
+
+    // Report suspicious but legal code, and the conventions of the linter
+    // when enabled. With -Werror any warning fails the assembly just like
+    // a syntax error
+    let mut warnings = check_program(&program, &cli.warnings);
+    warnings.extend(lint_program(&program, &symbols, entry, &cli.warnings));
+    warnings.sort_by_key(Warning::line);
+    for w in &warnings {
+        warning_handler(w, &program, cli.warnings.werror);
+    }
+    if cli.warnings.werror && !warnings.is_empty() {
+        exit(1);
+    }
+
+    // Peephole optimization, with -O. Warnings above refer to the source as
+    // written; the optimized program is laid out and encoded once more, so
+    // every output below reflects it
+    let mut optimizations = Vec::new();
+    let entry = match cli.optimize {
+        true => {
+            optimizations = match optimize(&mut program, &mut symbols) {
+                Ok(o) => o,
+                Err(e) => {
+                    error_handler(&program.locate_error(e), file);
+                    exit(1);
+                }
+            };
+            image.clear();
+            if let Err(e) = assemble_program(&program, &symbols, &mut image) {
+                error_handler(&program.locate_error(e), file);
+                exit(1);
+            }
+            entry_point(&cli, &program, &symbols)
+        }
+        false => entry
+    };
+    let symbol_map = symbols.symbol_map();
+    let code = program.section_range(SourceSection::Code).unwrap_or(0..0);
+    let data = program.section_range(SourceSection::Data);
+
+    // Listing of the source with the assembled words, on request. It also
+    // shows what the optimizer changed
+    if let Some(listing_file) = &cli.listing_file {
+        let words: Vec<u16> = image.chunks_exact(2).map(|p| u16::from_be_bytes([p[0], p[1]])).collect();
+        let report = listing(&program, &words, &optimizations);
+        write(listing_file, report).expect("Could not write listing");
+    }
+
+    // Make rule listing every file the output was assembled from, on
+    // request, so build systems assemble again when an included file changes
+    if let Some(dep_file) = &cli.dep_file {
+        write(dep_file, dependency_rule(out_file, &program)).expect("Could not write dependency file");
+    }
+
+    // Source-level debug information, on request
+    let debug_info = cli.debug_info.then(|| build_debug_info(&program, &symbols));
+
+    // Output file in the format requested. The image is only written once
+    // assembly succeeded, so errors never leave a partial file behind.
+    // Stripped executables carry neither symbols nor debug information
+    let (symbol_section, debug) = match cli.strip {
+        true => (None, None),
+        false => (Some(symbol_map.as_str()), debug_info.as_ref())
+    };
+    let mut image = build_image(&image, code, data, entry, symbol_section, debug);
+
+    // Build information, with --build-id, right after the end of the
+    // program, so the stack at the top of memory never reaches it before
+    // the program itself. It is kept when stripping, as it identifies the
+    // program rather than describing it
+    let build_words = if cli.build_id.is_some() { BUILD_WORDS } else { 0 };
+    if let Some(id) = &cli.build_id {
+        let words = image.flatten();
+        if words.len() + BUILD_WORDS > cli.depth {
+            CLI::print_error(&format!("Program of {} words leaves no room for the {} words \
+                                       of build information", words.len(), BUILD_WORDS));
+            exit(1);
+        }
+        let sources: Vec<Vec<u8>> = source_files(&program).into_iter()
+            .map(|f| read(f).expect("Could not read source file"))
+            .collect();
+        let info = BuildInfo {
+            id: id.clone(), source_hash: source_hash(&sources), crc: crc16(&words)
+        };
+        image.sections.push(Section::build(&info, words.len() as u16));
+    }
+    match write_image(&image, cli.format, cli.depth) {
+        Ok(bytes) => write(out_file, bytes).expect("Could not write output file"),
+        Err(e) => {
+            CLI::print_error(&e.to_string());
+            exit(1);
+        }
+    }
+
+    // Symbol map for the disassembler, on request
+    if let Some(map_file) = &cli.map_file {
+        write(map_file, symbol_map).expect("Could not write symbol map");
+    }
+
+    // Cross-reference report of symbols and registers, on request
+    if let Some(xref_file) = &cli.xref_file {
+        let report = cross_reference(&program, &symbols).report(&program);
+        write(xref_file, report).expect("Could not write cross-reference report");
+    }
+
+    // Worst-case stack depth of every function, on request. The stack
+    // grows down from the top of memory towards the end of the program and
+    // its build information
+    if let Some(stack_file) = &cli.stack_file {
+        let end = program.nodes.last().map_or(0, |n| n.addr as usize + n.size) + build_words;
+        let free = cli.depth.saturating_sub(end);
+        let report = analyze_stack(&program, &symbols, entry, free).report(&program);
+        write(stack_file, report).expect("Could not write stack analysis");
+    }
+
+    // Debug information next to the output file, for every format
+    if let Some(info) = &debug_info {
+        let dbg_file = Path::new(out_file).with_extension("dbg");
+        write(dbg_file, info.to_text()).expect("Could not write debug information");
+    }
+}
+
+// *************************** HELPER FUNCTIONS **************************** //
+
+// Address of the label given with --entry, or the start of the code section
+fn entry_point(cli: &CLI, program: &Program, symbols: &Symbols) -> u16 {
+    match &cli.entry {
+        Some(label) => match symbols.labels.get(label) {
+            Some(addr) => *addr,
+            None => {
+                CLI::print_error(&format!("Entry label '{}' does not exist", label));
+                exit(1);
+            }
+        },
+        None => program.section_range(SourceSection::Code).map_or(0, |c| c.start as u16)
+    }
+}
+
+// Splits the assembled words, written from address 0, into the code and
+// data sections at the addresses the layout gave them
+fn build_image(bytes: &[u8], code: Range<usize>, data: Option<Range<usize>>, entry: u16,
+               symbol_map: Option<&str>, debug_info: Option<&DebugInfo>) -> Image {
+    let words: Vec<u16> = bytes.chunks_exact(2)
+        .map(|p| u16::from_be_bytes([p[0], p[1]]))
+        .collect();
+    let section = |kind, range: Range<usize>| {
+        Section::new(kind, range.start as u16, words[range].to_vec())
+    };
+
+    let mut sections = vec![section(SectionKind::Code, code)];
+    if let Some(data) = data.filter(|d| !d.is_empty()) {
+        sections.push(section(SectionKind::Data, data));
+    }
+    if let Some(map) = symbol_map {
+        sections.push(Section::symbols(map));
+    }
+    if let Some(info) = debug_info {
+        sections.push(Section::debug(info));
     }
-}
\ No newline at end of file
+    Image { entry, sections }
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use crate::err_handler::LineError;
+use crate::expr::parse_expr;
+use isa::registers::register_number;
+use crate::encoder::unknown_register;
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+// Operand: Every argument of an instruction is parsed into exactly one of
+// these addressing modes before it reaches the encoder. The accepted
+// syntax for each mode is:
+//     Immediate       #5, #-1, #0x1F, #0b101, #node.next (a constant)
+//     Register        r0..r7, fp, sp, lr, mbr
+//     Indirect        [r2] or &r2
+//     IndirectOffset  [r2, #3] or &r2 #3
+//     Label           arr or &arr (absolute address of a label)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Immediate(i32),
+    Register(u8),
+    Indirect(u8),
+    IndirectOffset(u8, i32),
+    Label(String)
+}
+
+impl Operand {
+    // Human readable name of the addressing mode, used in error messages
+    pub fn mode_name(&self) -> &'static str {
+        match self {
+            Operand::Immediate(_)         => "an immediate value",
+            Operand::Register(_)          => "a register",
+            Operand::Indirect(_)          => "a register-indirect address",
+            Operand::IndirectOffset(_, _) => "a register-indirect address with offset",
+            Operand::Label(_)             => "a label"
+        }
+    }
+}
+
+// ************************** OPERAND PARSING ****************************** //
+
+// Parses the argument list of an instruction (everything after the
+// mnemonic, without comments) into its operands. Operands may be separated
+// by whitespace and/or commas. The legacy form '&r2 #3' is folded into a
+// single IndirectOffset operand so every mnemonic sees the same shape.
+pub fn parse_operands(args: &str, line_num: usize) -> Result<Vec<Operand>, LineError> {
+    parse_operands_with(args, line_num, &HashMap::new())
+}
+
+// Parses an argument list whose immediates may name constants, such as
+// '#node.next'. Immediates are then expressions of numbers and constants
+pub fn parse_operands_with(args: &str, line_num: usize, constants: &HashMap<String, u16>)
+-> Result<Vec<Operand>, LineError> {
+    let mut operands: Vec<Operand> = Vec::new();
+    let mut prev_token = String::new();
+    for token in split_operands(args, line_num)? {
+        let op = operand(&token, line_num, constants)?;
+        match (operands.last(), &op) {
+            // Only '&reg' takes a trailing offset, never '[reg] #3'
+            (Some(Operand::Indirect(r)), Operand::Immediate(ofst))
+                if prev_token.starts_with('&') => {
+                let r = *r;
+                operands.pop();
+                operands.push(Operand::IndirectOffset(r, *ofst));
+            }
+            _ => operands.push(op)
+        }
+        prev_token = token;
+    }
+    Ok(operands)
+}
+
+// Parses a single operand token into its addressing mode
+pub fn parse_operand(token: &str, line_num: usize) -> Result<Operand, LineError> {
+    operand(token, line_num, &HashMap::new())
+}
+
+fn operand(token: &str, line_num: usize, constants: &HashMap<String, u16>)
+-> Result<Operand, LineError> {
+    let bad_operand = || LineError::BadOperand(token.to_string(), line_num);
+
+    if let Some(imm) = token.strip_prefix('#') {
+        // Immediate value, or an expression of constants
+        if let Some(value) = parse_number(imm) {
+            return Ok(Operand::Immediate(value));
+        }
+        let expr = parse_expr(imm, line_num).map_err(|_| bad_operand())?;
+        let value = expr.eval(constants, line_num)?;
+        i32::try_from(value).map(Operand::Immediate).map_err(|_| bad_operand())
+    }
+    else if let Some(inner) = token.strip_prefix('[') {
+        // Bracketed register-indirect, with optional offset
+        let inner = inner.strip_suffix(']').ok_or_else(bad_operand)?;
+        let parts: Vec<&str> = inner.split(',').map(|s| s.trim()).collect();
+        let reg = match register_number(parts[0]) {
+            Some(reg) => reg,
+            None if is_identifier(parts[0]) => {
+                return Err(unknown_register(parts[0], line_num));
+            }
+            None => return Err(bad_operand())
+        };
+        match parts.len() {
+            1 => Ok(Operand::Indirect(reg)),
+            2 => match operand(parts[1], line_num, constants)? {
+                Operand::Immediate(ofst) => Ok(Operand::IndirectOffset(reg, ofst)),
+                _ => Err(LineError::StartWithHash(line_num))
+            },
+            _ => Err(bad_operand())
+        }
+    }
+    else if let Some(name) = token.strip_prefix('&') {
+        // Either a register used as an address or the address of a label
+        match register_number(name) {
+            Some(reg) => Ok(Operand::Indirect(reg)),
+            None if is_identifier(name) => Ok(Operand::Label(name.to_string())),
+            None => Err(bad_operand())
+        }
+    }
+    else if let Some(reg) = register_number(token) {
+        Ok(Operand::Register(reg))
+    }
+    else if token.starts_with(|c: char| c.is_ascii_digit() || c == '-') {
+        // Numbers are only valid as immediates
+        Err(LineError::StartWithHash(line_num))
+    }
+    else if is_identifier(token) {
+        Ok(Operand::Label(token.to_string()))
+    }
+    else {
+        Err(bad_operand())
+    }
+}
+
+// Parses a decimal, hexadecimal (0x) or binary (0b) number with an
+// optional leading minus sign
+pub fn parse_number(s: &str) -> Option<i32> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s)
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i32::from_str_radix(bin, 2).ok()?
+    } else {
+        digits.parse::<i32>().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+// Valid label names start with a letter, '_' or '.', followed by
+// letters, digits, '_' or '.'
+pub fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        }
+        _ => false
+    }
+}
+
+// **************************** HELPER FUNCTIONS **************************** //
+
+// Splits an argument list into operand tokens on whitespace and commas,
+// keeping bracketed operands such as '[r2, #3]' together
+pub(crate) fn split_operands(args: &str, line_num: usize) -> Result<Vec<String>, LineError> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_brackets = false;
+
+    for c in args.chars() {
+        match c {
+            '[' if !in_brackets => {
+                in_brackets = true;
+                current.push(c);
+            }
+            ']' if in_brackets => {
+                in_brackets = false;
+                current.push(c);
+            }
+            c if !in_brackets && (c.is_whitespace() || c == ',') => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c)
+        }
+    }
+    if in_brackets {
+        return Err(LineError::BadOperand(current, line_num));
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addressing_modes() {
+        assert_eq!(parse_operand("#-1", 0).unwrap(), Operand::Immediate(-1));
+        assert_eq!(parse_operand("#0x1F", 0).unwrap(), Operand::Immediate(31));
+        assert_eq!(parse_operand("mbr", 0).unwrap(), Operand::Register(7));
+        assert_eq!(parse_operand("[r2]", 0).unwrap(), Operand::Indirect(2));
+        assert_eq!(parse_operand("&r2", 0).unwrap(), Operand::Indirect(2));
+        assert_eq!(parse_operand("[sp, #3]", 0).unwrap(),
+                   Operand::IndirectOffset(5, 3));
+        assert_eq!(parse_operand("arr", 0).unwrap(), Operand::Label("arr".to_string()));
+        assert_eq!(parse_operand("&arr", 0).unwrap(), Operand::Label("arr".to_string()));
+    }
+
+    #[test]
+    fn operand_lists() {
+        assert_eq!(parse_operands("r4, [r2, #1]", 0).unwrap(),
+                   vec![Operand::Register(4), Operand::IndirectOffset(2, 1)]);
+        assert_eq!(parse_operands("r4 &r2 #1", 0).unwrap(),
+                   vec![Operand::Register(4), Operand::IndirectOffset(2, 1)]);
+        assert_eq!(parse_operands("r3  r3\tr4", 0).unwrap(),
+                   vec![Operand::Register(3), Operand::Register(3), Operand::Register(4)]);
+    }
+
+    #[test]
+    fn malformed_operands() {
+        assert!(matches!(parse_operand("5", 0), Err(LineError::StartWithHash(_))));
+        assert!(matches!(parse_operand("[r2", 0), Err(LineError::BadOperand(_, _))));
+        assert!(matches!(parse_operand("[r9]", 0), Err(LineError::UnknownRegister(_, _, _))));
+        assert!(matches!(parse_operand("[r2, 3]", 0), Err(LineError::StartWithHash(_))));
+        assert!(matches!(parse_operands("r4 [r2", 0), Err(LineError::BadOperand(_, _))));
+    }
+}
@@ -1,236 +1,417 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::fs::{self, read_to_string};
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::CharIndices;
+use std::collections::HashMap;
+use crate::ast::{Directive, Node, Program, SourceFile, Statement};
+use crate::definitions::{check_closed, define, parsed_definition, OpenRecord};
 use crate::err_handler::LineError;
+use crate::expr::{parse_expr, Expr};
+use crate::layout::layout;
+use crate::lexer::{code_lines, find_unquoted};
 use crate::symbols::{Symbols, Section};
+use crate::operands::parse_operands_with;
+use crate::suggestions::closest_match;
 
-// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
-
-// Line Content: Categorizes the kinds of expressions
-// that can be found in a source file. Errors and Sections
-// are further categorized into their own variants
-enum LineContent {
-    Label(String),
-    Instruction(String, Vec<String>),
-    Data(Vec<u8>),
-    Section(Section),
-    NonRelevant
+// *********************** MAIN ASSEMBLING FUNCTIONS *********************** //
+
+// PARSING: Reads the source file once and parses every line into the
+// program nodes the passes below work on. Each node records the section
+// it was declared in. Included files are read too, and their nodes placed
+// right after the .include that names them
+pub fn parse_program(file: &str) -> Result<Program, LineError> {
+    let text = read_to_string(file).expect("Could not open file");
+    let mut program = new_program(file);
+    let path = PathBuf::from(file);
+    let mut includes = vec![(fs::canonicalize(&path).unwrap_or_else(|_| path.clone()), path)];
+    let mut state = ParseState::default();
+    match parse_lines(&mut program, &text, &mut state, Some(&mut includes))
+              .and_then(|_| check_closed(&state.record)) {
+        Ok(()) => Ok(program),
+        Err(e) => Err(program.locate_error(e))
+    }
 }
 
-// *********************** MAIN ASSEMBLING FUNCTIONS *********************** //
+// Parses source text that was already read, as if it came from file.
+// Includes are not read
+pub fn parse_source(file: &str, text: &str) -> Result<Program, LineError> {
+    let mut program = new_program(file);
+    let mut state = ParseState::default();
+    parse_lines(&mut program, text, &mut state, None)?;
+    check_closed(&state.record)?;
+    Ok(program)
+}
 
 // FIRST PASS OF ASSEMBLY PROCESS: Getting all label names and 
 // storing them alongside their address in a symbol table.
 // Returns a Symbol struct containing the symbol table (labels),
-// And the ranges for start and end line of code and data sections
-pub fn parse_symbols(file: &str) -> Result<Symbols, LineError> {
-    let file = File::open(file).expect("Could not open file");
-    let reader = BufReader::new(file);
+// And the ranges for start and end line of code and data sections.
+// The address and size of every node is stored in the program
+pub fn parse_symbols(program: &mut Program) -> Result<Symbols, LineError> {
     let mut symbols = Symbols::new();
-    let mut address = 0x00;
-    let mut line_idx = 0;
-
-    for line in reader.lines() {
-        let line = line.unwrap();
-
-        match parse_line(&line, line_idx)? {
-            // LABELS: Append label to symbol table
-            LineContent::Label(k) => {
-                match symbols.labels.insert(k, address) {
-                    Some(_) => Err(LineError::LabelMultiple(line_idx)),
-                    _       => Ok(())
-                }
+
+    for node in &program.nodes {
+        match &node.stmt {
+            // LABELS: Names must be unique, constants included
+            Statement::Label(k) if symbols.labels.insert(k.clone(), 0).is_some()
+                                   || program.constants.contains_key(k) => {
+                return Err(LineError::LabelMultiple(node.line));
             }
             // SECTION: Determine line ranges for each program section
-            LineContent::Section(s) => Ok(symbols.update_sections(s, line_idx)),
-            // DATA: Increment address by size of data
-            //       Divide by 2 as d is a vec of bytes, and words are 2 bytes
-            LineContent::Data(d) => Ok(address += (d.len()/2) as u16),   
-            // INSTRUCTIONS: Increment address by 1
-            LineContent::Instruction(_,_) => Ok(address += 1),  
-            // Empty lines or comments not relevant to do any action
-            LineContent::NonRelevant => Ok(()),              
-        }?;
-        line_idx += 1;
+            Statement::Section(s) => symbols.update_sections(*s, node.line),
+            _ => ()
+        }
     }
 
     // Check if section declarations are valid and populate 
     // upper bound of one of them
-    symbols.check_sections_valid(line_idx)?;
+    symbols.check_sections_valid(program.source.len())?;
+
+    // LAYOUT: Place every node and store the address of each label
+    symbols.labels = layout(program)?;
 
-    return Ok(symbols);
+    Ok(symbols)
 }
 
 // SECOND PASS OF ASSEMBLY PROCESS: Traverses each section (code and data)
-// line by line. Instructions and data are decoded and written to a binary
-// output file
-pub fn assemble_program(file: &str, syms: Symbols, out_file: &str) 
+// node by node. Instructions and data are encoded and written to out as
+// big-endian words, starting from address 0. Gaps left by directives are
+// written as zeros, so every word is written at its address
+pub fn assemble_program(program: &Program, syms: &Symbols, out_file: &mut impl Write) 
 -> Result<(), LineError> {
-    use super::encoder::MNEMONICS;
-
-    let mut out_file = {
-        let f = File::create(out_file).expect("Could not create file");
-        BufWriter::new(f)
-    };
-    let lines = {
-        let file = File::open(file).expect("Could not open file");
-        BufReader::new(file).lines()
-    };
-
-    // Traverse entire file
-    for (idx, line) in lines.enumerate() {
-        let line = line.unwrap();
-
-        // Assemble Code Section
-        if syms.code_range().is_some() && 
-           syms.code_range().unwrap().contains(&idx) 
-        {
-            match parse_line(&line, idx)? {
-                LineContent::Instruction(m, args) => {
-                    // Check if Mnemonic exists. If not, throw error
-                    match MNEMONICS.get(m.as_str()) {
-                        Some(func) => {
-                            // Encode instruction into two bytes [msb, lsb]
-                            let bytes = func(trim_comments(args), &syms, idx)?;
-                            // Write encoded bytes to output file
-                            out_file.write_all(&bytes).expect("Can not write output file");
-                            Ok(())
-                        }
-                        None => Err(LineError::Unrecognized(m, idx))
+    use super::encoder::encode_instruction;
+
+    let mut addr = 0;
+    for node in &program.nodes {
+        let idx = node.line;
+        if node.section.is_some() {
+            write_zeros(out_file, node.addr as usize - addr);
+            addr = node.addr as usize + node.size;
+        }
+        match (node.section, &node.stmt) {
+            // Assemble Code Section
+            (Some(Section::Code), Statement::Instruction(m, args)) => {
+                // Check if Mnemonic exists. If not, throw error
+                match encode_instruction(m, args, syms, idx) {
+                    Some(bytes) => {
+                        // Encode instruction into two bytes [msb, lsb]
+                        // and write them to the output file
+                        out_file.write_all(&bytes?).expect("Can not write output file");
+                    }
+                    None => {
+                        let hint = closest_match(m, isa::mnemonics());
+                        return Err(LineError::UnknownMnemonic(m.clone(), hint, idx));
                     }
                 }
-                LineContent::Data(_) => Err(LineError::SectionMismatch(idx)),
-                _ => Ok(()) // Only care if line is an instruction
-            }?
-        }
-        // Assemble Data Section
-        else if syms.data_range().is_some() && 
-                syms.data_range().unwrap().contains(&idx) 
-        {
-            match parse_line(&line, idx)? {
-                LineContent::Data(d) => {
-                    out_file.write_all(&d).expect("Can not write output file");
-                    Ok(())
+            }
+            // Assemble Data Section
+            (Some(Section::Data), Statement::Data(d)) => {
+                for word in d {
+                    out_file.write_all(&word.to_be_bytes()).expect("Can not write output file");
                 }
-                LineContent::Instruction(_, _) => {
-                    Err(LineError::SectionMismatch(idx))
+            }
+            (Some(_), Statement::Directive(Directive::Word(e))) => {
+                let value = e.eval(&syms.labels, idx)?;
+                if !(i16::MIN as i64..=u16::MAX as i64).contains(&value) {
+                    return Err(LineError::DirectiveValue(".word".to_string(), value, idx));
                 }
-                _ => Ok(()) // Only care if line is data
-            }?
+                let word = (value as u16).to_be_bytes();
+                out_file.write_all(&word).expect("Can not write output file");
+            }
+            (Some(_), Statement::Directive(_)) => write_zeros(out_file, node.size),
+            (Some(Section::Code), Statement::Data(_)) |
+            (Some(Section::Data), Statement::Instruction(_, _)) => {
+                return Err(LineError::SectionMismatch(idx));
+            }
+            _ => () // Labels and section declarations produce no words
         }
     }
 
     Ok(())
 }
 
-// Parse Line: Takes a single line from the file and determines
-// what kind of line content it is. On instructions, it tokenizes
-// the mnemonic and arguments into a (String, Vec<String>) for 
-// further processing
-fn parse_line(line: &String, line_num: usize) -> Result<LineContent, LineError> {
+fn write_zeros(out_file: &mut impl Write, words: usize) {
+    for _ in 0..words {
+        out_file.write_all(&[0, 0]).expect("Can not write output file");
+    }
+}
+
+// Parse Line: Takes a single line from the file, its comments already
+// removed by the lexer, and determines what kind of statement it is. On
+// instructions, it tokenizes the mnemonic and parses its operands into a
+// (String, Vec<Operand>) for further processing. Constants defined so far
+// are replaced by their values. Empty lines yield no statement
+fn parse_line(line: &str, line_num: usize, constants: &HashMap<String, u16>)
+-> Result<Option<Statement>, LineError> {
     let line = line.trim();
 
-    // Line is either a comment or pure whitespace
-    if line.starts_with("//") || line.is_empty() {
-        Ok(LineContent::NonRelevant)
-    }
-    // Line declares the start of a section
-    else if line.starts_with(".section") {
-        parsed_section(line, line_num)
-    }
-    // Line is declaring Data
-    else if line.starts_with(|x: char| x == '\"' || x.is_ascii_digit()) {
-        parsed_data(line, line_num)
-    }
-    // Line is a label
-    else if line.contains(":") {
-        parsed_label(line, line_num)
-    }
-    // Line is either an instruction or a syntax error
-    else  {
-        parsed_instruction(line, line_num)
+    // Line was either a comment or pure whitespace
+    if line.is_empty() {
+        return Ok(None);
     }
+    let stmt = {
+        // Line declares the start of a section
+        if line.starts_with(".section") {
+            parsed_section(line, line_num)
+        }
+        // Line is declaring Data
+        else if line.starts_with(|x: char| x == '\"' || x.is_ascii_digit()) {
+            parsed_data(line, line_num)
+        }
+        // Line is a label. Colons inside strings do not declare one
+        else if find_unquoted(line, ":").is_some() {
+            parsed_label(line, line_num)
+        }
+        // Line is any other directive
+        else if line.starts_with('.') {
+            parsed_directive(line, line_num, constants)
+        }
+        // Line is either an instruction or a syntax error
+        else  {
+            parsed_instruction(line, line_num, constants)
+        }
+    }?;
+    Ok(Some(stmt))
 }
 
 // **************************** HELPER FUNCTIONS **************************** //
 
-fn trim_comments(args: Vec<String>) -> Vec<String>{
-    let mut v = args;
-    for i in 0..v.len() {
-        if v[i].starts_with("//") {
-            v.truncate(i);
-            break;
+// Files being read while includes are resolved, innermost last, as their
+// canonical path (to find files that include themselves) and the path
+// their includes are relative to
+type IncludeStack = Vec<(PathBuf, PathBuf)>;
+
+// Parse State: What parsing carries from one line to the next, across
+// included files too: the section lines belong to and the record being
+// defined, if any
+#[derive(Default)]
+struct ParseState {
+    section: Option<Section>,
+    record: Option<OpenRecord>
+}
+
+fn new_program(file: &str) -> Program {
+    Program {
+        file: file.to_string(),
+        files: vec![SourceFile { path: file.to_string(), first_line: 0 }],
+        ..Program::default()
+    }
+}
+
+// Parses the lines of one file into nodes at the end of the program, and
+// appends the lines to its source. Parsing continues in the section the
+// lines before left off in, so included files share the section of the
+// .include line (and a section they declare goes on after it). Includes
+// are only read with a stack of the files being read
+fn parse_lines(program: &mut Program, text: &str, state: &mut ParseState,
+               mut includes: Option<&mut IncludeStack>) -> Result<(), LineError> {
+    let first_line = program.source.len();
+    program.source.extend(text.lines().map(String::from));
+
+    for (idx, line) in code_lines(text, first_line)?.into_iter().enumerate() {
+        let line_idx = first_line + idx;
+        if let Some(stmt) = parse_line(&line.code, line_idx, &program.constants)? {
+            if let Statement::Section(s) = stmt {
+                state.section = Some(s);
+            }
+            let column = line.code.len() - line.code.trim_start().len() + 1;
+            let node = Node {
+                stmt, line: line_idx, column, section: state.section, addr: 0, size: 0
+            };
+            program.nodes.push(node);
+            define(program, &mut state.record)?;
+            if let Some(stack) = includes.as_deref_mut() {
+                read_include(program, state, stack)?;
+            }
         }
     }
-    let v = v.iter().filter(|s| s.trim().len() != 0).map(|s| s.to_string()).collect();
-    v
+    Ok(())
 }
 
-fn parsed_section(line: &str, line_num: usize) -> Result<LineContent, LineError> {
+// Reads the file named by the last node of the program, if it is an
+// include. Included sources are parsed right after it, and an .incbin
+// becomes the data of its file
+fn read_include(program: &mut Program, state: &mut ParseState, stack: &mut IncludeStack)
+-> Result<(), LineError> {
+    let node = program.nodes.last_mut().expect("Node was just parsed");
+    let line = node.line;
+    let (name, binary) = match &node.stmt {
+        Statement::Include(name) => (name, false),
+        Statement::Incbin(name) => (name, true),
+        _ => return Ok(())
+    };
+    let path = match stack.last().and_then(|(_, p)| p.parent()) {
+        Some(dir) => dir.join(name),
+        None => PathBuf::from(name)
+    };
+    let display = path.display().to_string();
+    let read_error = |e: std::io::Error| LineError::IncludeRead(display.clone(), e.to_string(), line);
+
+    if binary {
+        let bytes = fs::read(&path).map_err(read_error)?;
+        node.stmt = Statement::Data(bytes.chunks(2)
+            .map(|p| u16::from_be_bytes([p[0], p.get(1).copied().unwrap_or(0)]))
+            .collect());
+        program.binaries.push(display);
+        return Ok(());
+    }
+
+    let text = read_to_string(&path).map_err(read_error)?;
+    let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+    if stack.iter().any(|(c, _)| *c == canonical) {
+        return Err(LineError::IncludeCycle(display, line));
+    }
+    program.files.push(SourceFile { path: display, first_line: program.source.len() });
+    stack.push((canonical, path));
+    parse_lines(program, &text, state, Some(stack))?;
+    stack.pop();
+    Ok(())
+}
+
+fn parsed_section(line: &str, line_num: usize) -> Result<Statement, LineError> {
     if line.contains("Code") || line.contains("code") {
-        Ok(LineContent::Section(Section::Code))
+        Ok(Statement::Section(Section::Code))
     }
     else if line.contains("Data") || line.contains("data") {
-        Ok(LineContent::Section(Section::Data))
+        Ok(Statement::Section(Section::Data))
     }
     else {
         Err(LineError::WrongSection(line.trim().to_string(), line_num))
     } 
 }
 
-// Using the as_bytes() method on a string apparently treats control 
-// characters as two individual bytes. Example '\n' yields [b'\\', 'n']
-// This function finds these two-byte occurrences matching control chars
-// and replaces the first byte with the actual value for the control char
-// and removes the second byte 
-fn replace_control_ascii(s: &mut Vec<u8>) {
-    let mut vec_len = s.len();
-    let mut i = 0;
-    while i < (vec_len-1) {
-        if s[i] == b'\\' && (s[i+1] == b'n' || s[i+1] == b't') {
-            match s[i + 1] {
-                b'n' => s[i] = b'\n',
-                b't' => s[i] = b'\t',
-                _ => ()
-            }
-            s.remove(i+1); // Remove t or n (from newline or tab)
-            vec_len = vec_len - 1;
-        }
-        i += 1;
-    }
-}
-
-fn parsed_data(line: &str, line_num: usize) -> Result<LineContent, LineError> {
+fn parsed_data(line: &str, line_num: usize) -> Result<Statement, LineError> {
     let data: Vec<u16>;
     if line.starts_with("\"") {
-        // Line is a string
-        let mut char_arr = line.trim_matches('\"').as_bytes().to_vec();
-        replace_control_ascii(&mut char_arr);
+        // Line is a string, one character per word
+        let mut char_arr = string_literal(line, line_num)?;
         char_arr.push(0); // Push NULL termination character for string
         data = char_arr.iter().map(|c| *c as u16).collect();
-    } else if line.chars().nth(0).unwrap().is_ascii_digit() {
+    } else if line.chars().next().unwrap().is_ascii_digit() {
         // Line is an array
         data = line.split(',')
                     .map(|s| match s.trim().parse::<i16>() {
-                        Ok(v) => v,
-                        Err(e) => {println!("{} line_number: {}", e, line_num); 0},
-                    }).map(|c| c as u16).collect();
+                        Ok(v) => Ok(v as u16),
+                        Err(_) => Err(LineError::Unrecognized(s.trim().to_string(), line_num))
+                    }).collect::<Result<_, _>>()?;
     } else {
         return Err(LineError::Unrecognized(line.to_string(), line_num));
     }
 
-    // Convert the 16 bit data vectors to an 8-bit data vector with
-    // their msb and lsb
-    let byte_size: u8 = 8;
-    let mut new_data: Vec<u8> = Vec::new();
-    for i in 0..data.len() {
-        new_data.push((data[i] >> byte_size) as u8); // msb
-        new_data.push(data[i] as u8); // lsb
+    Ok(Statement::Data(data))
+}
+
+// String Literal: Characters of a literal between double quotes, with
+// its escape sequences replaced by the bytes they stand for. Only
+// whitespace may follow the closing quote
+fn string_literal(text: &str, line_num: usize) -> Result<Vec<u8>, LineError> {
+    let text = text.trim();
+    let Some(body) = text.strip_prefix('"') else {
+        return Err(LineError::Unrecognized(text.to_string(), line_num));
+    };
+    let mut bytes = Vec::new();
+    let mut chars = body.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => {
+                let rest = body[idx + 1..].trim();
+                return match rest.is_empty() {
+                    true => Ok(bytes),
+                    false => Err(LineError::Unrecognized(rest.to_string(), line_num))
+                };
+            }
+            '\\' => bytes.push(escape_byte(&mut chars, line_num)?),
+            _ => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes())
+        }
     }
+    Err(LineError::UnclosedString(text.to_string(), line_num))
+}
 
-    return Ok(LineContent::Data(new_data));
+// Byte of the escape sequence after a backslash: \n \t \r \0 \e (escape),
+// \\, \" or \x followed by two hex digits
+fn escape_byte(chars: &mut CharIndices, line_num: usize) -> Result<u8, LineError> {
+    let byte = match chars.next().map(|(_, c)| c) {
+        Some('n')  => b'\n',
+        Some('t')  => b'\t',
+        Some('r')  => b'\r',
+        Some('0')  => 0,
+        Some('e')  => 0x1B,
+        Some('\\') => b'\\',
+        Some('"')  => b'"',
+        Some('x')  => {
+            let hex: String = chars.by_ref().take(2).map(|(_, c)| c).collect();
+            match hex.len() == 2 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                true => u8::from_str_radix(&hex, 16).expect("Two hex digits fit a byte"),
+                false => return Err(LineError::BadEscape(format!("\\x{}", hex), line_num))
+            }
+        }
+        Some(c) => return Err(LineError::BadEscape(format!("\\{}", c), line_num)),
+        None => return Err(LineError::BadEscape("\\".to_string(), line_num))
+    };
+    Ok(byte)
+}
+
+// Packed strings store two characters per word, ended by a zero byte.
+// .packed puts the first character of each pair in the high byte and
+// .packed_le in the low byte
+fn parsed_packed(name: &str, args: &str, line_num: usize) -> Result<Statement, LineError> {
+    let args = args.trim();
+    if args.is_empty() {
+        return Err(LineError::WrongArgs(name.to_string(), line_num));
+    }
+    let mut bytes = string_literal(args, line_num)?;
+    bytes.push(0);
+    let words = bytes.chunks(2)
+        .map(|pair| {
+            let (first, second) = (pair[0], pair.get(1).copied().unwrap_or(0));
+            match name {
+                ".packed" => u16::from_be_bytes([first, second]),
+                _ => u16::from_le_bytes([first, second])
+            }
+        })
+        .collect();
+    Ok(Statement::Data(words))
 }
 
-fn parsed_label(line: &str, line_num: usize) -> Result<LineContent, LineError> {
+fn parsed_directive(line: &str, line_num: usize, constants: &HashMap<String, u16>)
+-> Result<Statement, LineError> {
+    let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let directive: fn(Expr) -> Directive = match name {
+        ".org"   => Directive::Org,
+        ".align" => Directive::Align,
+        ".space" => Directive::Space,
+        ".word"  => Directive::Word,
+        ".include" | ".incbin" => return parsed_include(name, args, line_num),
+        ".packed" | ".packed_le" => return parsed_packed(name, args, line_num),
+        ".struct" | ".field" | ".ends" | ".enum" => return parsed_definition(name, args, line_num),
+        _ => {
+            let hint = closest_match(name, Directive::NAMES);
+            return Err(LineError::UnknownDirective(name.to_string(), hint, line_num));
+        }
+    };
+    if args.trim().is_empty() {
+        return Err(LineError::WrongArgs(name.to_string(), line_num));
+    }
+    Ok(Statement::Directive(directive(parse_expr(args, line_num)?.with_constants(constants))))
+}
+
+// Includes take the name of a file in double quotes
+fn parsed_include(name: &str, args: &str, line_num: usize) -> Result<Statement, LineError> {
+    let args = args.trim();
+    if args.is_empty() {
+        return Err(LineError::WrongArgs(name.to_string(), line_num));
+    }
+    let file = match String::from_utf8(string_literal(args, line_num)?) {
+        Ok(f) if !f.is_empty() => f,
+        _ => return Err(LineError::Unrecognized(args.to_string(), line_num))
+    };
+    Ok(match name {
+        ".include" => Statement::Include(file),
+        _ => Statement::Incbin(file)
+    })
+}
+
+fn parsed_label(line: &str, line_num: usize) -> Result<Statement, LineError> {
     if line.matches(":").count() > 1 {
         Err(LineError::LabelMoreColon(line.trim().to_string(), line_num))
     }
@@ -240,30 +421,63 @@ fn parsed_label(line: &str, line_num: usize) -> Result<LineContent, LineError> {
         if l.contains(" ") || l.contains("\t") {
             Err(LineError::LabelWhitespace(l, line_num))
         } else {
-            Ok(LineContent::Label(l))
+            Ok(Statement::Label(l))
         }
     }
 }
 
-fn parsed_instruction(line: &str, line_num: usize) -> Result<LineContent, LineError> {
-    let mut instr: Vec<_> = line.split(" ").map(|s| s.to_string()).collect();
-    if instr.len() >= 1 && instr[0].is_ascii() {
-        let mnemonic = instr.remove(0).to_lowercase().to_string();
-        let params = instr;
-        return Ok(LineContent::Instruction(mnemonic, params));
+fn parsed_instruction(line: &str, line_num: usize, constants: &HashMap<String, u16>)
+-> Result<Statement, LineError> {
+    let (mnemonic, args) = match line.split_once(char::is_whitespace) {
+        Some((m, a)) => (m, a),
+        None => (line, "")
+    };
+    if mnemonic.is_ascii() {
+        let operands = parse_operands_with(args, line_num, constants)?;
+        Ok(Statement::Instruction(mnemonic.to_lowercase(), operands))
     } else {
         // Unrecognized string pattern
-        return Err(LineError::Unrecognized(line.trim().to_string(), line_num));
+        Err(LineError::Unrecognized(line.trim().to_string(), line_num))
     } 
 }
 
 // ***************************** TESTING MODULE ***************************** //
 #[cfg(test)]
 mod tests {
-    use std::{io::Read, fs::remove_file};
+    use std::{io::{BufReader, BufWriter, Read}, fs::{File, remove_file}};
+    use crate::operands::parse_operands;
     use std::collections::HashMap;
     use super::*;
 
+    // Statement of a single line, comments removed
+    fn parsed(line: &str) -> Result<Option<Statement>, LineError> {
+        Ok(parse_source("test.s", line)?.nodes.first().map(|n| n.stmt.clone()))
+    }
+
+    fn symbols_of(file: &str) -> Symbols {
+        parse_symbols(&mut parse_program(file).unwrap()).unwrap()
+    }
+
+    #[test]
+    // The source is parsed once into nodes tagged with their section
+    // [Test File 1]
+    fn program_file1() {
+        let mut program = parse_program("test/file1.s").unwrap();
+        parse_symbols(&mut program).unwrap();
+        assert_eq!(program.nodes[0], Node {
+            stmt: Statement::Section(Section::Code), line: 1, column: 1,
+            section: Some(Section::Code), addr: 0, size: 0
+        });
+        assert_eq!(program.nodes[2].stmt,
+                   Statement::Instruction("mov".to_string(), parse_operands("r1 #0", 3).unwrap()));
+        assert_eq!(program.nodes[2].column, 5);
+        assert_eq!(program.section_range(Section::Code), Some(0..13));
+        assert_eq!(program.section_range(Section::Data), Some(13..18));
+        assert_eq!(program.entry_label(), Some("start"));
+        let data: Vec<&Node> = program.in_section(Section::Data).collect();
+        assert_eq!(data[2].stmt, Statement::Data(vec![17, 22, 0xFFFF, 4, 38]));
+    }
+
     #[test]
     // Tests only for labels and nothing else [Test File 1]
     fn labels_file1() {
@@ -278,7 +492,7 @@ mod tests {
             data_section: (None, None)
         };
         assert_eq!(compare_symbols.labels, 
-                   parse_symbols("test/file1.s").unwrap().labels);
+                   symbols_of("test/file1.s").labels);
     }
 
     #[test]
@@ -293,7 +507,7 @@ mod tests {
             data_section: (None, None)
         };
         assert_eq!(compare_symbols.labels, 
-                   parse_symbols("test/file2.s").unwrap().labels);
+                   symbols_of("test/file2.s").labels);
     }
 
     #[test]
@@ -301,11 +515,117 @@ mod tests {
     fn sections_file1() {
         let code_sec = 1..21;
         let data_sec = 21..49;
-        let symbols = parse_symbols("test/file1.s").unwrap();
+        let symbols = symbols_of("test/file1.s");
         assert_eq!(code_sec, symbols.code_range().unwrap());
         assert_eq!(data_sec, symbols.data_range().unwrap());
     }
 
+    #[test]
+    // Included lines are placed at the .include, numbered after the main
+    // file, and errors in them name their file [Test File 3]
+    fn includes_file3() {
+        let mut program = parse_program("test/file3.s").unwrap();
+        let symbols = parse_symbols(&mut program).unwrap();
+        assert_eq!(program.files[1], SourceFile {
+            path: "test/include/sum.s".to_string(), first_line: 14
+        });
+        assert_eq!(program.binaries, ["test/include/table.bin"]);
+        assert_eq!(symbols.labels["sum"], 5);
+        assert_eq!(symbols.labels["table"], 14);
+        assert_eq!(program.nodes.last().unwrap().stmt, Statement::Data(vec![5, 7, 0x0100]));
+        assert_eq!(program.locate(label_line(&program, "sum_end")), (1, 11));
+
+        let dir = std::env::temp_dir().join("include_error");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.s"), ".section[code]\n.include \"bad.s\"\n").unwrap();
+        std::fs::write(dir.join("bad.s"), "halt\nmov r1 #\n.include \"main.s\"\n").unwrap();
+        let error = parse_program(dir.join("main.s").to_str().unwrap()).unwrap_err();
+        assert!(matches!(&error, LineError::InFile(f, 1, e)
+                         if f.ends_with("bad.s") && matches!(**e, LineError::BadOperand(_, 3))));
+        std::fs::write(dir.join("bad.s"), "halt\n.include \"main.s\"\n").unwrap();
+        let error = parse_program(dir.join("main.s").to_str().unwrap()).unwrap_err();
+        assert!(matches!(&error, LineError::InFile(_, 1, e) if matches!(**e, LineError::IncludeCycle(..))));
+    }
+
+    #[test]
+    // Packed strings hold two characters per word in either byte order and
+    // end in a zero byte [Test File 4]
+    fn packed_strings_file4() {
+        let program = parse_program("test/file4.s").unwrap();
+        let data: Vec<_> = program.nodes.iter().rev().take(3).map(|n| n.stmt.clone()).collect();
+        assert_eq!(data[0], Statement::Data(vec![0x6948, 0x0021]));
+        assert_eq!(data[2], Statement::Data(vec![0x4865, 0x6C6C, 0x6F00]));
+
+        assert_eq!(parsed(".packed \"Hi\" // two words").unwrap(),
+                   Some(Statement::Data(vec![0x4869, 0x0000])));
+        assert!(matches!(parsed_directive(".packed_le Hi", 0, &HashMap::new()),
+                         Err(LineError::Unrecognized(..))));
+    }
+
+    #[test]
+    // Escapes stand for single bytes, and commas, colons and slashes are
+    // just characters inside a string
+    fn string_escapes() {
+        let words = |bytes: &[u8]| Some(Statement::Data(bytes.iter().map(|b| *b as u16).collect()));
+        assert_eq!(parsed(r#""a\\b\"c\0\r\x41\e, x: y // z" // comment"#).unwrap(),
+                   words(b"a\\b\"c\0\rA\x1B, x: y // z\0"));
+        assert_eq!(parsed("\"\"").unwrap(), words(b"\0"));
+        assert_eq!(parsed(".packed \"a:\\tb\"").unwrap(),
+                   Some(Statement::Data(vec![0x613A, 0x0962, 0x0000])));
+
+        assert!(matches!(parsed(r#""\q""#), Err(LineError::BadEscape(e, 0)) if e == "\\q"));
+        assert!(matches!(parsed(r#""\xG1""#), Err(LineError::BadEscape(e, 0)) if e == "\\xG1"));
+        assert!(matches!(parsed(r#""abc\""#), Err(LineError::UnclosedString(..))));
+        assert!(matches!(parsed(r#""ab" "c""#), Err(LineError::Unrecognized(..))));
+    }
+
+    fn label_line(program: &Program, label: &str) -> usize {
+        program.labels().find(|(l, _)| *l == label).unwrap().1
+    }
+
+    #[test]
+    // Operands in the wrong addressing mode are rejected per mnemonic
+    fn wrong_addressing_mode() {
+        use crate::encoder::encode_instruction;
+        let syms = Symbols::new();
+        let ldr = |args| encode_instruction("ldr", &parse_operands(args, 0).unwrap(), &syms, 0);
+        assert!(matches!(ldr("r4 r2"), Some(Err(LineError::WrongMode(_, 2, _, _, 0)))));
+
+        let args = parse_operands("r1, r1, #32", 0).unwrap();
+        assert!(matches!(encode_instruction("add", &args, &syms, 0),
+                         Some(Err(LineError::ImmRange(32, 0, 31, 0)))));
+
+        assert_eq!(ldr("r4, [r2, #1]").unwrap().unwrap(), [0x1C, 0x41]);
+    }
+
+    #[test]
+    // Push and pop encode how many registers they were given
+    fn push_register_count() {
+        use crate::encoder::encode_instruction;
+        let syms = Symbols::new();
+        let encode = |m, args| {
+            encode_instruction(m, &parse_operands(args, 0).unwrap(), &syms, 0).unwrap().unwrap()
+        };
+        assert_eq!(encode("push", "r1 r2"), [0x31, 0x42]);
+        assert_eq!(encode("pop", "lr"), [0x3E, 0x01]);
+    }
+
+    #[test]
+    // Words are placed as given, in code too, and may use later labels
+    fn word_directive() {
+        let assembled = |source: &str| {
+            let mut program = parse_source("test.s", source)?;
+            let symbols = parse_symbols(&mut program)?;
+            let mut bytes = Vec::new();
+            assemble_program(&program, &symbols, &mut bytes).map(|_| bytes)
+        };
+        assert_eq!(assembled(".section[code]\n.word end\n.word 0xF800\n.word -1\nend:\nhalt\n")
+                       .unwrap(),
+                   [0x00, 0x03, 0xF8, 0x00, 0xFF, 0xFF, 0xE0, 0x00]);
+        assert!(matches!(assembled(".section[code]\n.word 0x10000\n"),
+                         Err(LineError::DirectiveValue(_, 0x10000, 1))));
+    }
+
     #[test]
     fn assemble_test1() {
         match compare_files("test/file1.s", "test/file1.bin") {
@@ -316,10 +636,13 @@ mod tests {
 
     fn compare_files(ref_asm_path: &str, ref_bin_path: &str) -> Result<(), ()> {
         let result_bin_name = &format!("{}_test.bin", ref_bin_path);
-        let symbols = parse_symbols(&ref_asm_path).unwrap();
-        if let Err(_) = assemble_program(&ref_asm_path, symbols, &result_bin_name) {
+        let mut program = parse_program(ref_asm_path).unwrap();
+        let symbols = parse_symbols(&mut program).unwrap();
+        let mut out_file = BufWriter::new(File::create(result_bin_name).unwrap());
+        if assemble_program(&program, &symbols, &mut out_file).is_err() {
             panic!();
         }
+        drop(out_file); // Flush before comparing
 
         let f_ref = File::open(ref_bin_path).expect("could not open file");
         let f_res = File::open(result_bin_name).expect("Could not open file");
@@ -343,6 +666,6 @@ mod tests {
         }
 
         remove_file(result_bin_name).unwrap();
-        return Ok(());
+        Ok(())
     }
 }
\ No newline at end of file
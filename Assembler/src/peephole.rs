@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use isa::Flow;
+use isa::registers::REG_NAMES;
+use crate::ast::{Program, Statement};
+use crate::encoder::select_def;
+use crate::err_handler::LineError;
+use crate::layout::layout;
+use crate::operands::Operand;
+use crate::symbols::{Section, Symbols};
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+// Mnemonics that set the flags from their own result, so the flags of any
+// instruction before them can no longer be read
+const FLAG_SETTERS: [&str; 8] = ["add", "sub", "cmp", "shl", "shr", "and", "or", "not"];
+
+// Optimization: A change the optimizer made to the instruction of a line
+#[derive(Debug, Clone, PartialEq)]
+pub enum Optimization {
+    SelfMove(u8, usize),                // mov rX rX, removed
+    ZeroImmediate(String, u8, u8, usize), // add/sub rX rY #0, removed or made a mov
+    BranchToNext(String, usize),        // Jump or branch to the next word, removed
+    JumpChain(String, String, usize)    // Target that is a jmp, replaced by where it leads
+}
+
+// Edit of a node of the program: removed, or replaced by a new statement
+enum Edit {
+    Remove,
+    Replace(Statement)
+}
+
+// Instructions of the code section by address, as indices into the nodes
+type Code = BTreeMap<u16, usize>;
+
+impl Optimization {
+    pub fn line(&self) -> usize {
+        match self {
+            Optimization::SelfMove(_, l)            |
+            Optimization::ZeroImmediate(_, _, _, l) |
+            Optimization::BranchToNext(_, l)        |
+            Optimization::JumpChain(_, _, l) => *l
+        }
+    }
+
+    // Whether the instruction is gone from the program
+    pub fn removes(&self) -> bool {
+        match self {
+            Optimization::ZeroImmediate(_, dst, src, _) => dst == src,
+            Optimization::JumpChain(..) => false,
+            _ => true
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Optimization::SelfMove(r, _) => {
+                format!("removed, moves {} onto itself", REG_NAMES[*r as usize])
+            }
+            Optimization::ZeroImmediate(m, dst, src, _) if dst == src => {
+                format!("removed, {} of #0 leaves {} unchanged", m, REG_NAMES[*dst as usize])
+            }
+            Optimization::ZeroImmediate(m, dst, src, _) => {
+                format!("{} of #0 replaced by mov {} {}", m, REG_NAMES[*dst as usize],
+                        REG_NAMES[*src as usize])
+            }
+            Optimization::BranchToNext(m, _) => {
+                format!("removed, {} to the next instruction", m)
+            }
+            Optimization::JumpChain(from, to, _) => {
+                format!("target '{}' only jumps on, now targets '{}'", from, to)
+            }
+        }
+    }
+}
+
+// ****************************** OPTIMIZATION ***************************** //
+
+// PEEPHOLE OPTIMIZATION: Removes or simplifies instructions of a program
+// that was laid out, then lays it out again so labels follow the code that
+// moved. One change may open up another (removing a branch can put a jmp
+// right before its target), so this repeats until nothing changes. Returns
+// every change, sorted by line number
+pub fn optimize(program: &mut Program, syms: &mut Symbols) -> Result<Vec<Optimization>, LineError> {
+    let mut done = Vec::new();
+    loop {
+        let changes = find_optimizations(program, syms);
+        if changes.is_empty() {
+            break;
+        }
+
+        let mut edits: HashMap<usize, Edit> = HashMap::new();
+        for (idx, edit, opt) in changes {
+            edits.insert(idx, edit);
+            done.push(opt);
+        }
+        let nodes = std::mem::take(&mut program.nodes);
+        program.nodes = nodes.into_iter().enumerate()
+            .filter_map(|(i, mut node)| match edits.remove(&i) {
+                Some(Edit::Remove) => None,
+                Some(Edit::Replace(stmt)) => {
+                    node.stmt = stmt;
+                    Some(node)
+                }
+                None => Some(node)
+            })
+            .collect();
+        syms.labels = layout(program)?;
+    }
+    done.sort_by_key(Optimization::line);
+    Ok(done)
+}
+
+// Changes that apply to the program as it is laid out now, at most one per
+// instruction
+fn find_optimizations(program: &Program, syms: &Symbols) -> Vec<(usize, Edit, Optimization)> {
+    let code: Code = program.nodes.iter().enumerate()
+        .filter(|(_, n)| n.section == Some(Section::Code))
+        .filter_map(|(i, n)| match n.stmt {
+            Statement::Instruction(..) => Some((n.addr, i)),
+            _ => None
+        })
+        .collect();
+
+    let mut changes = Vec::new();
+    for (&addr, &idx) in &code {
+        let node = &program.nodes[idx];
+        let Statement::Instruction(m, args) = &node.stmt else { continue };
+        let Some(def) = select_def(m, args) else { continue };
+        let line = node.line;
+
+        match (m.as_str(), args.as_slice()) {
+            ("mov", [Operand::Register(dst), Operand::Register(src)]) if dst == src => {
+                changes.push((idx, Edit::Remove, Optimization::SelfMove(*dst, line)));
+            }
+            ("add" | "sub", [Operand::Register(dst), Operand::Register(src), Operand::Immediate(0)])
+            if flags_overwritten(program, &code, addr) => {
+                let edit = match dst == src {
+                    true => Edit::Remove,
+                    false => Edit::Replace(Statement::Instruction("mov".to_string(), args[..2].to_vec()))
+                };
+                changes.push((idx, edit, Optimization::ZeroImmediate(m.clone(), *dst, *src, line)));
+            }
+            (_, [Operand::Label(target)]) if matches!(def.flow, Flow::Jump | Flow::Branch)
+                && syms.labels.get(target).map(|t| *t as usize) == Some(addr as usize + 1) => {
+                changes.push((idx, Edit::Remove, Optimization::BranchToNext(m.clone(), line)));
+            }
+            (_, [Operand::Label(target)]) if matches!(def.flow, Flow::Jump | Flow::Branch | Flow::Call) => {
+                if let Some(end) = chain_end(program, syms, &code, target) {
+                    let stmt = Statement::Instruction(m.clone(), vec![Operand::Label(end.clone())]);
+                    changes.push((idx, Edit::Replace(stmt),
+                                  Optimization::JumpChain(target.clone(), end, line)));
+                }
+            }
+            _ => ()
+        }
+    }
+    changes
+}
+
+// Label a chain of jmps starting at the given label ends at. None if the
+// label is not a jmp, or the jmps go around in a loop. A jmp to itself
+// ends the chain, as programs wait for a reset that way
+fn chain_end<'a>(program: &'a Program, syms: &Symbols, code: &Code, label: &'a str)
+-> Option<String> {
+    let mut seen = HashSet::new();
+    let mut current = label;
+    while let Some(next) = jmp_target(program, code, *syms.labels.get(current)?) {
+        if syms.labels.get(next) == syms.labels.get(current) {
+            break;
+        }
+        if !seen.insert(current) {
+            return None;
+        }
+        current = next;
+    }
+    (current != label).then(|| current.to_string())
+}
+
+// Label of the jmp at an address, if there is one
+fn jmp_target<'a>(program: &'a Program, code: &Code, addr: u16) -> Option<&'a str> {
+    match &program.nodes[*code.get(&addr)?].stmt {
+        Statement::Instruction(m, args) if m == "jmp" => match args.as_slice() {
+            [Operand::Label(l)] => Some(l.as_str()),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+// Whether the flags set by the instruction at an address are overwritten
+// before anything can read them. Only the instructions that follow it are
+// looked at: a branch, jump, call, return or halt keeps the flags alive
+fn flags_overwritten(program: &Program, code: &Code, addr: u16) -> bool {
+    let mut next = addr;
+    loop {
+        next = match next.checked_add(1) {
+            Some(a) => a,
+            None => return false
+        };
+        let Some(&idx) = code.get(&next) else { return false };
+        let Statement::Instruction(m, args) = &program.nodes[idx].stmt else { return false };
+        match select_def(m, args) {
+            Some(def) if def.flow != Flow::Next => return false,
+            Some(_) if FLAG_SETTERS.contains(&m.as_str()) => return true,
+            Some(_) => (),
+            None => return false
+        }
+    }
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_source, parse_symbols};
+
+    fn optimized(source: &str) -> (Program, Symbols, Vec<Optimization>) {
+        let mut program = parse_source("test.s", source).unwrap();
+        let mut syms = parse_symbols(&mut program).unwrap();
+        let opts = optimize(&mut program, &mut syms).unwrap();
+        (program, syms, opts)
+    }
+
+    fn instructions(program: &Program) -> Vec<String> {
+        program.instructions().map(|(m, args, _)| format!("{} {:?}", m, args)).collect()
+    }
+
+    #[test]
+    fn removes_redundant_instructions() {
+        let source = ".section[code]\nstart:\n    mov r1 r1\n    add r2 r2 #0\n    sub r3 r1 #0\n\
+                      \n    cmp r2 #1\n    add r0 r0 #0\n    beq end\nend:\n    halt\n";
+        let (program, syms, opts) = optimized(source);
+        assert_eq!(opts, [
+            Optimization::SelfMove(1, 2),
+            Optimization::ZeroImmediate("add".to_string(), 2, 2, 3),
+            Optimization::ZeroImmediate("sub".to_string(), 3, 1, 4),
+            Optimization::BranchToNext("beq".to_string(), 8)
+        ]);
+        // The flags of the last add can still be seen once the program halts
+        assert_eq!(instructions(&program), [
+            "mov [Register(3), Register(1)]", "cmp [Register(2), Immediate(1)]",
+            "add [Register(0), Register(0), Immediate(0)]", "halt []"
+        ]);
+        assert_eq!(syms.labels["end"], 3);
+    }
+
+    #[test]
+    fn follows_jump_chains() {
+        let source = ".section[code]\nstart:\n    beq a\n    bln b\n    halt\n\
+                      a:\n    jmp b\n    halt\nb:\n    jmp c\n    halt\nc:\n    jmp c\n";
+        let (program, _, opts) = optimized(source);
+        assert_eq!(opts, [
+            Optimization::JumpChain("a".to_string(), "c".to_string(), 2),
+            Optimization::JumpChain("b".to_string(), "c".to_string(), 3),
+            Optimization::JumpChain("b".to_string(), "c".to_string(), 6)
+        ]);
+        assert_eq!(program.instructions().map(|(_, args, _)| args.to_vec()).take(2)
+                          .collect::<Vec<_>>(),
+                   [[Operand::Label("c".to_string())], [Operand::Label("c".to_string())]]);
+    }
+}
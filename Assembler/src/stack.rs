@@ -0,0 +1,285 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use isa::{Flow, InstrDef, Opcode};
+use isa::registers::SP_PTR;
+use crate::ast::{Program, Statement};
+use crate::encoder::select_def;
+use crate::operands::Operand;
+use crate::symbols::{Section, Symbols};
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+// Function: Code reached from the entry point or from a 'bln'. Frame is
+// the deepest the function itself takes the stack and worst case adds
+// the functions it calls. Worst case is None when it is unbounded, as in
+// recursive functions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub addr: u16,
+    pub frame: usize,
+    pub worst: Option<usize>,
+    pub calls: Vec<u16>
+}
+
+// Stack Issue: Paths that leave the stack in a different state than they
+// found it, and stacks that may grow into the program
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackIssue {
+    Unbalanced(usize, usize, usize),
+    PopUnderflow(usize),
+    ReturnDepth(usize, usize),
+    Recursion(Vec<u16>),
+    Overflow(Option<usize>, usize)
+}
+
+// Stack Analysis: Every function found, sorted by address, and the issues
+// found while walking them. Line numbers start at 0
+#[derive(Debug)]
+pub struct StackAnalysis {
+    pub functions: Vec<Function>,
+    pub issues: Vec<StackIssue>,
+    pub entry: u16,
+    pub free: usize
+}
+
+// Instruction of the code section with the variant it is encoded as
+struct Instr<'a> {
+    line: usize,
+    def: &'static InstrDef,
+    args: &'a [Operand]
+}
+
+struct Analyzer<'a> {
+    instrs: BTreeMap<u16, Instr<'a>>,
+    labels: &'a HashMap<String, u16>,
+    functions: BTreeMap<u16, Function>,
+    in_progress: Vec<u16>,
+    issues: Vec<StackIssue>
+}
+
+// **************************** STACK ANALYSIS ***************************** //
+
+// Walks the call graph from the entry point, following 'bln' into every
+// function it calls, and counts the words each path pushes and pops.
+// Free is the number of words between the end of the program and the top
+// of memory, where the stack grows down from
+pub fn analyze_stack(program: &Program, syms: &Symbols, entry: u16, free: usize)
+-> StackAnalysis {
+    let instrs = program.in_section(Section::Code)
+        .filter_map(|n| match &n.stmt {
+            Statement::Instruction(m, args) => {
+                let def = select_def(m, args)?;
+                Some((n.addr, Instr { line: n.line, def, args }))
+            }
+            _ => None
+        })
+        .collect();
+    let mut analyzer = Analyzer {
+        instrs, labels: &syms.labels, functions: BTreeMap::new(),
+        in_progress: Vec::new(), issues: Vec::new()
+    };
+
+    let worst = analyzer.function(entry);
+    if worst.is_none_or(|w| w > free) {
+        analyzer.issues.push(StackIssue::Overflow(worst, free));
+    }
+
+    // Functions are named after the first label at their address
+    let mut names: BTreeMap<u16, &str> = BTreeMap::new();
+    for (name, addr) in &syms.labels {
+        let e = names.entry(*addr).or_insert(name);
+        if name.as_str() < *e {
+            *e = name;
+        }
+    }
+    let name_of = |addr: &u16| match names.get(addr) {
+        Some(name) => name.to_string(),
+        None => format!("sub_{:03x}", addr)
+    };
+    let mut functions: Vec<Function> = analyzer.functions.into_values().collect();
+    functions.iter_mut().for_each(|f| f.name = name_of(&f.addr));
+    StackAnalysis { functions, issues: analyzer.issues, entry, free }
+}
+
+impl Analyzer<'_> {
+    // Worst-case stack depth of the function at addr, analyzing it first
+    // if needed. None if it is unbounded
+    fn function(&mut self, addr: u16) -> Option<usize> {
+        if let Some(f) = self.functions.get(&addr) {
+            return f.worst;
+        }
+        if let Some(pos) = self.in_progress.iter().position(|a| *a == addr) {
+            let mut cycle = self.in_progress[pos..].to_vec();
+            cycle.push(addr);
+            self.issues.push(StackIssue::Recursion(cycle));
+            return None;
+        }
+
+        self.in_progress.push(addr);
+        let mut frame = 0;
+        let mut worst = Some(0);
+        let mut calls = Vec::new();
+        let mut visited: HashMap<u16, usize> = HashMap::new();
+        let mut pending: Vec<(u16, usize)> = vec![(addr, 0)];
+
+        while let Some((addr, depth)) = pending.pop() {
+            let Some(instr) = self.instrs.get(&addr) else { continue };
+            let (line, def) = (instr.line, instr.def);
+            match visited.get(&addr) {
+                Some(d) if *d != depth => {
+                    let issue = StackIssue::Unbalanced(line, (*d).min(depth), (*d).max(depth));
+                    if !self.issues.contains(&issue) {
+                        self.issues.push(issue);
+                    }
+                    continue;
+                }
+                Some(_) => continue,
+                None => visited.insert(addr, depth)
+            };
+
+            let effect = stack_effect(instr);
+            let depth = match depth as i64 + effect {
+                d if d < 0 => {
+                    self.issues.push(StackIssue::PopUnderflow(line));
+                    0
+                }
+                d => d as usize
+            };
+            frame = frame.max(depth);
+            let target = instr.args.iter().find_map(|a| match a {
+                Operand::Label(l) => self.labels.get(l).copied(),
+                _ => None
+            });
+
+            match (def.flow, target) {
+                (Flow::Call, Some(t)) => {
+                    if !calls.contains(&t) {
+                        calls.push(t);
+                    }
+                    let callee = self.function(t);
+                    worst = worst.zip(callee).map(|(w, c)| w.max(depth + c));
+                    pending.push((addr + 1, depth));
+                }
+                (Flow::Return, _) if depth != 0 => {
+                    self.issues.push(StackIssue::ReturnDepth(line, depth));
+                }
+                (Flow::Return | Flow::Halt, _) => (),
+                (Flow::Jump, Some(t)) => pending.push((t, depth)),
+                (Flow::Branch, Some(t)) => {
+                    pending.push((t, depth));
+                    pending.push((addr + 1, depth));
+                }
+                _ => pending.push((addr + 1, depth))
+            }
+        }
+
+        self.in_progress.pop();
+        let worst = worst.map(|w| w.max(frame));
+        self.functions.insert(addr, Function { name: String::new(), addr, frame, worst, calls });
+        worst
+    }
+}
+
+// Words an instruction pushes onto the stack, negative if it pops them.
+// Adding to or subtracting from sp itself releases or reserves words
+fn stack_effect(instr: &Instr) -> i64 {
+    let sp = SP_PTR as u8;
+    match (instr.def.opcode, instr.args) {
+        (Opcode::Push, args) => args.len() as i64,
+        (Opcode::Pop, args) => -(args.len() as i64),
+        (Opcode::SubIm, [Operand::Register(d), Operand::Register(s), Operand::Immediate(n)])
+            if *d == sp && *s == sp => *n as i64,
+        (Opcode::AddIm, [Operand::Register(d), Operand::Register(s), Operand::Immediate(n)])
+            if *d == sp && *s == sp => -(*n as i64),
+        _ => 0
+    }
+}
+
+// ******************************** REPORT ********************************* //
+
+impl StackAnalysis {
+    // Text report with the functions found and the issues, if any, of the
+    // program that was analyzed. Line numbers are shown starting at 1, like
+    // in error messages, and lines of included files are named with their file
+    pub fn report(&self, program: &Program) -> String {
+        let depth = |d: Option<usize>| d.map_or("unbounded".to_string(), |d| d.to_string());
+        let name_of = |addr: &u16| self.functions.iter()
+            .find(|f| f.addr == *addr)
+            .map_or(format!("sub_{:03x}", addr), |f| f.name.clone());
+        let width = self.functions.iter().map(|f| f.name.len()).max().unwrap_or(0).max(4) + 2;
+
+        let mut out = format!("// Stack analysis of {}\n\nFunctions\n", program.file);
+        writeln!(out, "    {:width$}{:9}{:7}{:12}CALLS", "NAME", "ADDRESS", "FRAME", "WORST CASE")
+            .unwrap();
+        for f in &self.functions {
+            let calls: Vec<String> = f.calls.iter().map(name_of).collect();
+            let calls = if calls.is_empty() { "-".to_string() } else { calls.join(", ") };
+            writeln!(out, "    {:width$}0x{:04X}   {:<7}{:12}{}", f.name, f.addr, f.frame,
+                     depth(f.worst), calls).unwrap();
+        }
+        let worst = self.functions.iter().find(|f| f.addr == self.entry).and_then(|f| f.worst);
+        writeln!(out, "\nWorst-case stack depth from the entry point: {} of {} free words",
+                 depth(worst), self.free).unwrap();
+
+        if !self.issues.is_empty() {
+            out.push_str("\nProblems\n");
+        }
+        for issue in &self.issues {
+            let text = match issue {
+                StackIssue::Unbalanced(n, a, b) => format!("line {}: reached with {} and {} \
+                    words on the stack on different paths", program.line_name(*n), a, b),
+                StackIssue::PopUnderflow(n) => format!("line {}: pops more words than the \
+                    function pushed", program.line_name(*n)),
+                StackIssue::ReturnDepth(n, d) => format!("line {}: returns with {} words left \
+                    on the stack", program.line_name(*n), d),
+                StackIssue::Recursion(cycle) => format!("recursion: {}", cycle.iter()
+                    .map(name_of).collect::<Vec<_>>().join(" -> ")),
+                StackIssue::Overflow(worst, free) => format!("the stack may grow to {} words, \
+                    but only {} are free after the program", depth(*worst), free)
+            };
+            writeln!(out, "    {}", text).unwrap();
+        }
+        out
+    }
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_source, parse_symbols};
+
+    fn analyze(source: &str) -> (Program, StackAnalysis) {
+        let mut program = parse_source("test.s", source).unwrap();
+        let syms = parse_symbols(&mut program).unwrap();
+        let stack = analyze_stack(&program, &syms, 0, 100);
+        (program, stack)
+    }
+
+    #[test]
+    fn call_graph_depths() {
+        let (program, stack) = analyze(".section[code]\nmain:\npush r1\nbln sum\npop r1\nhalt\n\
+                                        sum:\npush r2 r3\nbln inc\npop r2 r3\nret\n\
+                                        inc:\nsub sp sp #1\nadd sp sp #1\nret\n");
+        let depths: Vec<(&str, usize, Option<usize>)> = stack.functions.iter()
+            .map(|f| (f.name.as_str(), f.frame, f.worst))
+            .collect();
+        assert_eq!(depths, [("main", 1, Some(4)), ("sum", 2, Some(3)), ("inc", 1, Some(1))]);
+        assert!(stack.issues.is_empty());
+        assert!(stack.report(&program).contains("    main  0x0000   1      4           sum\n"));
+    }
+
+    #[test]
+    fn stack_issues() {
+        let (program, stack) = analyze(".section[code]\nmain:\nbeq skip\npush r1\nskip:\n\
+                                        bln f\nhalt\nf:\npush r1\nbln f\nret\n");
+        assert_eq!(stack.issues, [
+            StackIssue::Recursion(vec![4, 4]),
+            StackIssue::ReturnDepth(10, 1),
+            StackIssue::Unbalanced(5, 0, 1),
+            StackIssue::Overflow(None, 100)
+        ]);
+        assert!(stack.report(&program).contains("    recursion: f -> f\n"));
+    }
+}
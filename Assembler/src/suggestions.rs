@@ -0,0 +1,81 @@
+// Helpers to find the closest known name to an unrecognized token, so error
+// messages can propose a correction ("did you mean 'bne'?")
+
+// Maximum edit distance for a candidate to still be considered a typo
+const MAX_DISTANCE: usize = 2;
+
+// Levenshtein edit distance between two strings: the minimum number of
+// single character insertions, deletions or substitutions needed to turn
+// one into the other
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // Only two rows of the distance matrix are needed at a time
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr: Vec<usize> = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        curr[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i-1] == b[j-1] { 0 } else { 1 };
+            curr[j] = (prev[j] + 1)           // Deletion
+                      .min(curr[j-1] + 1)     // Insertion
+                      .min(prev[j-1] + cost); // Substitution
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+// Returns the candidate closest to word, if any is close enough to be a
+// plausible typo. Ties are broken alphabetically so the result does not
+// depend on the iteration order of the candidates. Case is ignored when
+// measuring, so a candidate differing only in case ('R1' for 'r1') is the
+// closest of all; only the word itself, exactly as written, is left out
+pub fn closest_match<'a, I>(word: &str, candidates: I) -> Option<String>
+where I: IntoIterator<Item = &'a str> {
+    let lowercase = word.to_lowercase();
+    // Allow fewer edits on very short words, otherwise anything matches
+    let max_distance = MAX_DISTANCE.min(lowercase.chars().count() / 2).max(1);
+
+    candidates.into_iter()
+        .filter(|c| *c != word)
+        .map(|c| (edit_distance(&lowercase, &c.to_lowercase()), c))
+        .filter(|(d, _)| *d <= max_distance)
+        .min_by(|x, y| x.0.cmp(&y.0).then(x.1.cmp(y.1)))
+        .map(|(_, c)| c.to_string())
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances() {
+        assert_eq!(edit_distance("bne", "bne"), 0);
+        assert_eq!(edit_distance("bnq", "bne"), 1);
+        assert_eq!(edit_distance("end_lop", "end_loop"), 1);
+        assert_eq!(edit_distance("", "halt"), 4);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn suggestions() {
+        let mnemonics = ["mov", "bne", "beq", "halt", "push"];
+        assert_eq!(closest_match("bnne", mnemonics), Some("bne".to_string()));
+        assert_eq!(closest_match("HLT", mnemonics), Some("halt".to_string()));
+        assert_eq!(closest_match("xyzzy", mnemonics), None);
+        assert_eq!(closest_match("end_lop", ["loop", "end_loop"]),
+                   Some("end_loop".to_string()));
+    }
+
+    #[test]
+    // Names that only differ in case are suggested before any typo
+    fn case_only_differences() {
+        let registers = ["r0", "r1", "r2", "sp", "lr", "mbr"];
+        assert_eq!(closest_match("R1", registers), Some("r1".to_string()));
+        assert_eq!(closest_match("Start", ["start", "stars"]), Some("start".to_string()));
+        assert_eq!(closest_match("start", ["start", "stars"]), Some("stars".to_string()));
+    }
+}
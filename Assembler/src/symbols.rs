@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use crate::err_handler::LineError;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Section {
     Code,
     Data
@@ -9,7 +10,7 @@ pub enum Section {
 // Struct containig symbol table (labels) and two tuples
 // describing the range of lines in the assembly source
 // file that make up the code section and data section
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Default)]
 pub struct Symbols {
     pub labels: HashMap<String, u16>,
     pub code_section: (Option<usize>, Option<usize>),
@@ -18,11 +19,19 @@ pub struct Symbols {
 
 impl Symbols {
     pub fn new() -> Symbols {
-        Symbols {
-            labels: HashMap::new(),
-            code_section: (None, None),
-            data_section: (None, None)
+        Symbols::default()
+    }
+
+    // Symbol map: one label per line as '<address> <name>', sorted by
+    // address. Read by the disassembler to show the real label names
+    pub fn symbol_map(&self) -> String {
+        let mut labels: Vec<(&String, &u16)> = self.labels.iter().collect();
+        labels.sort_by_key(|(name, addr)| (**addr, *name));
+        let mut map = String::from("// Symbol map generated by the assembler\n");
+        for (name, addr) in labels {
+            map.push_str(&format!("0x{:04X} {}\n", addr, name));
         }
+        map
     }
 
     pub fn code_range(&self) -> Option<std::ops::Range<usize>> {
@@ -39,7 +48,7 @@ impl Symbols {
         }
     }
 
-    pub fn update_sections(&mut self, s: Section, line: usize) -> () {
+    pub fn update_sections(&mut self, s: Section, line: usize) {
         let neither_declared = || self.code_section == (None, None) &&
                                   self.data_section == (None, None);
         match s {
@@ -71,15 +80,18 @@ impl Symbols {
         match (self.code_section, self.data_section) {
             ((Some(_), Some(x)), (Some(y), None)) if x == y => {
                 // Code Section was declared first
-                Ok(self.data_section.1 = Some(line_num))
+                self.data_section.1 = Some(line_num);
+                Ok(())
             },
             ((Some(x), None), (Some(_), Some(y))) if x == y => {
                 // Data section was declared first
-                Ok(self.code_section.1 = Some(line_num))
+                self.code_section.1 = Some(line_num);
+                Ok(())
             },
             ((Some(_), None), (None, None)) => {
                 // Only Code section was declared
-                Ok(self.code_section.1 = Some(line_num))
+                self.code_section.1 = Some(line_num);
+                Ok(())
             },
             ((None, None), (Some(_), None)) => {
                 // Only Data section was declared
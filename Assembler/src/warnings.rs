@@ -0,0 +1,347 @@
+use std::collections::HashSet;
+use colored::Colorize;
+use isa::{Flow, Opcode, OperandKind};
+use isa::registers::{REG_NAMES, SP_PTR, LNR_PTR, MBR_PTR};
+use crate::ast::{Program, Statement};
+use crate::encoder::{reg_usage, select_def};
+use crate::operands::Operand;
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+// Warning Kind: Categories of suspicious but legal code. Each category can
+// be switched on or off individually from the command line by its name
+// (-W<name> / -Wno-<name>). The last four are the conventions checked by
+// the linter (see lint.rs), which the assembler only checks on request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningKind {
+    UnpoppedPush,
+    SpecialRegWrite,
+    MissingHalt,
+    UnusedLabel,
+    CalleeSaved,
+    UnsavedLr,
+    UninitRead,
+    Unreachable
+}
+
+impl WarningKind {
+    pub const ALL: [WarningKind; 8] = [
+        WarningKind::UnpoppedPush,
+        WarningKind::SpecialRegWrite,
+        WarningKind::MissingHalt,
+        WarningKind::UnusedLabel,
+        WarningKind::CalleeSaved,
+        WarningKind::UnsavedLr,
+        WarningKind::UninitRead,
+        WarningKind::Unreachable
+    ];
+
+    // Categories the assembler reports without being asked to
+    pub const DEFAULT: [WarningKind; 4] = [
+        WarningKind::UnpoppedPush,
+        WarningKind::SpecialRegWrite,
+        WarningKind::MissingHalt,
+        WarningKind::UnusedLabel
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WarningKind::UnpoppedPush    => "unpopped-push",
+            WarningKind::SpecialRegWrite => "special-reg",
+            WarningKind::MissingHalt     => "missing-halt",
+            WarningKind::UnusedLabel     => "unused-label",
+            WarningKind::CalleeSaved     => "callee-saved",
+            WarningKind::UnsavedLr       => "unsaved-lr",
+            WarningKind::UninitRead      => "uninit-read",
+            WarningKind::Unreachable     => "unreachable"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<WarningKind> {
+        WarningKind::ALL.into_iter().find(|k| k.name() == name)
+    }
+}
+
+// Warning: A single occurrence of a warning. Like LineError, variants carry
+// the line number they refer to and any names needed for the message
+#[derive(Debug, PartialEq)]
+pub enum Warning {
+    UnpoppedPush(u8, usize),
+    SpecialRegWrite(String, usize),
+    MissingHalt(usize),
+    UnusedLabel(String, usize),
+    CalleeSaved(u8, String, usize),
+    UnsavedLr(String, usize),
+    UninitRead(u8, usize),
+    Unreachable(usize)
+}
+
+impl Warning {
+    pub fn kind(&self) -> WarningKind {
+        match self {
+            Warning::UnpoppedPush(_, _)       => WarningKind::UnpoppedPush,
+            Warning::SpecialRegWrite(_, _)    => WarningKind::SpecialRegWrite,
+            Warning::MissingHalt(_)           => WarningKind::MissingHalt,
+            Warning::UnusedLabel(_, _)        => WarningKind::UnusedLabel,
+            Warning::CalleeSaved(_, _, _)     => WarningKind::CalleeSaved,
+            Warning::UnsavedLr(_, _)          => WarningKind::UnsavedLr,
+            Warning::UninitRead(_, _)         => WarningKind::UninitRead,
+            Warning::Unreachable(_)           => WarningKind::Unreachable
+        }
+    }
+
+    pub fn line(&self) -> usize {
+        match self {
+            Warning::UnpoppedPush(_, n) | Warning::SpecialRegWrite(_, n) |
+            Warning::MissingHalt(n) | Warning::UnusedLabel(_, n) |
+            Warning::CalleeSaved(_, _, n) | Warning::UnsavedLr(_, n) |
+            Warning::UninitRead(_, n) | Warning::Unreachable(n) => *n
+        }
+    }
+}
+
+// Set of enabled warning categories and whether warnings are promoted
+// to errors (-Werror). The default categories are enabled by default
+pub struct WarningConfig {
+    enabled: HashSet<WarningKind>,
+    pub werror: bool
+}
+
+impl Default for WarningConfig {
+    fn default() -> Self {
+        WarningConfig { enabled: WarningKind::DEFAULT.into_iter().collect(), werror: false }
+    }
+}
+
+impl WarningConfig {
+    pub fn enable(&mut self, kind: WarningKind) {
+        self.enabled.insert(kind);
+    }
+
+    pub fn disable(&mut self, kind: WarningKind) {
+        self.enabled.remove(&kind);
+    }
+
+    pub fn disable_all(&mut self) {
+        self.enabled.clear();
+    }
+
+    pub fn is_enabled(&self, kind: WarningKind) -> bool {
+        self.enabled.contains(&kind)
+    }
+}
+
+// ***************************** WARNING CHECKS **************************** //
+
+// Runs every enabled check over the program and returns the warnings
+// found, sorted by line number
+pub fn check_program(prog: &Program, config: &WarningConfig) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    warnings.extend(unpopped_pushes(prog));
+    warnings.extend(special_reg_writes(prog));
+    warnings.extend(missing_halt(prog));
+    warnings.extend(unused_labels(prog));
+
+    warnings.retain(|w| config.is_enabled(w.kind()));
+    warnings.sort_by_key(Warning::line);
+    warnings
+}
+
+// Registers pushed somewhere in the program but never popped anywhere
+fn unpopped_pushes(prog: &Program) -> Vec<Warning> {
+    let regs_of = |mnemonic: &str| -> Vec<(u8, usize)> {
+        prog.instructions()
+            .filter(|(m, _, _)| *m == mnemonic)
+            .flat_map(|(_, args, line)| args.iter().filter_map(move |a| match a {
+                Operand::Register(r) => Some((*r, line)),
+                _ => None
+            }))
+            .collect()
+    };
+    let popped: HashSet<u8> = regs_of("pop").into_iter().map(|(r, _)| r).collect();
+
+    regs_of("push").into_iter()
+        .filter(|(r, _)| !popped.contains(r))
+        .map(|(r, line)| Warning::UnpoppedPush(r, line))
+        .collect()
+}
+
+// Writes to sp, lr or mbr with general purpose instructions. A write to
+// mbr directly followed by 'stra' is how stra is meant to be used and
+// is not reported
+fn special_reg_writes(prog: &Program) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let instructions: Vec<_> = prog.instructions().collect();
+    for (i, (mnemonic, args, line)) in instructions.iter().enumerate() {
+        let Some(r) = destination(mnemonic, args) else { continue };
+        let next_is_stra = || instructions.get(i + 1)
+            .and_then(|(m, args, _)| select_def(m, args))
+            .is_some_and(|d| d.opcode == Opcode::Store);
+        match r as usize {
+            SP_PTR | LNR_PTR => (),
+            MBR_PTR if !next_is_stra() => (),
+            _ => continue
+        }
+        warnings.push(Warning::SpecialRegWrite(REG_NAMES[r as usize].to_string(), *line));
+    }
+    warnings
+}
+
+// Register an instruction writes through its first operand. Registers
+// written implicitly, and the ones 'pop' restores, are used as intended
+fn destination(mnemonic: &str, args: &[Operand]) -> Option<u8> {
+    let def = select_def(mnemonic, args)?;
+    match (def.operands.first(), args.first()) {
+        (Some(OperandKind::Reg(_)), Some(Operand::Register(r)))
+            if reg_usage(mnemonic, args)?.writes.contains(r) => Some(*r),
+        _ => None
+    }
+}
+
+// Last instruction of the code section lets execution run past the end
+fn missing_halt(prog: &Program) -> Option<Warning> {
+    let (m, args, line) = prog.instructions().last()?;
+    let ends_flow = select_def(m, args)
+        .is_some_and(|d| matches!(d.flow, Flow::Jump | Flow::Return | Flow::Halt));
+    match ends_flow {
+        true => None,
+        false => Some(Warning::MissingHalt(line))
+    }
+}
+
+// Labels never used as an operand. The label at the entry point of the
+// program is exempt, as it usually only documents where execution starts
+fn unused_labels(prog: &Program) -> Vec<Warning> {
+    let mut referenced: HashSet<&str> = prog.instructions()
+        .flat_map(|(_, args, _)| args.iter())
+        .filter_map(|a| match a {
+            Operand::Label(l) => Some(l.as_str()),
+            _ => None
+        })
+        .collect();
+
+    // Labels used in the expressions of directives, such as '.word end'
+    for node in &prog.nodes {
+        if let Statement::Directive(d) = &node.stmt {
+            referenced.extend(d.expr().labels());
+        }
+    }
+
+    prog.labels()
+        .filter(|(l, _)| !referenced.contains(l))
+        .filter(|(l, _)| prog.entry_label() != Some(l))
+        .map(|(l, line)| Warning::UnusedLabel(l.to_string(), line))
+        .collect()
+}
+
+// **************************** WARNING HANDLER **************************** //
+
+impl Warning {
+    // Description of the warning, without the file and line it refers to
+    pub fn message(&self) -> String {
+        match self {
+            Warning::UnpoppedPush(r, _) => {
+                format!("Register '{}' is pushed onto the stack but never popped",
+                        REG_NAMES[*r as usize].bold())
+            }
+            Warning::SpecialRegWrite(name, _) => {
+                format!("Writing to '{}' as a general purpose register", name.bold())
+            }
+            Warning::MissingHalt(_) => {
+                format!("Execution runs past the last instruction of the code section. \
+                         Did you forget '{}'?", "halt".bold())
+            }
+            Warning::UnusedLabel(l, _) => format!("Label '{}' is never referenced", l.bold()),
+            Warning::CalleeSaved(r, f, _) => {
+                format!("Function '{}' changes callee-saved register '{}' without saving \
+                         it on the stack", f.bold(), REG_NAMES[*r as usize].bold())
+            }
+            Warning::UnsavedLr(f, _) => {
+                format!("Function '{}' calls another function without saving '{}' first, \
+                         so it can not return", f.bold(), "lr".bold())
+            }
+            Warning::UninitRead(r, _) => {
+                format!("Register '{}' may be read before it is ever written",
+                        REG_NAMES[*r as usize].bold())
+            }
+            Warning::Unreachable(_) => "Code can never be reached".to_string()
+        }
+    }
+}
+
+// Warning Handler: Displays a warning message to the screen, in the same
+// format as the messages of the error handler
+pub fn warning_handler(w: &Warning, program: &Program, as_error: bool) {
+    let (file, line) = program.locate(w.line());
+    let title = if as_error { "Error".red().bold() } else { "Warning".yellow().bold() };
+    let header = format!("\n{} [-W{}] in file {}\n", title, w.kind().name(),
+                         program.file_name(file).yellow());
+    println!("{}{}\nLine Number: {}\n", header, w.message(), line + 1);
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Directive, Node};
+    use crate::expr::parse_expr;
+    use crate::operands::parse_operands;
+    use crate::symbols::Section;
+
+    // Builds a code section from (line number, mnemonic, operands) triples
+    // and (label, line number) pairs
+    fn program(instrs: &[(usize, &str, &str)], labels: &[(&str, usize)]) -> Program {
+        let node = |stmt, line| {
+            Node { stmt, line, column: 1, section: Some(Section::Code), addr: 0, size: 0 }
+        };
+        let mut nodes: Vec<Node> = instrs.iter()
+            .map(|(n, m, a)| {
+                node(Statement::Instruction(m.to_string(), parse_operands(a, *n).unwrap()), *n)
+            })
+            .chain(labels.iter().map(|(l, n)| node(Statement::Label(l.to_string()), *n)))
+            .collect();
+        nodes.sort_by_key(|n| n.line);
+        Program { nodes, ..Default::default() }
+    }
+
+    #[test]
+    fn each_category() {
+        let prog = program(&[(1, "push", "r1 r2"), (2, "pop", "r1"),
+                             (3, "add", "sp sp #1"), (4, "mov", "mbr r1"),
+                             (5, "stra", "data"), (6, "mov", "lr r0"),
+                             (7, "pop", "lr"), (8, "cmp", "r1 #0")],
+                           &[("start", 0), ("unused", 5), ("data", 8)]);
+        let warnings = check_program(&prog, &WarningConfig::default());
+        assert_eq!(warnings, vec![
+            Warning::UnpoppedPush(2, 1),
+            Warning::SpecialRegWrite("sp".to_string(), 3),
+            Warning::UnusedLabel("unused".to_string(), 5),
+            Warning::SpecialRegWrite("lr".to_string(), 6),
+            Warning::MissingHalt(8)
+        ]);
+        let message = Warning::UnpoppedPush(5, 1).message();
+        assert!(message.contains("sp") && !message.contains("r5"));
+    }
+
+    #[test]
+    fn disabled_categories() {
+        let prog = program(&[(1, "mov", "sp #0"), (2, "jmp", "start")],
+                           &[("start", 0), ("end", 3)]);
+        let mut config = WarningConfig::default();
+        config.disable(WarningKind::UnusedLabel);
+        assert_eq!(check_program(&prog, &config),
+                   vec![Warning::SpecialRegWrite("sp".to_string(), 1)]);
+        config.disable_all();
+        assert!(check_program(&prog, &config).is_empty());
+    }
+
+    #[test]
+    // A label referenced only from a directive expression is used
+    fn label_used_by_directive() {
+        let mut prog = program(&[(1, "halt", "")], &[("start", 0), ("table", 2)]);
+        let word = Directive::Word(parse_expr("table + 1", 3).unwrap());
+        prog.nodes.push(Node { stmt: Statement::Directive(word), line: 3, column: 5,
+                               section: Some(Section::Code), addr: 0, size: 1 });
+        assert!(check_program(&prog, &WarningConfig::default()).is_empty());
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt::Write;
+use isa::registers::{REG_NAMES, REG_TOTAL_NUM};
+use crate::ast::{Program, Statement};
+use crate::encoder::reg_usage;
+use crate::operands::Operand;
+use crate::symbols::Symbols;
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+// Symbol Reference: Where a label is declared and every line that uses it,
+// either as an instruction operand or in a directive expression
+#[derive(Debug, PartialEq)]
+pub struct SymbolRef {
+    pub name: String,
+    pub addr: u16,
+    pub defined: usize,
+    pub references: Vec<usize>
+}
+
+// Register Reference: Lines that read and lines that write a register,
+// counting the registers instructions use implicitly (mbr, sp and lr)
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RegisterRef {
+    pub reads: Vec<usize>,
+    pub writes: Vec<usize>
+}
+
+// Cross Reference: Symbols sorted by address and registers by number.
+// Line numbers start at 0 like everywhere else in the assembler
+#[derive(Debug)]
+pub struct CrossReference {
+    pub symbols: Vec<SymbolRef>,
+    pub registers: [RegisterRef; REG_TOTAL_NUM]
+}
+
+// ********************** CROSS REFERENCE GENERATION *********************** //
+
+// Builds the cross reference of an assembled program (--xref) from its
+// resolved symbols
+pub fn cross_reference(program: &Program, syms: &Symbols) -> CrossReference {
+    let mut symbols: Vec<SymbolRef> = program.nodes.iter()
+        .filter_map(|n| match &n.stmt {
+            Statement::Label(l) => Some(SymbolRef {
+                name: l.clone(), addr: syms.labels[l], defined: n.line, references: Vec::new()
+            }),
+            _ => None
+        })
+        .collect();
+    let mut registers: [RegisterRef; REG_TOTAL_NUM] = Default::default();
+
+    let mut add_ref = |name: &str, line: usize| {
+        if let Some(s) = symbols.iter_mut().find(|s| s.name == name) {
+            push_line(&mut s.references, line);
+        }
+    };
+    for node in program.nodes.iter().filter(|n| n.section.is_some()) {
+        match &node.stmt {
+            Statement::Instruction(m, args) => {
+                for arg in args {
+                    if let Operand::Label(l) = arg {
+                        add_ref(l, node.line);
+                    }
+                }
+                if let Some(usage) = reg_usage(m, args) {
+                    for r in usage.reads {
+                        push_line(&mut registers[r as usize].reads, node.line);
+                    }
+                    for r in usage.writes {
+                        push_line(&mut registers[r as usize].writes, node.line);
+                    }
+                }
+            }
+            Statement::Directive(d) => d.expr().labels().into_iter()
+                                         .for_each(|l| add_ref(l, node.line)),
+            _ => ()
+        }
+    }
+
+    symbols.sort_by(|a, b| (a.addr, &a.name).cmp(&(b.addr, &b.name)));
+    CrossReference { symbols, registers }
+}
+
+// Adds a line to a list of lines, once
+fn push_line(lines: &mut Vec<usize>, line: usize) {
+    if lines.last() != Some(&line) {
+        lines.push(line);
+    }
+}
+
+// ******************************** REPORT ********************************* //
+
+impl CrossReference {
+    // Text report with one table for symbols and one for registers of the
+    // program the cross reference was built from. Line numbers are shown
+    // starting at 1, like in error messages, and lines of included files
+    // are named with their file
+    pub fn report(&self, program: &Program) -> String {
+        let lines = |l: &[usize]| match l.is_empty() {
+            true => "-".to_string(),
+            false => l.iter().map(|n| program.line_name(*n)).collect::<Vec<_>>().join(", ")
+        };
+        let width = self.symbols.iter().map(|s| s.name.len()).max().unwrap_or(0).max(4) + 2;
+        let defined: Vec<String> = self.symbols.iter()
+            .map(|s| program.line_name(s.defined))
+            .collect();
+        let def_width = defined.iter().map(|d| d.len()).max().unwrap_or(0).max(7) + 2;
+
+        let mut out = format!("// Cross-reference report of {}\n\nSymbols\n", program.file);
+        writeln!(out, "    {:width$}{:9}{:def_width$}REFERENCED ON LINES", "NAME", "ADDRESS",
+                 "DEFINED").unwrap();
+        for (s, defined) in self.symbols.iter().zip(&defined) {
+            writeln!(out, "    {:width$}0x{:04X}   {:def_width$}{}", s.name, s.addr, defined,
+                     lines(&s.references)).unwrap();
+        }
+
+        out.push_str("\nRegisters\n");
+        let reads: Vec<String> = self.registers.iter().map(|r| lines(&r.reads)).collect();
+        let read_width = reads.iter().map(|r| r.len()).max().unwrap_or(0).max(28) + 2;
+        writeln!(out, "    {:6}{:read_width$}WRITTEN ON LINES", "REG", "READ ON LINES").unwrap();
+        for ((name, r), reads) in REG_NAMES.iter().zip(&self.registers).zip(&reads) {
+            writeln!(out, "    {:6}{:read_width$}{}", name, reads, lines(&r.writes)).unwrap();
+        }
+        out
+    }
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_program, parse_symbols};
+
+    #[test]
+    // [Test File 1] Sums an array in the data section
+    fn cross_reference_file1() {
+        let mut program = parse_program("test/file1.s").unwrap();
+        let syms = parse_symbols(&mut program).unwrap();
+        let xref = cross_reference(&program, &syms);
+
+        let symbols: Vec<(&str, usize, &[usize])> = xref.symbols.iter()
+            .map(|s| (s.name.as_str(), s.defined, s.references.as_slice()))
+            .collect();
+        assert_eq!(symbols, [("start", 2, &[][..]), ("loop", 8, &[15]), ("end_loop", 17, &[10]),
+                             ("arr", 22, &[5])]);
+        assert_eq!(xref.registers[1], RegisterRef { reads: vec![9, 14], writes: vec![3, 14] });
+        // lda writes mbr, which is then copied into r2
+        assert_eq!(xref.registers[7], RegisterRef { reads: vec![6], writes: vec![5] });
+
+        let report = xref.report(&program);
+        assert!(report.contains("    loop      0x0004   9        16\n"));
+        assert!(report.contains("    mbr   7                             6\n"));
+    }
+
+    #[test]
+    // [Test File 3] Lines of included files are named with their file
+    fn cross_reference_file3() {
+        let mut program = parse_program("test/file3.s").unwrap();
+        let syms = parse_symbols(&mut program).unwrap();
+        let report = cross_reference(&program, &syms).report(&program);
+        assert!(report.contains("    sum       0x0005   test/include/sum.s:2   8\n"));
+        assert!(report.contains("    sum_end   0x000D   test/include/sum.s:12  \
+                                 test/include/sum.s:6\n"));
+        assert!(report.contains("    table     0x000E   13                     5\n"));
+
+        // Long read lists push the write column further out
+        assert!(report.contains("    r1    test/include/sum.s:5, test/include/sum.s:10  \
+                                 7, test/include/sum.s:10\n"));
+    }
+}
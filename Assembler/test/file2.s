@@ -1,10 +1,19 @@
 // TEST FILE 2: TESTING SECTION DECLARATIONS
 .section(data)
-array:
     30, 20, -2, 4
 
 .section[code]
-start:
     mov r1 #7
     mov r2 r1
-    add r3 r1 r2
\ No newline at end of file
+    add r3 r1 r2
+
+/* Helpers taking their operands in r1 and r2:
+   sum2nums: r0 <- r1 + r2
+   sub2nums: r0 <- r1 - r2 */
+sum2nums:
+    add r0 r1 r2    ; result: r0
+    ret
+
+sub2nums:
+    sub r0 r1 r2
+    ret
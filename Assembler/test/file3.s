@@ -0,0 +1,14 @@
+// Test File 3: Includes. Sums a table of words read from a binary file,
+// with a function from another source file
+.section[code]
+start:
+    lda table
+    mov r2 mbr
+    mov r1 #3
+    bln sum
+    halt
+    .include "include/sum.s"
+
+.section[data]
+table:
+    .incbin "include/table.bin"
@@ -0,0 +1,32 @@
+// Test File 4: Packed strings. Counts the characters of a message and
+// reads single characters with the routines of lib/pstring.s
+.section[code]
+start:
+    lda message
+    mov r2 mbr  // Address of the message
+    mov r3 #0
+count:
+    mov r0 r2
+    mov r1 r3
+    bln pstr_char
+    cmp r0 #0
+    beq counted
+    add r3 r3 #1
+    jmp count
+counted:
+    mov r0 r2  // r3 <- 5
+    mov r1 #4
+    bln pstr_char
+    mov r2 r0  // r2 <- 'o'
+    lda greeting
+    mov r0 mbr
+    mov r1 #1
+    bln pstr_char_le  // r0 <- 'i'
+    halt
+    .include "../lib/pstring.s"
+
+.section[data]
+message:
+    .packed "Hello"
+greeting:
+    .packed_le "Hi!"
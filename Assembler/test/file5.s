@@ -0,0 +1,37 @@
+// Test File 5: Records. Links three nodes of a list in a buffer and adds
+// up their values, reading the fields at their offsets
+    .struct node
+    .field value
+    .field next  // Address of the next node, 0 at the end
+    .ends
+
+.section[code]
+start:
+    lda list
+    mov r2 mbr  // Node being linked
+    mov r1 #10
+link:
+    strr r1 [r2, #node.value]
+    add r3 r2 #node.size
+    strr r3 [r2, #node.next]
+    mov r2 r3
+    add r1 r1 #10
+    cmp r1 #40
+    bne link
+    sub r2 r2 #node.size
+    mov r3 #0
+    strr r3 [r2, #node.next]
+    lda list
+    mov r2 mbr
+    mov r0 #0
+sum:
+    ldr r1 [r2, #node.value]
+    add r0 r0 r1
+    ldr r2 [r2, #node.next]
+    cmp r2 #0
+    bne sum
+    halt  // r0 <- 60
+
+.section[data]
+list:
+    .space node.size * 3
@@ -0,0 +1,13 @@
+// Sums r1 words from the address in r2 into r0
+sum:
+    mov r0 #0
+sum_loop:
+    cmp r1 #0
+    beq sum_end
+    ldr r3 [r2]
+    add r0 r0 r3
+    add r2 r2 #1
+    sub r1 r1 #1
+    jmp sum_loop
+sum_end:
+    ret
@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use isa::Flow;
+use crate::decoder::decode;
+use crate::disassemble::format_instruction;
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+// Basic Block: Instructions from start up to end (not included) that always
+// run one after the other. Blocks start at the entry point, at labels and
+// at branch targets, and end after every instruction that changes the flow
+// of execution (jmp, branches, bln, ret and halt)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: u16,
+    pub end: u16
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    Taken,       // jmp, or a branch whose condition holds
+    FallThrough, // next block, also where a call returns to
+    Call         // bln to the start of a function
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub from: u16,
+    pub to: u16,
+    pub kind: EdgeKind
+}
+
+// Control-Flow Graph: Blocks of the code reachable from the entry point,
+// sorted by address, and the edges between them
+#[derive(Debug, Default)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<Edge>
+}
+
+// ***************************** GRAPH BUILDING **************************** //
+
+// Builds the control-flow graph of a program by following every path of
+// execution from entry. Labels also start a block, so blocks line up with
+// the source
+pub fn build_cfg(words: &[u16], entry: u16, labels: &BTreeMap<u16, String>) -> Cfg {
+    let decoded = |addr: usize| words.get(addr).and_then(|w| decode(*w));
+
+    // Reachable instructions, and the addresses every block starts at
+    let mut reachable: BTreeSet<u16> = BTreeSet::new();
+    let mut leaders: BTreeSet<u16> = BTreeSet::from([entry]);
+    let mut pending = vec![entry];
+    while let Some(addr) = pending.pop() {
+        let Some(instr) = decoded(addr as usize) else { continue };
+        if !reachable.insert(addr) {
+            continue;
+        }
+        if let Some(target) = instr.target() {
+            leaders.insert(target);
+            pending.push(target);
+        }
+        if instr.flow() != Flow::Next {
+            leaders.insert(addr + 1);
+        }
+        if instr.falls_through() {
+            pending.push(addr + 1);
+        }
+    }
+    leaders.extend(labels.keys());
+
+    let mut cfg = Cfg::default();
+    for &start in leaders.iter().filter(|a| reachable.contains(a)) {
+        let mut end = start + 1;
+        while reachable.contains(&end) && !leaders.contains(&end) {
+            end += 1;
+        }
+        cfg.blocks.push(BasicBlock { start, end });
+
+        let last = decoded(end as usize - 1).expect("Blocks only hold instructions");
+        let mut edge = |to: u16, kind| {
+            if reachable.contains(&to) {
+                cfg.edges.push(Edge { from: start, to, kind });
+            }
+        };
+        match (last.flow(), last.target()) {
+            (Flow::Jump, Some(t)) => edge(t, EdgeKind::Taken),
+            (Flow::Branch, Some(t)) => edge(t, EdgeKind::Taken),
+            (Flow::Call, Some(t)) => edge(t, EdgeKind::Call),
+            _ => ()
+        }
+        if last.falls_through() {
+            edge(end, EdgeKind::FallThrough);
+        }
+    }
+    cfg
+}
+
+// ****************************** DOT OUTPUT ******************************* //
+
+impl Cfg {
+    // Graphviz DOT graph with one box per block listing its instructions.
+    // Taken branches are solid, fall-through paths dashed and calls blue
+    pub fn to_dot(&self, words: &[u16], labels: &BTreeMap<u16, String>) -> String {
+        let mut out = String::from("digraph cfg {\n");
+        out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        for b in &self.blocks {
+            let mut text = String::new();
+            if let Some(name) = labels.get(&b.start) {
+                text.push_str(&format!("{}:\\l", name));
+            }
+            for addr in b.start..b.end {
+                let instr = decode(words[addr as usize]).expect("Blocks only hold instructions");
+                text.push_str(&format!("0x{:03X}  {}\\l", addr, format_instruction(&instr, labels)));
+            }
+            writeln!(out, "    b{:03x} [label=\"{}\"];", b.start, text.replace('"', "\\\"")).unwrap();
+        }
+        for e in &self.edges {
+            let style = match e.kind {
+                EdgeKind::Taken => "label=\"taken\"",
+                EdgeKind::FallThrough => "style=dashed",
+                EdgeKind::Call => "label=\"call\", color=blue"
+            };
+            writeln!(out, "    b{:03x} -> b{:03x} [{}];", e.from, e.to, style).unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassemble::program_labels;
+    use std::collections::HashMap;
+
+    // Words of test file 1 of the assembler (sum of an array)
+    const FILE1: [u16; 18] = [0x0100, 0x0300, 0x100D, 0x0AE0, 0xA105, 0xB00B,
+                              0x1C40, 0x4B70, 0x4241, 0x4121, 0x8804, 0x0860,
+                              0xE000, 0x0011, 0x0016, 0xFFFF, 0x0004, 0x0026];
+
+    #[test]
+    fn blocks_and_edges() {
+        let labels = program_labels(&FILE1, 0, &HashMap::new());
+        let cfg = build_cfg(&FILE1, 0, &labels);
+        let blocks: Vec<(u16, u16)> = cfg.blocks.iter().map(|b| (b.start, b.end)).collect();
+        assert_eq!(blocks, [(0, 4), (4, 6), (6, 11), (11, 13)]);
+        let edges: Vec<(u16, u16, EdgeKind)> = cfg.edges.iter()
+            .map(|e| (e.from, e.to, e.kind))
+            .collect();
+        assert_eq!(edges, [(0, 4, EdgeKind::FallThrough), (4, 11, EdgeKind::Taken),
+                           (4, 6, EdgeKind::FallThrough), (6, 4, EdgeKind::Taken)]);
+
+        let dot = cfg.to_dot(&FILE1, &labels);
+        assert!(dot.contains("    b004 [label=\"loc_004:\\l0x004  cmp r1 #5\\l0x005  beq loc_00b\\l\"];"));
+        assert!(dot.contains("    b004 -> b00b [label=\"taken\"];"));
+    }
+
+    #[test]
+    // A call links to the function and falls through to where it returns
+    fn call_edges() {
+        // bln 3; halt; (unreachable); ret
+        let words = [0x9003, 0xE000, 0x0000, 0x9800];
+        let cfg = build_cfg(&words, 0, &BTreeMap::new());
+        let edges: Vec<(u16, u16, EdgeKind)> = cfg.edges.iter()
+            .map(|e| (e.from, e.to, e.kind))
+            .collect();
+        assert_eq!(edges, [(0, 3, EdgeKind::Call), (0, 1, EdgeKind::FallThrough)]);
+        assert_eq!(cfg.blocks.len(), 3);
+    }
+}
@@ -0,0 +1,70 @@
+use colored::Colorize;
+use objformat::Format;
+
+pub struct CLI {
+    pub file_path: String,
+    pub map_path: Option<String>,
+    pub out_file: Option<String>,
+    pub format: Option<Format>,
+    pub cfg: bool
+}
+
+impl CLI {
+    // Parses the command line arguments:
+    //     disassembler [-m symbol_map] [-o out_file] [--format <format>] [--cfg]
+    //                  file.bin
+    // Without -o the disassembly is printed to the screen. With --cfg the
+    // control-flow graph is written as Graphviz DOT instead. Without
+    // --format the format is found from the file, see Format::detect
+    pub fn new(args: Vec<String>) -> Result<CLI, String> {
+        let mut file_path: Option<String> = None;
+        let mut a = CLI {
+            file_path: String::new(), map_path: None, out_file: None, format: None, cfg: false
+        };
+
+        let mut args = args.into_iter().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-m" => {
+                    a.map_path = Some(args.next().ok_or("Missing file name after -m")?);
+                }
+                "-o" => {
+                    a.out_file = Some(args.next().ok_or("Missing file name after -o")?);
+                }
+                "--format" => {
+                    let name = args.next().ok_or("Missing format name after --format")?;
+                    a.format = Some(input_format(&name)?);
+                }
+                "--cfg" => a.cfg = true,
+                flag if flag.starts_with('-') => {
+                    return Err(format!("Unknown flag '{}'", flag));
+                }
+                _ if file_path.is_some() => {
+                    return Err("Must pass in only one file as argument".to_string());
+                }
+                file => file_path = Some(file.to_string())
+            }
+        }
+
+        match file_path {
+            Some(f) => {
+                a.file_path = f;
+                Ok(a)
+            }
+            None => Err("Must pass in one binary file as argument, and optionally \
+                         pass flags".to_string())
+        }
+    }
+
+    pub fn print_error(msg: &str) {
+        println!("{} {}", "Error".red(), msg);
+    }
+}
+
+pub fn input_format(name: &str) -> Result<Format, String> {
+    match Format::from_name(name) {
+        Some(f) if !f.is_memory_init() => Ok(f),
+        _ => Err(format!("Unknown input format '{}'. Valid formats are: exe, bin, ihex, srec",
+                         name))
+    }
+}
@@ -0,0 +1,122 @@
+use isa::{Flow, InstrDef};
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+// Register names as the assembler accepts them. Registers 4 to 7 are
+// shown by their special purpose name, like the emulator does
+pub use isa::registers::REG_NAMES;
+
+// Operand: Decoded fields of an instruction, one variant per addressing
+// mode of the assembler's operand syntax
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Register(usize),
+    Immediate(usize),
+    Indirect(usize, usize),
+    Address(u16)
+}
+
+// Decoded instruction: mnemonic as written in assembly plus its operands
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub mnemonic: &'static str,
+    pub operands: Vec<Operand>,
+    flow: Flow
+}
+
+impl Instruction {
+    // How execution continues after this instruction
+    pub fn flow(&self) -> Flow {
+        self.flow
+    }
+
+    // Address this instruction may transfer control to, if any
+    pub fn target(&self) -> Option<u16> {
+        match (self.flow, self.operands.first()) {
+            (Flow::Jump | Flow::Branch | Flow::Call, Some(Operand::Address(a))) => Some(*a),
+            _ => None
+        }
+    }
+
+    // Address of memory this instruction refers to by label, if any
+    pub fn data_ref(&self) -> Option<u16> {
+        match (self.flow, self.operands.first()) {
+            (Flow::Next, Some(Operand::Address(a))) => Some(*a),
+            _ => None
+        }
+    }
+
+    // Whether execution can continue with the next word after this one
+    pub fn falls_through(&self) -> bool {
+        !matches!(self.flow, Flow::Jump | Flow::Return | Flow::Halt)
+    }
+}
+
+// ************************** DECODING FUNCTIONS *************************** //
+
+// Decodes a 16-bit word into an instruction. Returns None if the opcode
+// does not belong to any instruction (0x1D to 0x1F)
+pub fn decode(word: u16) -> Option<Instruction> {
+    let (def, values): (&InstrDef, _) = isa::decode(word)?;
+    // Push and pop registers are listed as separate operands
+    let operands = values.into_iter().flat_map(|v| match v {
+        isa::Operand::Register(r) => vec![Operand::Register(r as usize)],
+        isa::Operand::Immediate(v) => vec![Operand::Immediate(v as usize)],
+        isa::Operand::Indirect(r, o) => vec![Operand::Indirect(r as usize, o as usize)],
+        isa::Operand::Address(a) => vec![Operand::Address(a)],
+        isa::Operand::RegList(regs) => {
+            regs.into_iter().map(|r| Operand::Register(r as usize)).collect()
+        }
+    }).collect();
+    Some(Instruction { mnemonic: def.mnemonic, operands, flow: def.flow })
+}
+
+// Decodes a word only if the assembler writes the instruction back as the
+// same word: the bits no field uses must be 0, and push and pop must list
+// at least one register, as a count of 0 has no source form
+pub fn decode_exact(word: u16) -> Option<Instruction> {
+    let (def, values) = isa::decode(word)?;
+    let empty_list = values.iter().any(|v| matches!(v, isa::Operand::RegList(r) if r.is_empty()));
+    match isa::encode(def, &values) == word && !empty_list {
+        true => decode(word),
+        false => None
+    }
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Words taken from the hand disassembly of test file 1
+    fn decode_file1_words() {
+        assert_eq!(decode(0x0100).unwrap(),
+                   Instruction { mnemonic: "mov", flow: Flow::Next,
+                                 operands: vec![Operand::Register(1), Operand::Immediate(0)] });
+        assert_eq!(decode(0x1C40).unwrap(),
+                   Instruction { mnemonic: "ldr", flow: Flow::Next,
+                                 operands: vec![Operand::Register(4), Operand::Indirect(2, 0)] });
+        assert_eq!(decode(0x4B70).unwrap(),
+                   Instruction { mnemonic: "add", flow: Flow::Next,
+                                 operands: vec![Operand::Register(3), Operand::Register(3),
+                                                Operand::Register(4)] });
+        assert_eq!(decode(0xB00B).unwrap(),
+                   Instruction { mnemonic: "beq", flow: Flow::Branch,
+                                 operands: vec![Operand::Address(11)] });
+        assert_eq!(decode(0xE000).unwrap(),
+                   Instruction { mnemonic: "halt", flow: Flow::Halt, operands: vec![] });
+        assert_eq!(decode(0xF800), None);
+    }
+
+    #[test]
+    fn decode_push_count() {
+        // push r1 r2: only two of the three register fields are used
+        assert_eq!(decode(0x3142).unwrap().operands,
+                   vec![Operand::Register(1), Operand::Register(2)]);
+        assert!(decode_exact(0x3142).is_some());
+        // No registers, and a third register field set past the count
+        assert!(decode(0x3000).is_some() && decode_exact(0x3000).is_none());
+        assert!(decode_exact(0x3146).is_none());
+    }
+}
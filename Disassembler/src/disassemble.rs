@@ -0,0 +1,220 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::decoder::{decode, decode_exact, Instruction, Operand, REG_NAMES};
+
+// Maximum amount of data words written on a single line
+const DATA_WORDS_PER_LINE: usize = 8;
+
+// Column at which the address/word comment of each instruction starts
+const COMMENT_COLUMN: usize = 28;
+
+// ***************************** SYMBOL MAPS ******************************* //
+
+// Parses a symbol map as written by the assembler with '-m': one label per
+// line as '<address> <name>', where the address may be hex (0x) or decimal.
+// Empty lines and lines starting with '//' are ignored
+pub fn parse_symbol_map(text: &str) -> Result<HashMap<u16, String>, String> {
+    let mut names = HashMap::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+        let bad_line = || format!("Invalid symbol map entry '{}' on line {}", line, idx + 1);
+        let mut fields = line.split_whitespace();
+        let (Some(addr), Some(name), None) = (fields.next(), fields.next(), fields.next())
+        else { return Err(bad_line()) };
+        let addr = match addr.strip_prefix("0x") {
+            Some(hex) => u16::from_str_radix(hex, 16),
+            None => addr.parse::<u16>()
+        }.map_err(|_| bad_line())?;
+        // Keep the first name given to an address, like the listing order
+        names.entry(addr).or_insert_with(|| name.to_string());
+    }
+    Ok(names)
+}
+
+// *************************** DISASSEMBLY PASSES ************************** //
+
+// Finds which words are code by following every path of execution from
+// the entry point and from address 0, where programs without one start.
+// Returns the end of the code section: every word before it is
+// disassembled as an instruction and every word after it as data
+fn code_section_end(words: &[u16], entry: u16) -> usize {
+    let mut visited: HashSet<usize> = HashSet::new();
+    let mut pending: Vec<usize> = vec![0, entry as usize];
+    let mut end = 0;
+
+    while let Some(addr) = pending.pop() {
+        if addr >= words.len() || !visited.insert(addr) {
+            continue;
+        }
+        let Some(instr) = decode(words[addr]) else { continue };
+        end = end.max(addr + 1);
+        if instr.falls_through() {
+            pending.push(addr + 1);
+        }
+        if let Some(target) = instr.target() {
+            pending.push(target as usize);
+        }
+    }
+    end
+}
+
+// Names every address that needs a label: names from the symbol map are
+// used as given, and branch targets and data references without a name
+// get a synthesized one
+fn label_names(words: &[u16], code_end: usize, names: &HashMap<u16, String>)
+-> BTreeMap<u16, String> {
+    let mut labels: BTreeMap<u16, String> = names.iter()
+        .map(|(a, n)| (*a, n.clone()))
+        .collect();
+    for word in &words[..code_end] {
+        let Some(instr) = decode_exact(*word) else { continue };
+        if let Some(target) = instr.target() {
+            labels.entry(target).or_insert_with(|| format!("loc_{:03x}", target));
+        }
+        if let Some(data) = instr.data_ref() {
+            labels.entry(data).or_insert_with(|| format!("data_{:03x}", data));
+        }
+    }
+    labels
+}
+
+// Every label of a program: the names of the symbol map plus the labels
+// synthesized for branch targets and data references
+pub fn program_labels(words: &[u16], entry: u16, names: &HashMap<u16, String>)
+-> BTreeMap<u16, String> {
+    label_names(words, code_section_end(words, entry), names)
+}
+
+// ************************** OUTPUT FORMATTING **************************** //
+
+// Formats a decoded instruction in the syntax accepted by the assembler
+pub fn format_instruction(instr: &Instruction, labels: &BTreeMap<u16, String>) -> String {
+    let operands: Vec<String> = instr.operands.iter().map(|op| match op {
+        Operand::Register(r) => REG_NAMES[*r].to_string(),
+        Operand::Immediate(v) => format!("#{}", v),
+        Operand::Indirect(r, 0) => format!("[{}]", REG_NAMES[*r]),
+        Operand::Indirect(r, ofst) => format!("[{}, #{}]", REG_NAMES[*r], ofst),
+        Operand::Address(a) => match labels.get(a) {
+            Some(name) => name.clone(),
+            None => format!("loc_{:03x}", a)
+        }
+    }).collect();
+
+    if operands.is_empty() {
+        instr.mnemonic.to_string()
+    } else {
+        format!("{} {}", instr.mnemonic, operands.join(" "))
+    }
+}
+
+// Formats a word of the code section. Words the assembler could not write
+// back as the same instruction are kept as they are with '.word'
+pub fn format_word(word: u16, labels: &BTreeMap<u16, String>) -> String {
+    match decode_exact(word) {
+        Some(instr) => format_instruction(&instr, labels),
+        None => format!(".word 0x{:04X}", word)
+    }
+}
+
+// Disassembles a program into source that the assembler turns back into
+// the same words. Labels that point past the end of the program are
+// written after the last word, with a '.org' to reach their address
+pub fn disassemble(words: &[u16], entry: u16, names: &HashMap<u16, String>) -> String {
+    let code_end = code_section_end(words, entry);
+    let labels = label_names(words, code_end, names);
+    let mut out = String::new();
+    let label_line = |out: &mut String, addr: usize| {
+        if let Some(name) = labels.get(&(addr as u16)) {
+            out.push_str(&format!("{}:\n", name));
+        }
+    };
+
+    // Code section: one instruction per word
+    if code_end > 0 {
+        out.push_str(".section[code]\n");
+    }
+    for (addr, word) in words.iter().enumerate().take(code_end) {
+        label_line(&mut out, addr);
+        let comment = format!("// 0x{:03X} | 0x{:04X}", addr, word);
+        let text = format!("    {}", format_word(*word, &labels));
+        out.push_str(&format!("{:<width$}{}\n", text, comment, width = COMMENT_COLUMN));
+    }
+
+    // Data section: signed words, split into lines at every label
+    if code_end < words.len() {
+        out.push_str("\n.section[data]\n");
+    }
+    let mut addr = code_end;
+    while addr < words.len() {
+        label_line(&mut out, addr);
+        let mut line: Vec<String> = vec![(words[addr] as i16).to_string()];
+        addr += 1;
+        while addr < words.len() && line.len() < DATA_WORDS_PER_LINE &&
+              !labels.contains_key(&(addr as u16)) {
+            line.push((words[addr] as i16).to_string());
+            addr += 1;
+        }
+        out.push_str(&format!("    {}\n", line.join(", ")));
+    }
+
+    // Labels at or past the end of the program. The ones past it leave
+    // zeros up to their address when assembled again
+    for (addr, name) in labels.range(words.len() as u16..) {
+        if *addr as usize > words.len() {
+            out.push_str(&format!("    .org 0x{:03X}\n", addr));
+        }
+        out.push_str(&format!("{}:\n", name));
+    }
+    out
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Words of test file 1 of the assembler (sum of an array)
+    const FILE1: [u16; 18] = [0x0100, 0x0300, 0x100D, 0x0AE0, 0xA105, 0xB00B,
+                              0x1C40, 0x4B70, 0x4241, 0x4121, 0x8804, 0x0860,
+                              0xE000, 0x0011, 0x0016, 0xFFFF, 0x0004, 0x0026];
+
+    #[test]
+    fn sections_and_labels() {
+        let text = disassemble(&FILE1, 0, &HashMap::new());
+        assert!(text.contains("loc_004:\n    cmp r1 #5"));
+        assert!(text.contains("    beq loc_00b"));
+        assert!(text.contains("    lda data_00d"));
+        assert!(text.contains("    ldr fp [r2]"));
+        assert!(text.contains(".section[data]\ndata_00d:\n    17, 22, -1, 4, 38\n"));
+
+        // An undefined opcode and a push of no registers inside the code
+        let mut words = FILE1;
+        words[7] = 0xF800;
+        words[8] = 0x3000;
+        let text = disassemble(&words, 0, &HashMap::new());
+        assert!(text.contains("    .word 0xF800            // 0x007 | 0xF800\n    \
+                               .word 0x3000            // 0x008 | 0x3000\n"));
+    }
+
+    #[test]
+    fn entry_point_and_labels_past_the_end() {
+        // Only reachable from the entry point, and loads past the end
+        let words = [0xE000, 0x100D, 0xE000];
+        let text = disassemble(&words, 1, &HashMap::new());
+        assert!(text.contains("    lda data_00d"));
+        assert!(text.ends_with("    .org 0x00D\ndata_00d:\n"));
+        assert!(!disassemble(&words, 0, &HashMap::new()).contains("lda"));
+    }
+
+    #[test]
+    fn symbol_map_names() {
+        let names = parse_symbol_map("// map\n0x0004 loop\n13 arr\n").unwrap();
+        let text = disassemble(&FILE1, 0, &names);
+        assert!(text.contains("loop:\n    cmp r1 #5"));
+        assert!(text.contains("    jmp loop"));
+        assert!(text.contains("    lda arr"));
+        assert!(parse_symbol_map("0x0004").is_err());
+    }
+}
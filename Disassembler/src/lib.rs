@@ -0,0 +1,4 @@
+pub mod decoder;
+pub mod disassemble;
+pub mod cfg;
+pub mod cli;
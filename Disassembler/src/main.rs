@@ -0,0 +1,64 @@
+use std::env;
+use std::fs;
+use std::process::exit;
+
+use disassembler::cfg::build_cfg;
+use disassembler::disassemble::{disassemble, parse_symbol_map, program_labels};
+use disassembler::cli::CLI;
+use objformat::{read_image, Format};
+
+fn main() {
+    let cli = match CLI::new(env::args().collect()) {
+        Ok(cli) => cli,
+        Err(msg) => {
+            CLI::print_error(&msg);
+            exit(1);
+        }
+    };
+
+    // Executables, raw binaries, Intel HEX and S-record files
+    let bytes = match fs::read(&cli.file_path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            CLI::print_error(&format!("Could not read '{}': {}", cli.file_path, e));
+            exit(1);
+        }
+    };
+    let format = cli.format.unwrap_or_else(|| Format::detect(&bytes, &cli.file_path));
+    let image = read_image(&bytes, format).unwrap_or_else(|e| {
+        CLI::print_error(&e.to_string());
+        exit(1);
+    });
+    let words = image.flatten();
+
+    // Optional symbol map with the real label names. Executables carry
+    // their own unless they were stripped
+    let map = match &cli.map_path {
+        Some(path) => Some(fs::read_to_string(path).unwrap_or_else(|e| {
+            CLI::print_error(&format!("Could not read '{}': {}", path, e));
+            exit(1);
+        })),
+        None => image.symbols()
+    };
+    let names = match map {
+        Some(text) => parse_symbol_map(&text).unwrap_or_else(|msg| {
+            CLI::print_error(&msg);
+            exit(1);
+        }),
+        None => Default::default()
+    };
+
+    let source = match cli.cfg {
+        true => {
+            let labels = program_labels(&words, image.entry, &names);
+            format!("// Control flow of {}\n{}", cli.file_path,
+                    build_cfg(&words, image.entry, &labels).to_dot(&words, &labels))
+        }
+        false => format!("// Disassembly of {}\n{}", cli.file_path,
+                         disassemble(&words, image.entry, &names))
+    };
+    match &cli.out_file {
+        Some(path) => fs::write(path, source).expect("Could not write output file"),
+        None => print!("{}", source)
+    }
+}
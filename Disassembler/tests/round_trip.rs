@@ -0,0 +1,233 @@
+// Round-trip tests between the assembler, the disassembler and the
+// emulator's decoder. Every opcode is generated with every legal
+// combination of operands and, for each one, the test checks that:
+//     1. the fields the emulator reads from the assembled word are the
+//        operands written in the source
+//     2. the disassembler decodes the same operands
+//     3. assembling the disassembled text yields the identical word
+// and every other word is disassembled to a '.word' of itself
+use std::collections::BTreeMap;
+use assembler::encoder::encode_instruction;
+use assembler::expr::parse_expr;
+use assembler::operands::{parse_operands, Operand as AsmOperand};
+use assembler::symbols::Symbols;
+use disassembler::decoder::{decode, Operand};
+use disassembler::disassemble::format_word;
+use emulator::cpu_cycle;
+use emulator::instructions::Opcode;
+use isa::fields::{self, REG0, REG1, REG2, IMM8, IMM5, IMM4, OFFSET, REG_NUM};
+
+const REGS: [&str; 8] = ["r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7"];
+const LABEL: &str = "target";
+const MEMORY_WORDS: u16 = 2048;
+
+// One generated test case: source line plus the address of its label
+struct Case {
+    mnemonic: &'static str,
+    args: String,
+    label_addr: u16
+}
+
+fn case(mnemonic: &'static str, args: String) -> Case {
+    Case { mnemonic, args, label_addr: 0 }
+}
+
+// ************************** CASE GENERATION ****************************** //
+
+fn reg_pairs() -> impl Iterator<Item = (&'static str, &'static str)> {
+    REGS.iter().flat_map(|a| REGS.iter().map(move |b| (*a, *b)))
+}
+
+fn reg_triples() -> impl Iterator<Item = (&'static str, &'static str, &'static str)> {
+    reg_pairs().flat_map(|(a, b)| REGS.iter().map(move |c| (a, b, *c)))
+}
+
+// Every legal source line of every mnemonic, in all accepted syntaxes
+fn all_cases() -> Vec<Case> {
+    let mut cases = Vec::new();
+
+    for m in ["mov", "cmp"] {
+        for r in REGS {
+            cases.extend((-128..=255).map(|v| case(m, format!("{} #{}", r, v))));
+        }
+        cases.extend(reg_pairs().map(|(a, b)| case(m, format!("{}, {}", a, b))));
+    }
+    for m in ["lda", "stra", "jmp", "bln", "beq", "bne", "bgt", "bgtu", "blt", "bltu"] {
+        for addr in 0..MEMORY_WORDS {
+            cases.push(Case { mnemonic: m, args: LABEL.to_string(), label_addr: addr });
+            cases.push(Case { mnemonic: m, args: format!("&{}", LABEL), label_addr: addr });
+        }
+    }
+    for m in ["ldr", "strr"] {
+        for (a, b) in reg_pairs() {
+            cases.push(case(m, format!("{} [{}]", a, b)));
+            cases.push(case(m, format!("{} &{}", a, b)));
+            for ofst in 0..=31 {
+                cases.push(case(m, format!("{}, [{}, #{}]", a, b, ofst)));
+                cases.push(case(m, format!("{} &{} #{}", a, b, ofst)));
+            }
+        }
+    }
+    for m in ["push", "pop"] {
+        cases.extend(REGS.iter().map(|a| case(m, a.to_string())));
+        cases.extend(reg_pairs().map(|(a, b)| case(m, format!("{} {}", a, b))));
+        cases.extend(reg_triples().map(|(a, b, c)| case(m, format!("{} {} {}", a, b, c))));
+    }
+    for m in ["add", "sub"] {
+        for (a, b) in reg_pairs() {
+            cases.extend((0..=31).map(|v| case(m, format!("{} {} #{}", a, b, v))));
+        }
+    }
+    for m in ["add", "sub", "and", "or"] {
+        cases.extend(reg_triples().map(|(a, b, c)| case(m, format!("{} {} {}", a, b, c))));
+    }
+    for m in ["shl", "shr"] {
+        for (a, b) in reg_pairs() {
+            cases.extend((0..=15).map(|v| case(m, format!("{} {} #{:#x}", a, b, v))));
+        }
+    }
+    cases.extend(reg_pairs().map(|(a, b)| case("not", format!("{} {}", a, b))));
+    cases.push(case("ret", String::new()));
+    cases.push(case("halt", String::new()));
+    cases
+}
+
+// ***************************** HELPERS ********************************* //
+
+fn symbols(label_addr: u16) -> Symbols {
+    let mut syms = Symbols::new();
+    syms.labels.insert(LABEL.to_string(), label_addr);
+    syms
+}
+
+fn assemble(mnemonic: &str, args: &str, syms: &Symbols) -> u16 {
+    let operands = parse_operands(args, 0)
+        .unwrap_or_else(|e| panic!("'{} {}' does not parse: {:?}", mnemonic, args, e));
+    let bytes = encode_instruction(mnemonic, &operands, syms, 0).unwrap()
+        .unwrap_or_else(|e| panic!("'{} {}' does not assemble: {:?}", mnemonic, args, e));
+    u16::from_be_bytes(bytes)
+}
+
+// Numeric value of every field written in the source, truncated to the
+// width of its field in the instruction
+fn source_fields(mnemonic: &str, args: &str, syms: &Symbols) -> Vec<usize> {
+    let width_mask = match mnemonic {
+        "mov" | "cmp" => 0xFF,
+        "shl" | "shr" => 0x0F,
+        _ => 0x1F
+    };
+    parse_operands(args, 0).unwrap().iter().flat_map(|op| match op {
+        AsmOperand::Register(r) => vec![*r as usize],
+        AsmOperand::Immediate(v) => vec![(*v & width_mask) as usize],
+        AsmOperand::Indirect(r) => vec![*r as usize, 0],
+        AsmOperand::IndirectOffset(r, o) => vec![*r as usize, *o as usize],
+        AsmOperand::Label(l) => vec![syms.labels[l] as usize]
+    }).collect()
+}
+
+// Fields of a word as the emulator reads them when executing it, together
+// with the mnemonic of the opcode the emulator decodes
+fn emulator_fields(word: u16) -> (&'static str, Vec<usize>) {
+    let [reg0, reg1, reg2, b8, b5, b4, label, ofst, num] =
+        [REG0, REG1, REG2, IMM8, IMM5, IMM4, fields::LABEL, OFFSET, REG_NUM]
+            .map(|f| f.extract(word) as usize);
+    let pushed: Vec<usize> = [REG0, REG1, REG2].iter().take(num)
+        .map(|f| f.extract(word) as usize)
+        .collect();
+
+    match Opcode::from_u8(cpu_cycle::decode(word)) {
+        Some(Opcode::MovIm)  => ("mov", vec![reg0, b8]),
+        Some(Opcode::MovRg)  => ("mov", vec![reg0, reg1]),
+        Some(Opcode::Load)   => ("lda", vec![label]),
+        Some(Opcode::LoadRg) => ("ldr", vec![reg0, reg1, ofst]),
+        Some(Opcode::Store)  => ("stra", vec![label]),
+        Some(Opcode::StrRg)  => ("strr", vec![reg0, reg1, ofst]),
+        Some(Opcode::Push)   => ("push", pushed),
+        Some(Opcode::Pop)    => ("pop", pushed),
+        Some(Opcode::AddIm)  => ("add", vec![reg0, reg1, b5]),
+        Some(Opcode::AddRg)  => ("add", vec![reg0, reg1, reg2]),
+        Some(Opcode::SubIm)  => ("sub", vec![reg0, reg1, b5]),
+        Some(Opcode::SubRg)  => ("sub", vec![reg0, reg1, reg2]),
+        Some(Opcode::ShftL)  => ("shl", vec![reg0, reg1, b4]),
+        Some(Opcode::ShftR)  => ("shr", vec![reg0, reg1, b4]),
+        Some(Opcode::And)    => ("and", vec![reg0, reg1, reg2]),
+        Some(Opcode::Or)     => ("or", vec![reg0, reg1, reg2]),
+        Some(Opcode::Not)    => ("not", vec![reg0, reg1]),
+        Some(Opcode::Jmp)    => ("jmp", vec![label]),
+        Some(Opcode::Bln)    => ("bln", vec![label]),
+        Some(Opcode::Ret)    => ("ret", vec![]),
+        Some(Opcode::CmpIm)  => ("cmp", vec![reg0, b8]),
+        Some(Opcode::CmpRg)  => ("cmp", vec![reg0, reg1]),
+        Some(Opcode::Beq)    => ("beq", vec![label]),
+        Some(Opcode::Bne)    => ("bne", vec![label]),
+        Some(Opcode::Bgt)    => ("bgt", vec![label]),
+        Some(Opcode::Bgtu)   => ("bgtu", vec![label]),
+        Some(Opcode::Blt)    => ("blt", vec![label]),
+        Some(Opcode::Bltu)   => ("bltu", vec![label]),
+        Some(Opcode::Halt)   => ("halt", vec![]),
+        None => panic!("Emulator does not recognize word {:#06x}", word)
+    }
+}
+
+fn disassembler_fields(operands: &[Operand]) -> Vec<usize> {
+    operands.iter().flat_map(|op| match op {
+        Operand::Register(r) | Operand::Immediate(r) => vec![*r],
+        Operand::Indirect(r, o) => vec![*r, *o],
+        Operand::Address(a) => vec![*a as usize]
+    }).collect()
+}
+
+// ******************************* TESTS ********************************* //
+
+#[test]
+fn assemble_decode_disassemble_reassemble() {
+    let cases = all_cases();
+    let mut opcodes_seen = [false; 32];
+
+    for c in &cases {
+        let syms = symbols(c.label_addr);
+        let line = format!("{} {}", c.mnemonic, c.args);
+        let word = assemble(c.mnemonic, &c.args, &syms);
+        let expected = source_fields(c.mnemonic, &c.args, &syms);
+        opcodes_seen[(word >> 11) as usize] = true;
+
+        let (emu_mnemonic, emu_fields) = emulator_fields(word);
+        assert_eq!((emu_mnemonic, &emu_fields), (c.mnemonic, &expected),
+                   "Emulator decodes '{}' ({:#06x}) differently", line, word);
+
+        let instr = decode(word).unwrap_or_else(|| panic!("'{}' does not decode", line));
+        assert_eq!((instr.mnemonic, disassembler_fields(&instr.operands)),
+                   (c.mnemonic, expected),
+                   "Disassembler decodes '{}' ({:#06x}) differently", line, word);
+
+        let labels = BTreeMap::from([(c.label_addr, LABEL.to_string())]);
+        let text = format_word(word, &labels);
+        let (mnemonic, args) = text.split_once(' ').unwrap_or((&text, ""));
+        assert_eq!(assemble(mnemonic, args, &syms), word,
+                   "'{}' disassembles to '{}' which assembles differently", line, text);
+    }
+
+    // Every opcode of the instruction set was generated (0x00 to 0x1C)
+    assert!(opcodes_seen[..=0x1C].iter().all(|seen| *seen));
+}
+
+#[test]
+// Every word disassembles to a line the assembler turns back into the same
+// word: an instruction when one encodes to it, otherwise a '.word'
+fn every_word_reassembles() {
+    let labels: BTreeMap<u16, String> = (0..MEMORY_WORDS).map(|a| (a, format!("l{}", a))).collect();
+    let mut syms = Symbols::new();
+    syms.labels.extend(labels.iter().map(|(a, l)| (l.clone(), *a)));
+
+    for word in 0..=u16::MAX {
+        let text = format_word(word, &labels);
+        let reassembled = match text.strip_prefix(".word ") {
+            Some(value) => parse_expr(value, 0).unwrap().eval(&syms.labels, 0).unwrap() as u16,
+            None => {
+                let (mnemonic, args) = text.split_once(' ').unwrap_or((&text, ""));
+                assemble(mnemonic, args, &syms)
+            }
+        };
+        assert_eq!(reassembled, word, "{:#06x} disassembles to '{}'", word, text);
+    }
+}
@@ -1,26 +1,53 @@
+use std::process::exit;
 use colored::Colorize;
+use objformat::Format;
 use crate::instructions::Opcode;
-use num_traits::FromPrimitive;
+use crate::source::SourceView;
 
 pub struct CLI {
     pub debug: bool,
+    pub format: Option<Format>,
     pub file_path: Option<String>
 }
 
 impl CLI {
+    // Parses the command line arguments:
+    //     emulator [-DEBUG] [--format <format>] file
+    // Without --format the format is found from the file, see
+    // Format::detect. Prints the error and exits on wrong arguments
     pub fn new(args: Vec<String>) -> CLI {
-        let mut a = CLI{debug: false, file_path: None};
-        match args.len() {
-            2                         => a.file_path = Some(args[1].clone()),
-            3 if &args[1] == "-DEBUG" => {
-                a.debug = true; a.file_path = Some(args[2].clone())
-            }
-            _                         => {
-                println!("{} Must pass in one file as argument, and optionally pass \
-                        flags", "Error".red())
+        let mut a = CLI { debug: false, format: None, file_path: None };
+        if let Err(msg) = a.parse(args) {
+            println!("{} {}", "Error".red(), msg);
+            exit(1);
+        }
+        a
+    }
+
+    fn parse(&mut self, args: Vec<String>) -> Result<(), String> {
+        let mut args = args.into_iter().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-DEBUG" => self.debug = true,
+                "--format" => {
+                    let name = args.next().ok_or("Missing format name after --format")?;
+                    self.format = match Format::from_name(&name) {
+                        Some(f) if !f.is_memory_init() => Some(f),
+                        _ => return Err(format!("Unknown input format '{}'. Valid formats \
+                                                 are: exe, bin, ihex, srec", name))
+                    };
+                }
+                flag if flag.starts_with('-') => return Err(format!("Unknown flag '{}'", flag)),
+                _ if self.file_path.is_some() => {
+                    return Err("Must pass in only one file as argument".to_string());
+                }
+                file => self.file_path = Some(file.to_string())
             }
         }
-        return a;
+        match self.file_path {
+            Some(_) => Ok(()),
+            None => Err("Must pass in one file as argument, and optionally pass flags".to_string())
+        }
     }
 
     pub fn print_debug_welcome() {
@@ -33,39 +60,19 @@ impl CLI {
         print!("{esc}[2J{esc}[1;1H", esc = 27 as char);
     }
 
-    pub fn print_curr_instruction(opcode: u8) -> () {
-        let s = match FromPrimitive::from_u8(opcode) {
-            Some(Opcode::MovIm)  => "MOV immediate",
-            Some(Opcode::MovRg)  => "MOV with registers",
-            Some(Opcode::Load)   => "LDA",
-            Some(Opcode::LoadRg) => "LDR",
-            Some(Opcode::Store)  => "STRA",
-            Some(Opcode::StrRg)  => "STRR",
-            Some(Opcode::Push)   => "PUSH",
-            Some(Opcode::Pop)    => "POP",
-            Some(Opcode::AddIm)  => "ADD Immediate",
-            Some(Opcode::AddRg)  => "ADD with registers",
-            Some(Opcode::SubIm)  => "SUB Immediate",
-            Some(Opcode::SubRg)  => "SUB with registers",
-            Some(Opcode::ShftL)  => "SHL",
-            Some(Opcode::ShftR)  => "SHR",
-            Some(Opcode::And)    => "AND",
-            Some(Opcode::Or)     => "OR",
-            Some(Opcode::Not)    => "NOT",
-            Some(Opcode::Jmp)    => "JMP",
-            Some(Opcode::Bln)    => "BLN",
-            Some(Opcode::Ret)    => "RET",
-            Some(Opcode::CmpIm)  => "CMP Immediate",
-            Some(Opcode::CmpRg)  => "CMP with registers",
-            Some(Opcode::Beq)    => "BEQ",
-            Some(Opcode::Bne)    => "BNE",
-            Some(Opcode::Bgt)    => "BGT",
-            Some(Opcode::Bgtu)   => "BGTU",
-            Some(Opcode::Blt)    => "BLT",
-            Some(Opcode::Bltu)   => "BLTU",
-            Some(Opcode::Halt)   => "HALT",
-            _ => "Unrecognized Opcode"
+    pub fn print_curr_instruction(opcode: u8) {
+        let s = match Opcode::from_u8(opcode) {
+            Some(op) => op.def().description,
+            None => "Unrecognized Opcode"
         };
         println!("Current instruction: {}\n", s.bold());
     }
-}
\ No newline at end of file
+
+    // Source line of the instruction at addr, for programs assembled with
+    // debug information
+    pub fn print_source_line(source: &SourceView, addr: u16) {
+        if let Some((position, text)) = source.line_at(addr) {
+            println!("{}  {}", position.cyan(), text.unwrap_or(""));
+        }
+    }
+}
@@ -2,67 +2,130 @@ use std::ops::ControlFlow;
 
 use crate::instructions::{Opcode, execute};
 use crate::registers::Registers;
-use num_traits::FromPrimitive;
 
 // Fetch stage: returns the instruction in RAM specified by pc
-pub fn fetch (pc: u16, ram: &Vec<u16>) -> u16 {
+pub fn fetch (pc: u16, ram: &[u16]) -> u16 {
     ram[pc as usize]
 }
 
 // Decode stage: Obtains a 5-bit value from 16-bit register to
 //               use as opcode
 pub fn decode (ir_reg: u16) -> u8 {
-    const OPCODE_SHIFT_OFFSET: u16 = 11;
-    // 16-bit register offseted by 11 bits to retrieve
-    // only the value of the 5 bits of the opcode
-    (ir_reg >> OPCODE_SHIFT_OFFSET) as u8
+    isa::opcode_of(ir_reg)
 }
 
 // Execute Stage: Matches a given opcode to corresponding instruction
 //                and executes it
-pub fn execute(opcode: u8, mut regs: &mut Registers, mut mem: &mut Vec<u16>) 
+pub fn execute(opcode: u8, regs: &mut Registers, mem: &mut Vec<u16>) 
 -> Option<ControlFlow<()>> {
     let skip_pc_increment: Option<ControlFlow<()>> = Some(ControlFlow::Continue(()));
 
-    match FromPrimitive::from_u8(opcode) {
-        Some(Opcode::MovIm)  => {execute::mov_im(&mut regs); None}
-        Some(Opcode::MovRg)  => {execute::mov_rg(&mut regs); None}
-        Some(Opcode::Load)   => {execute::load(&mut regs); None}
-        Some(Opcode::LoadRg) => {execute::load_rg(&mut regs, &mem); None}
-        Some(Opcode::Store)  => {execute::store(&regs, &mut mem); None}
-        Some(Opcode::StrRg)  => {execute::store_rg(&regs, &mut mem); None}
-        Some(Opcode::Push)   => {execute::push(&mut regs, &mut mem); None}
-        Some(Opcode::Pop)    => {execute::pop(&mut regs, &mem); None}
-        Some(Opcode::AddIm)  => {execute::add_im(&mut regs); None}
-        Some(Opcode::AddRg)  => {execute::add_rg(&mut regs); None}
-        Some(Opcode::SubIm)  => {execute::sub_im(&mut regs); None}
-        Some(Opcode::SubRg)  => {execute::sub_rg(&mut regs); None}
-        Some(Opcode::ShftL)  => {execute::shift_l(&mut regs); None}
-        Some(Opcode::ShftR)  => {execute::shift_r(&mut regs); None}
-        Some(Opcode::And)    => {execute::and(&mut regs); None}
-        Some(Opcode::Or)     => {execute::or(&mut regs); None}
-        Some(Opcode::Not)    => {execute::not(&mut regs); None}
-        Some(Opcode::Jmp)    => {execute::jmp(&mut regs); 
+    match Opcode::from_u8(opcode) {
+        Some(Opcode::MovIm)  => {execute::mov_im(regs); None}
+        Some(Opcode::MovRg)  => {execute::mov_rg(regs); None}
+        Some(Opcode::Load)   => {execute::load(regs); None}
+        Some(Opcode::LoadRg) => {execute::load_rg(regs, mem); None}
+        Some(Opcode::Store)  => {execute::store(regs, mem); None}
+        Some(Opcode::StrRg)  => {execute::store_rg(regs, mem); None}
+        Some(Opcode::Push)   => {execute::push(regs, mem); None}
+        Some(Opcode::Pop)    => {execute::pop(regs, mem); None}
+        Some(Opcode::AddIm)  => {execute::add_im(regs); None}
+        Some(Opcode::AddRg)  => {execute::add_rg(regs); None}
+        Some(Opcode::SubIm)  => {execute::sub_im(regs); None}
+        Some(Opcode::SubRg)  => {execute::sub_rg(regs); None}
+        Some(Opcode::ShftL)  => {execute::shift_l(regs); None}
+        Some(Opcode::ShftR)  => {execute::shift_r(regs); None}
+        Some(Opcode::And)    => {execute::and(regs); None}
+        Some(Opcode::Or)     => {execute::or(regs); None}
+        Some(Opcode::Not)    => {execute::not(regs); None}
+        Some(Opcode::Jmp)    => {execute::jmp(regs); 
                                  skip_pc_increment}
-        Some(Opcode::Bln)    => {execute::bln(&mut regs); 
+        Some(Opcode::Bln)    => {execute::bln(regs); 
                                  skip_pc_increment}
-        Some(Opcode::Ret)    => {execute::ret(&mut regs);
+        Some(Opcode::Ret)    => {execute::ret(regs);
                                  skip_pc_increment}
-        Some(Opcode::CmpIm)  => {execute::cmp_im(&mut regs); None}
-        Some(Opcode::CmpRg)  => {execute::cmp_rg(&mut regs); None}
-        Some(Opcode::Beq)    => {execute::beq(&mut regs);
+        Some(Opcode::CmpIm)  => {execute::cmp_im(regs); None}
+        Some(Opcode::CmpRg)  => {execute::cmp_rg(regs); None}
+        Some(Opcode::Beq)    => {execute::beq(regs);
                                  skip_pc_increment}
-        Some(Opcode::Bne)    => {execute::bne(&mut regs);
+        Some(Opcode::Bne)    => {execute::bne(regs);
                                  skip_pc_increment}
-        Some(Opcode::Bgt)    => {execute::bgt(&mut regs) ;
+        Some(Opcode::Bgt)    => {execute::bgt(regs);
                                  skip_pc_increment}
-        Some(Opcode::Bgtu)   => {execute::bgtu(&mut regs) ;
+        Some(Opcode::Bgtu)   => {execute::bgtu(regs);
                                  skip_pc_increment}
-        Some(Opcode::Blt)    => {execute::blt(&mut regs) ;
+        Some(Opcode::Blt)    => {execute::blt(regs);
                                  skip_pc_increment}
-        Some(Opcode::Bltu)   => {execute::bltu(&mut regs) ;
+        Some(Opcode::Bltu)   => {execute::bltu(regs);
                                  skip_pc_increment}
         Some(Opcode::Halt)   => {Some(ControlFlow::Break(()))},
         _ => panic!("Unrecognized Opcode"),
     }
-}
\ No newline at end of file
+}
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use isa::Operand;
+
+    #[test]
+    // Returning from a subroutine continues after the bln that called it
+    fn bln_then_ret() {
+        let word = |op: Opcode, operands: &[Operand]| isa::encode(op.def(), operands);
+        let mut mem = vec![word(Opcode::Bln, &[Operand::Address(3)]),
+                           word(Opcode::Halt, &[]),
+                           0,
+                           word(Opcode::Ret, &[])];
+        let mut regs = Registers::new();
+        for _ in 0..10 {
+            regs.ir = fetch(regs.pc, &mem);
+            match execute(decode(regs.ir), &mut regs, &mut mem) {
+                Some(ControlFlow::Continue(_)) => continue,
+                Some(ControlFlow::Break(_)) => break,
+                None => ()
+            }
+            regs.pc += 1;
+        }
+        assert_eq!(regs.pc, 1, "Program did not halt after returning");
+    }
+
+    #[test]
+    // The build area after the program is out of reach of a stack that
+    // starts at the top of memory, so its CRC still holds after running
+    fn stack_keeps_build_area() {
+        use objformat::{Image, Section, MEMORY_DEPTH};
+        use objformat::build::{crc16, BuildInfo, BUILD_WORDS};
+        use isa::registers::SP_PTR;
+
+        let word = |op: Opcode, operands: &[Operand]| isa::encode(op.def(), operands);
+        let (reg, imm) = (Operand::Register, Operand::Immediate);
+        let code = vec![word(Opcode::MovIm, &[reg(1), imm(1)]),
+                        word(Opcode::ShftL, &[reg(1), reg(1), imm(11)]),
+                        word(Opcode::MovRg, &[reg(SP_PTR as u8), reg(1)]),
+                        word(Opcode::MovIm, &[reg(2), imm(7)]),
+                        word(Opcode::Push, &[Operand::RegList(vec![2, 3, 4])]),
+                        word(Opcode::Bln, &[Operand::Address(8)]),
+                        word(Opcode::Pop, &[Operand::RegList(vec![2, 3, 4])]),
+                        word(Opcode::Halt, &[]),
+                        word(Opcode::Ret, &[])];
+        let info = BuildInfo { id: "stack".to_string(), source_hash: 1, crc: crc16(&code) };
+        let mut image = Image::flat(code.clone());
+        image.sections.push(Section::build(&info, code.len() as u16));
+        let mut mem = image.memory(MEMORY_DEPTH).unwrap();
+
+        let mut regs = Registers::new();
+        for _ in 0..20 {
+            regs.ir = fetch(regs.pc, &mem);
+            match execute(decode(regs.ir), &mut regs, &mut mem) {
+                Some(ControlFlow::Continue(_)) => continue,
+                Some(ControlFlow::Break(_)) => break,
+                None => ()
+            }
+            regs.pc += 1;
+        }
+        assert_eq!((regs.pc, regs.gp[SP_PTR]), (7, MEMORY_DEPTH as i16));
+        assert_eq!(mem[MEMORY_DEPTH - 3..], [0, 0, 7]);
+        let loaded = Image::flat(mem[..code.len() + BUILD_WORDS].to_vec());
+        assert_eq!(loaded.build_info(), Ok(Some(info)));
+    }
+}
@@ -0,0 +1,5 @@
+pub mod instructions;
+pub mod registers;
+pub mod cpu_cycle;
+pub mod cli;
+pub mod source;
@@ -1,17 +1,17 @@
 use std::ops::ControlFlow;
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::path::Path;
 use std::io::{Read, Write, stdin, stdout};
+use std::process::exit;
 use colored::Colorize;
+use objformat::{read_image, Format, Image, MEMORY_DEPTH};
+use objformat::debug::DebugInfo;
 
-pub mod instructions;
-pub mod registers;
-use registers::*;
-pub mod cpu_cycle;
-use cpu_cycle::{fetch, decode, execute};
-pub mod cli;
-use cli::CLI;
+use emulator::registers::*;
+use emulator::cpu_cycle::{fetch, decode, execute};
+use emulator::cli::CLI;
+use emulator::source::SourceView;
 
 fn main() {
     let cli = CLI::new(env::args().collect());
@@ -24,8 +24,17 @@ fn main() {
     let mut regs = Registers::new();  // Registers
     let mut mem = Vec::new();         // Main memory (16-bit words)
 
-    // Load the bytes of the binary file into a vector (Main memory)
-    load_program(&cli.file_path.unwrap(), &mut mem);
+    // Load the program into main memory and start at its entry point
+    let file_path = cli.file_path.unwrap();
+    let image = load_program(&file_path, cli.format, &mut mem);
+    regs.pc = image.entry;
+
+    // Debug mode shows the source line of each instruction when the
+    // program was assembled with debug information
+    let source = match cli.debug {
+        true => load_debug_info(&file_path, &image).map(SourceView::new),
+        false => None
+    };
 
     if cli.debug {
         CLI::print_debug_welcome();
@@ -33,6 +42,7 @@ fn main() {
  
     // Fetch-Decode-Execute Cycle
     loop {
+        let addr = regs.pc;
         regs.ir = fetch(regs.pc, &mem);
         let opcode = decode(regs.ir);
 
@@ -47,6 +57,9 @@ fn main() {
             stdin.read_line(&mut input).unwrap();
             CLI::clear_screen();
             CLI::print_debug_welcome();
+            if let Some(source) = &source {
+                CLI::print_source_line(source, addr);
+            }
             CLI::print_curr_instruction(opcode);
             regs.print();
             stdout.flush().unwrap();
@@ -60,24 +73,54 @@ fn main() {
 
 // *************************** HELPER FUNCTIONS **************************** //
 
-// Loads contents of binary executable file into a vector of
-// 16-bit words that will act as main memory
-fn load_program(bin_path: &String, mem: &mut Vec<u16>) -> () {
+// Loads the sections of an executable file into a vector of 16-bit words
+// that will act as main memory, and returns the image with its entry point.
+// Raw binary, Intel HEX and S-record files are also accepted, starting at
+// address 0, in the format given or else the one their name tells. The
+// build ID of the program is shown when it has one
+fn load_program(bin_path: &str, format: Option<Format>, mem: &mut Vec<u16>) -> Image {
     let path = Path::new(bin_path);
     let mut file = File::open(path).expect("Can not find file");
     
     // Read entire file into a byte array
     let mut bytes: Vec<u8> = Vec::new();
     file.read_to_end(&mut bytes).expect("Could not read file");
-    
-    const CODE_SECTION_LENGTH: usize = 300;
-    const STACK_LENGTH: usize = 50;
-    mem.resize(CODE_SECTION_LENGTH + STACK_LENGTH, 0);
 
-    let mut w = 0; // Index to each memory word (2 bytes)
-    for idx in 0..(bytes.len()/2) {
-        // Convert each two-byte group into a 16-bit word
-        mem[idx] = u16::from_be_bytes([bytes[w], bytes[w+1]]);
-        w += 2;
+    // Check the file and place every section at its load address
+    let format = format.unwrap_or_else(|| Format::detect(&bytes, bin_path));
+    let image = read_image(&bytes, format).and_then(|image| {
+        *mem = image.memory(MEMORY_DEPTH)?;
+        Ok(image)
+    });
+
+    // Programs built with a build ID are checked against the CRC in their
+    // build area before running
+    match image.and_then(|image| Ok((image.build_info()?, image))) {
+        Ok((build, image)) => {
+            if let Some(build) = build {
+                println!("{} {} (source {:016x})", "Build".cyan(), build.id, build.source_hash);
+            }
+            image
+        }
+        Err(e) => {
+            println!("{} {}", "Error".red(), e);
+            exit(1);
+        }
+    }
+}
+
+// Debug information embedded in an executable or, for the other formats,
+// written by the assembler next to the program as <program>.dbg
+fn load_debug_info(bin_path: &str, image: &Image) -> Option<DebugInfo> {
+    let info = image.debug_info().or_else(|| {
+        let text = fs::read_to_string(Path::new(bin_path).with_extension("dbg")).ok()?;
+        Some(DebugInfo::parse(&text))
+    })?;
+    match info {
+        Ok(info) => Some(info),
+        Err(e) => {
+            println!("{} {}", "Error".red(), e);
+            exit(1);
+        }
     }
 }
@@ -1,10 +1,7 @@
 use colored::Colorize;
 
 // Constants
-pub const REG_TOTAL_NUM: usize = 8; // Total num of General Purpose Registers
-pub const MBR_PTR: usize = 7;   // Address of General Purpose Register MBR
-pub const LNR_PTR: usize = 6;   // Address of General Purpose Register Link Register
-pub const SP_PTR: usize = 5;    // Address of General Purpose Register Stack Pointer
+pub use isa::registers::{REG_TOTAL_NUM, MBR_PTR, LNR_PTR, SP_PTR};
 
 // Register type, gerneral purpose and special purpose
 pub struct Registers {
@@ -23,6 +20,12 @@ pub enum Flags {
     NG
 }
 
+impl Default for Registers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Registers {
     pub fn new() -> Self {
         Self {
@@ -34,7 +37,7 @@ impl Registers {
 
     //   7..4     3    2    1    0
     // | unused | NG | ZR | CA | OV |
-    pub fn change_flags(&mut self, flags: Vec<(Flags, bool)>) -> () {
+    pub fn change_flags(&mut self, flags: Vec<(Flags, bool)>) {
         for f in flags {
 
             match f {
@@ -66,7 +69,7 @@ impl Registers {
         }
     }
 
-    pub fn print(&self) -> () {
+    pub fn print(&self) {
         println!("\r{}", "General Purpose Registers:".bold());
         println!("\rREG  SIGNED UNSIGNED HEX   |  REG  SIGNED UNSIGNED HEX");
         println!("\rr0:  {r0:<7}{ru0:<8} {r0:04X}  |  fp:  {r4:<7}{ru4:<9}{r4:04X}", 
@@ -79,7 +82,7 @@ impl Registers {
                   r3=self.gp[3], ru3=(self.gp[3] as u16), r7=self.gp[7], ru7=(self.gp[7] as u16));
 
         println!("\r{}", "\nSpecial Purpose Registers:".bold());
-        println!("\rpc:  {pc:<7}{pcu:<8} {pc:04X}", pc=self.pc, pcu=(self.pc as u16));
-        println!("\rir:  {ir:<7}{iru:<8} {ir:04X}", ir=self.ir, iru=(self.ir as u16));   
+        println!("\rpc:  {pc:<7}{pcu:<8} {pc:04X}", pc=self.pc, pcu=self.pc);
+        println!("\rir:  {ir:<7}{iru:<8} {ir:04X}", ir=self.ir, iru=self.ir);   
     }
 }
\ No newline at end of file
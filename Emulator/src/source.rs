@@ -0,0 +1,40 @@
+use std::fs;
+use std::path::Path;
+use objformat::debug::DebugInfo;
+
+// Source View: Debug information of the running program together with
+// the text of its source files, so debug mode can show the source line
+// of every instruction. Source files that can not be read show only the
+// position of the instruction
+pub struct SourceView {
+    info: DebugInfo,
+    files: Vec<Vec<String>>
+}
+
+impl SourceView {
+    pub fn new(info: DebugInfo) -> SourceView {
+        let files = info.files.iter()
+            .map(|path| match fs::read_to_string(path) {
+                Ok(text) => text.lines().map(String::from).collect(),
+                Err(_) => Vec::new()
+            })
+            .collect();
+        SourceView { info, files }
+    }
+
+    // Position of the instruction at addr as 'file:line:column', followed
+    // by the label whose code contains it, and the text of its source line
+    pub fn line_at(&self, addr: u16) -> Option<(String, Option<&str>)> {
+        let entry = self.info.line_at(addr)?;
+        let path = self.info.files.get(entry.file)?;
+        let name = Path::new(path).file_name().map_or(path.clone(), |n| n.to_string_lossy().into());
+        let mut position = format!("{}:{}:{}", name, entry.line, entry.column);
+        if let Some(scope) = self.info.scope_at(addr) {
+            position.push_str(&format!(" in {}", scope.label));
+        }
+        let text = entry.line.checked_sub(1)
+            .and_then(|l| self.files[entry.file].get(l))
+            .map(|l| l.trim());
+        Some((position, text))
+    }
+}
@@ -0,0 +1,78 @@
+// Instruction frame constants
+// SIZE endings:
+//     refer to the size of the bitfield
+// ROFFSET endings:
+//     refer to the amount of bits from the left (msb) until bitfield begins
+// Example: 0110 01[00] 0011 1101 has SIZE = 2 and ROFFSET = 6
+pub const WORD_SIZE: usize            = 16;
+pub const OPCODE_SIZE: usize          = 5;
+pub const REG_ADDR_SIZE: usize        = 3;
+pub const REG_POS0_ROFFSET: usize     = OPCODE_SIZE;
+pub const REG_POS1_ROFFSET: usize     = REG_POS0_ROFFSET + REG_ADDR_SIZE;
+pub const REG_POS2_ROFFSET: usize     = REG_POS1_ROFFSET + REG_ADDR_SIZE;
+pub const MEM_OFFSET_SIZE: usize      = 5;
+pub const MEM_OFFSET_ROFFSET: usize   = REG_POS2_ROFFSET;
+pub const MEM_LABEL_SIZE: usize       = 11;
+pub const MEM_LABEL_ROFFSET: usize    = OPCODE_SIZE;
+pub const PUSHPOP_NUM_SIZE: usize     = 2;
+pub const PUSHPOP_NUM_ROFFSET: usize  = REG_POS2_ROFFSET + REG_ADDR_SIZE;
+pub const B8_CONSTANT_SIZE: usize     = 8;
+pub const B8_CONSTANT_ROFFSET: usize  = REG_POS0_ROFFSET + REG_ADDR_SIZE;
+pub const B5_CONSTANT_SIZE: usize     = 5;
+pub const B5_CONSTANT_ROFFSET: usize  = REG_POS1_ROFFSET + REG_ADDR_SIZE;
+pub const B4_CONSTANT_SIZE: usize     = 4;
+pub const B4_CONSTANT_ROFFSET: usize  = REG_POS1_ROFFSET + REG_ADDR_SIZE;
+
+// Field: A bitfield of the instruction word. Signed fields accept negative
+// values when assembling, which are stored as their two's complement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    pub size: usize,
+    pub roffset: usize,
+    pub signed: bool
+}
+
+pub const OPCODE: Field  = Field { size: OPCODE_SIZE, roffset: 0, signed: false };
+pub const REG0: Field    = Field { size: REG_ADDR_SIZE, roffset: REG_POS0_ROFFSET, signed: false };
+pub const REG1: Field    = Field { size: REG_ADDR_SIZE, roffset: REG_POS1_ROFFSET, signed: false };
+pub const REG2: Field    = Field { size: REG_ADDR_SIZE, roffset: REG_POS2_ROFFSET, signed: false };
+pub const OFFSET: Field  = Field { size: MEM_OFFSET_SIZE, roffset: MEM_OFFSET_ROFFSET, signed: false };
+pub const LABEL: Field   = Field { size: MEM_LABEL_SIZE, roffset: MEM_LABEL_ROFFSET, signed: false };
+pub const REG_NUM: Field = Field { size: PUSHPOP_NUM_SIZE, roffset: PUSHPOP_NUM_ROFFSET, signed: false };
+pub const IMM8: Field    = Field { size: B8_CONSTANT_SIZE, roffset: B8_CONSTANT_ROFFSET, signed: true };
+pub const IMM5: Field    = Field { size: B5_CONSTANT_SIZE, roffset: B5_CONSTANT_ROFFSET, signed: false };
+pub const IMM4: Field    = Field { size: B4_CONSTANT_SIZE, roffset: B4_CONSTANT_ROFFSET, signed: false };
+
+impl Field {
+    // Reads the value of the field out of an instruction word
+    pub fn extract(&self, word: u16) -> u16 {
+        extract_bits(word, self.size, self.roffset) as u16
+    }
+
+    // Places a value in the position of the field, masked to its width so
+    // it can never spill into neighbouring fields
+    pub fn insert(&self, value: u16) -> u16 {
+        let left_offset = WORD_SIZE - self.roffset - self.size;
+        (value & self.mask()) << left_offset
+    }
+
+    // Smallest and largest value the assembler accepts for the field
+    pub fn range(&self) -> (i32, i32) {
+        let max = self.mask() as i32;
+        if self.signed { (-(max + 1) / 2, max) } else { (0, max) }
+    }
+
+    fn mask(&self) -> u16 {
+        ((1u32 << self.size) - 1) as u16
+    }
+}
+
+// Helper function to extract the value of size amount of bits offseted
+// by right_offset from the left.
+// Example: size = 3, right_offset = 5 on num = 0110 1[011] 1000 0110
+//          yields -> 011 (decimal 3)
+pub fn extract_bits(num: u16, size: usize, right_offset: usize) -> usize {
+    let left_offset = WORD_SIZE - right_offset - size;
+    let mask: u16 = (((1u32 << size) - 1) as u16) << left_offset;
+    ((num & mask) >> left_offset) as usize
+}
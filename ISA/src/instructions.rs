@@ -0,0 +1,303 @@
+use crate::fields::*;
+use crate::registers::{SP_PTR, LNR_PTR, MBR_PTR};
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+// Opcode numerical translations (From 0x00 to 0x1C)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    MovIm,  // MOV immediate (MOV {reg_dst} {constant})
+    MovRg,  // MOV with registers (MOV {reg_dst} {reg_src})
+    Load,   // LOAD immediate (LOAD {label})
+    LoadRg, // LOAD with registers (LOAD {reg_dst} {reg_adr} {offset})
+    Store,  // STR immediate (STR {label})
+    StrRg,  // STR with registers (STR {reg_src} {reg_adr} {offset})
+    Push,   // PUSH (PUSH {reg_A} {reg_B} {reg_C} {num})
+    Pop,    // POP (POP {reg_A} {reg_B} {reg_C} {num})
+    AddIm,  // ADD immediate (ADD {reg_dst} {reg_A} {constant})
+    AddRg,  // ADD with registers (ADD {reg_dst} {reg_A} {reg_B})
+    SubIm,  // SUB immediate (SUB {reg_dst} {reg_A} {constant})
+    SubRg,  // SUB with registers (SUB {reg_dst} {reg_A} {reg_B})
+    ShftL,  // Shift Left (SHL {reg_dst} {reg_src} {constant})
+    ShftR,  // Shift Right (SHR {reg_dst} {reg_src} {constant})
+    And,    // Biwise AND (AND {reg_dst} {reg_A} {reg_B})
+    Or,     // Bitwise OR (OR {reg_dst} {reg_A} {reg_B})
+    Not,    // Bitwise NOT (NOT {reg_dst} {reg_src})
+    Jmp,    // Branch always (Jmp {label})
+    Bln,    // Branch with link (BLN {label})
+    Ret,    // Return from branch (RET {label})
+    CmpIm,  // Compare immediate (CMP {reg_A} {constant})
+    CmpRg,  // Compare registers (CMP {reg_A} {reg_B})
+    Beq,    // Branch if equal (BEQ {label})
+    Bne,    // Branch if not equal (BNE {label})
+    Bgt,    // Branch if greater than signed (BGT {label})
+    Bgtu,   // Branch if greater than unsigned (BGTU {label})
+    Blt,    // Branch if less than signed (BLT {label})
+    Bltu,   // Branch if less than unsigned (BLTU {label})
+    Halt,   // Halts program execution until system reset
+}
+
+// Instruction Type: Categorizes the types of instructions by the number and
+// bit sizes of the fields of the instruction.
+// Please refer to the CPU or Assembler Reference Manuals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstructionType {
+    T1, // reg0, 8-bit constant
+    T2, // reg0, reg1, 5-bit offset or constant
+    T3, // 11-bit label
+    T4, // reg0, reg1, reg2, 2-bit register count
+    T5  // reg0, reg1, 4-bit constant
+}
+
+// Operand Kind: What each operand written in assembly is, and which
+// fields of the word it is stored in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    Reg(Field),
+    Imm(Field),
+    Addr(Field, Field), // Address register and offset
+    Label(Field),
+    RegList             // One to three registers plus their count
+}
+
+// Flow: How an instruction affects the program counter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Next,   // Continues with the next instruction
+    Jump,   // Always continues at its label
+    Branch, // Continues at its label or the next instruction
+    Call,   // Jumps to its label, saving the return address in lr
+    Return, // Jumps to the address in lr
+    Halt    // Stops execution
+}
+
+// Instruction Definition: Everything the assembler, emulator, disassembler
+// and debugger need to know about one opcode
+#[derive(Debug)]
+pub struct InstrDef {
+    pub opcode: Opcode,
+    pub mnemonic: &'static str,
+    pub description: &'static str,
+    pub format: InstructionType,
+    pub operands: &'static [OperandKind],
+    pub flow: Flow
+}
+
+// Decoded operand values, one per OperandKind of the instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Register(u8),
+    Immediate(u16),
+    Indirect(u8, u16),
+    Address(u16),
+    RegList(Vec<u8>)
+}
+
+// ************************* INSTRUCTION TABLE ***************************** //
+
+use OperandKind::{Reg, Imm, Addr, Label, RegList};
+
+const fn def(opcode: Opcode, mnemonic: &'static str, description: &'static str,
+             format: InstructionType, operands: &'static [OperandKind], flow: Flow)
+-> InstrDef {
+    InstrDef { opcode, mnemonic, description, format, operands, flow }
+}
+
+// Every instruction of the CPU, indexed by opcode
+pub static INSTRUCTIONS: [InstrDef; 29] = {
+    use InstructionType::*;
+    use Opcode::*;
+    const BRANCH: &[OperandKind] = &[Label(LABEL)];
+    [
+        def(MovIm,  "mov",  "MOV immediate",      T1, &[Reg(REG0), Imm(IMM8)], Flow::Next),
+        def(MovRg,  "mov",  "MOV with registers", T2, &[Reg(REG0), Reg(REG1)], Flow::Next),
+        def(Load,   "lda",  "LDA",                T3, &[Label(LABEL)], Flow::Next),
+        def(LoadRg, "ldr",  "LDR",                T2, &[Reg(REG0), Addr(REG1, OFFSET)], Flow::Next),
+        def(Store,  "stra", "STRA",               T3, &[Label(LABEL)], Flow::Next),
+        def(StrRg,  "strr", "STRR",               T2, &[Reg(REG0), Addr(REG1, OFFSET)], Flow::Next),
+        def(Push,   "push", "PUSH",               T4, &[RegList], Flow::Next),
+        def(Pop,    "pop",  "POP",                T4, &[RegList], Flow::Next),
+        def(AddIm,  "add",  "ADD Immediate",      T2, &[Reg(REG0), Reg(REG1), Imm(IMM5)], Flow::Next),
+        def(AddRg,  "add",  "ADD with registers", T4, &[Reg(REG0), Reg(REG1), Reg(REG2)], Flow::Next),
+        def(SubIm,  "sub",  "SUB Immediate",      T2, &[Reg(REG0), Reg(REG1), Imm(IMM5)], Flow::Next),
+        def(SubRg,  "sub",  "SUB with registers", T4, &[Reg(REG0), Reg(REG1), Reg(REG2)], Flow::Next),
+        def(ShftL,  "shl",  "SHL",                T5, &[Reg(REG0), Reg(REG1), Imm(IMM4)], Flow::Next),
+        def(ShftR,  "shr",  "SHR",                T5, &[Reg(REG0), Reg(REG1), Imm(IMM4)], Flow::Next),
+        def(And,    "and",  "AND",                T4, &[Reg(REG0), Reg(REG1), Reg(REG2)], Flow::Next),
+        def(Or,     "or",   "OR",                 T4, &[Reg(REG0), Reg(REG1), Reg(REG2)], Flow::Next),
+        def(Not,    "not",  "NOT",                T2, &[Reg(REG0), Reg(REG1)], Flow::Next),
+        def(Jmp,    "jmp",  "JMP",                T3, BRANCH, Flow::Jump),
+        def(Bln,    "bln",  "BLN",                T3, BRANCH, Flow::Call),
+        def(Ret,    "ret",  "RET",                T3, &[], Flow::Return),
+        def(CmpIm,  "cmp",  "CMP Immediate",      T1, &[Reg(REG0), Imm(IMM8)], Flow::Next),
+        def(CmpRg,  "cmp",  "CMP with registers", T2, &[Reg(REG0), Reg(REG1)], Flow::Next),
+        def(Beq,    "beq",  "BEQ",                T3, BRANCH, Flow::Branch),
+        def(Bne,    "bne",  "BNE",                T3, BRANCH, Flow::Branch),
+        def(Bgt,    "bgt",  "BGT",                T3, BRANCH, Flow::Branch),
+        def(Bgtu,   "bgtu", "BGTU",               T3, BRANCH, Flow::Branch),
+        def(Blt,    "blt",  "BLT",                T3, BRANCH, Flow::Branch),
+        def(Bltu,   "bltu", "BLTU",               T3, BRANCH, Flow::Branch),
+        def(Halt,   "halt", "HALT",               T3, &[], Flow::Halt),
+    ]
+};
+
+// Registers that can be pushed or popped by a single instruction
+pub const MAX_REG_LIST: usize = 3;
+
+// *************************** LOOK-UP FUNCTIONS *************************** //
+
+impl Opcode {
+    // Converts the 5-bit opcode field into its Opcode, if it exists
+    pub fn from_u8(opcode: u8) -> Option<Opcode> {
+        INSTRUCTIONS.get(opcode as usize).map(|d| d.opcode)
+    }
+
+    pub fn def(&self) -> &'static InstrDef {
+        &INSTRUCTIONS[*self as usize]
+    }
+}
+
+// Reads the 5-bit opcode field of an instruction word
+pub fn opcode_of(word: u16) -> u8 {
+    OPCODE.extract(word) as u8
+}
+
+// Every definition sharing a mnemonic ('mov' has an immediate and a
+// register variant, for example), in opcode order
+pub fn defs_for(mnemonic: &str) -> impl Iterator<Item = &'static InstrDef> + '_ {
+    INSTRUCTIONS.iter().filter(move |d| d.mnemonic == mnemonic)
+}
+
+// Every distinct mnemonic of the instruction set
+pub fn mnemonics() -> impl Iterator<Item = &'static str> {
+    INSTRUCTIONS.iter().enumerate()
+        .filter(|(i, d)| INSTRUCTIONS[..*i].iter().all(|p| p.mnemonic != d.mnemonic))
+        .map(|(_, d)| d.mnemonic)
+}
+
+// ************************ ENCODING AND DECODING ************************** //
+
+// Builds the instruction word for a definition from its operand values.
+// Values must already have been validated against the operand kinds;
+// every field is masked to its width
+pub fn encode(def: &InstrDef, operands: &[Operand]) -> u16 {
+    let mut word = OPCODE.insert(def.opcode as u16);
+    for (kind, op) in def.operands.iter().zip(operands) {
+        word |= match (kind, op) {
+            (Reg(f), Operand::Register(r)) => f.insert(*r as u16),
+            (Imm(f), Operand::Immediate(v)) => f.insert(*v),
+            (Addr(r, o), Operand::Indirect(reg, ofst)) => r.insert(*reg as u16) | o.insert(*ofst),
+            (Label(f), Operand::Address(a)) => f.insert(*a),
+            (RegList, Operand::RegList(regs)) => {
+                regs.iter().zip([REG0, REG1, REG2])
+                    .fold(REG_NUM.insert(regs.len() as u16), |w, (r, f)| w | f.insert(*r as u16))
+            }
+            _ => panic!("Operand {:?} does not match kind {:?}", op, kind)
+        };
+    }
+    word
+}
+
+// Decodes an instruction word into its definition and operand values.
+// Returns None if the opcode does not belong to any instruction
+pub fn decode(word: u16) -> Option<(&'static InstrDef, Vec<Operand>)> {
+    let def = INSTRUCTIONS.get(opcode_of(word) as usize)?;
+    let operands = def.operands.iter().map(|kind| match kind {
+        Reg(f) => Operand::Register(f.extract(word) as u8),
+        Imm(f) => Operand::Immediate(f.extract(word)),
+        Addr(r, o) => Operand::Indirect(r.extract(word) as u8, o.extract(word)),
+        Label(f) => Operand::Address(f.extract(word)),
+        RegList => {
+            let num = REG_NUM.extract(word) as usize;
+            Operand::RegList([REG0, REG1, REG2].iter().take(num)
+                                .map(|f| f.extract(word) as u8).collect())
+        }
+    }).collect();
+    Some((def, operands))
+}
+
+// **************************** REGISTER USAGE ***************************** //
+
+// Registers an instruction reads and writes, in operand order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegUsage {
+    pub reads: Vec<u8>,
+    pub writes: Vec<u8>
+}
+
+// Returns the registers read and written by an instruction with the given
+// operands. Besides its operands, 'lda' writes and 'stra' reads mbr,
+// 'push' and 'pop' update sp, 'bln' writes lr and 'ret' reads it
+pub fn reg_usage(def: &InstrDef, operands: &[Operand]) -> RegUsage {
+    use Opcode::*;
+    let writes_first = matches!(def.opcode,
+        MovIm | MovRg | LoadRg | AddIm | AddRg | SubIm | SubRg | ShftL | ShftR | And | Or | Not);
+    let mut usage = RegUsage::default();
+    for (i, op) in operands.iter().enumerate() {
+        match op {
+            Operand::Register(r) if i == 0 && writes_first => usage.writes.push(*r),
+            Operand::Register(r) | Operand::Indirect(r, _) => usage.reads.push(*r),
+            Operand::RegList(regs) if def.opcode == Pop => usage.writes.extend(regs),
+            Operand::RegList(regs) => usage.reads.extend(regs),
+            Operand::Immediate(_) | Operand::Address(_) => ()
+        }
+    }
+    let (sp, lr, mbr) = (SP_PTR as u8, LNR_PTR as u8, MBR_PTR as u8);
+    match def.opcode {
+        Load => usage.writes.push(mbr),
+        Store => usage.reads.push(mbr),
+        Push | Pop => {
+            usage.reads.push(sp);
+            usage.writes.push(sp);
+        }
+        Bln => usage.writes.push(lr),
+        Ret => usage.reads.push(lr),
+        _ => ()
+    }
+    usage
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // The table is indexed by opcode
+    fn table_order() {
+        for (i, d) in INSTRUCTIONS.iter().enumerate() {
+            assert_eq!(d.opcode as usize, i);
+        }
+        assert_eq!(Opcode::from_u8(0x1C), Some(Opcode::Halt));
+        assert_eq!(Opcode::from_u8(0x1D), None);
+        assert_eq!(mnemonics().count(), 25);
+    }
+
+    #[test]
+    // Words taken from the hand disassembly of test file 1
+    fn encode_decode_words() {
+        let (def, ops) = decode(0x1C40).unwrap();
+        assert_eq!((def.opcode, &ops), (Opcode::LoadRg,
+                   &vec![Operand::Register(4), Operand::Indirect(2, 0)]));
+        assert_eq!(encode(def, &ops), 0x1C40);
+
+        let (def, ops) = decode(0xB00B).unwrap();
+        assert_eq!((def.flow, &ops), (Flow::Branch, &vec![Operand::Address(11)]));
+
+        let push = Opcode::Push.def();
+        assert_eq!(encode(push, &[Operand::RegList(vec![1, 2])]), 0x3142);
+        assert_eq!(decode(0x3142).unwrap().1, vec![Operand::RegList(vec![1, 2])]);
+    }
+
+    #[test]
+    fn register_usage() {
+        let (def, ops) = decode(0x1C40).unwrap(); // ldr r4 [r2]
+        assert_eq!(reg_usage(def, &ops), RegUsage { reads: vec![2], writes: vec![4] });
+        let (def, ops) = decode(0x0860).unwrap(); // mov r0 r3
+        assert_eq!(reg_usage(def, &ops), RegUsage { reads: vec![3], writes: vec![0] });
+        let pop = Opcode::Pop.def();
+        assert_eq!(reg_usage(pop, &[Operand::RegList(vec![1, 6])]),
+                   RegUsage { reads: vec![5], writes: vec![1, 6, 5] });
+        assert_eq!(reg_usage(Opcode::Load.def(), &[Operand::Address(13)]).writes, vec![7]);
+    }
+}
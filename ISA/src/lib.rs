@@ -0,0 +1,8 @@
+// Instruction set of the CPU: bitfields of the instruction word, opcodes,
+// mnemonics and operands of every instruction, and register names.
+// The assembler, emulator and disassembler are all derived from it
+pub mod fields;
+pub mod instructions;
+pub mod registers;
+
+pub use instructions::*;
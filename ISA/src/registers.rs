@@ -0,0 +1,25 @@
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+pub const REG_TOTAL_NUM: usize = 8; // Total num of General Purpose Registers
+pub const FP_PTR: usize = 4;        // Address of General Purpose Register Frame Pointer
+pub const SP_PTR: usize = 5;        // Address of General Purpose Register Stack Pointer
+pub const LNR_PTR: usize = 6;       // Address of General Purpose Register Link Register
+pub const MBR_PTR: usize = 7;       // Address of General Purpose Register MBR
+
+// Canonical name of each register. Registers 4 to 7 are named by their
+// special purpose
+pub const REG_NAMES: [&str; REG_TOTAL_NUM] = ["r0", "r1", "r2", "r3", "fp", "sp", "lr", "mbr"];
+
+// Every name a register can be written with in assembly
+pub const REG_ALIASES: [(&str, u8); 12] = [
+    ("r0", 0), ("r1", 1), ("r2", 2), ("r3", 3),
+    ("r4", 4), ("r5", 5), ("r6", 6), ("r7", 7),
+    ("fp", 4), ("sp", 5), ("lr", 6), ("mbr", 7)
+];
+
+// ************************** LOOK-UP FUNCTIONS **************************** //
+
+// Returns the address of a register given any of its names
+pub fn register_number(name: &str) -> Option<u8> {
+    REG_ALIASES.iter().find(|(n, _)| *n == name).map(|(_, r)| *r)
+}
@@ -0,0 +1,429 @@
+use std::io::sink;
+use assembler::ast::{Program, Statement};
+use assembler::encoder::{encode_instruction, select_def};
+use assembler::err_handler::LineError;
+use assembler::lint::lint_program;
+use assembler::operands::Operand;
+use assembler::lexer::comment_start;
+use assembler::parser::{assemble_program, parse_source, parse_symbols};
+use assembler::symbols::{Section, Symbols};
+use assembler::warnings::{check_program, WarningConfig, WarningKind};
+use isa::registers::{register_number, REG_ALIASES, REG_NAMES};
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning
+}
+
+// Diagnostic: An error or warning of the assembler on a line, with the
+// name of its warning category (-W<name>) if it has one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+    pub code: Option<&'static str>
+}
+
+// Span: Characters start to end (not included) of a line. Lines and
+// characters start at 0 and characters are counted in UTF-16 code units,
+// as in the protocol
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompletionKind {
+    Mnemonic,
+    Register,
+    Label,
+    Directive
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: String
+}
+
+// Document Symbol: A label, the section it was declared in and its
+// address, if the program could be laid out
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentSymbol {
+    pub name: String,
+    pub section: Section,
+    pub addr: Option<u16>,
+    pub span: Span
+}
+
+// Document: An open source file and what the assembler found in it. The
+// program is None only if no line could be parsed at all, and symbols are
+// None if the program could not be laid out
+pub struct Document {
+    pub lines: Vec<String>,
+    pub program: Option<Program>,
+    pub symbols: Option<Symbols>,
+    pub diagnostics: Vec<Diagnostic>
+}
+
+// ******************************** ANALYSIS ******************************* //
+
+impl Document {
+    // Runs the assembler over the text. The assembler stops at the first
+    // error, so the line of every error is replaced and the text assembled
+    // again, to report as many errors as possible and still know the labels
+    // of the other lines. Lines are replaced by space for as many words as
+    // they take, so the lines after them keep their addresses, and only
+    // blanked if that space is an error too. Once it assembles, every
+    // warning category is checked, lints included
+    pub fn analyze(file: &str, text: &str) -> Document {
+        let lines: Vec<String> = text.lines().map(String::from).collect();
+        let mut source = lines.clone();
+        let mut replaced = vec![false; source.len()];
+        let mut diagnostics = Vec::new();
+        let mut program = None;
+        let mut symbols = None;
+
+        loop {
+            let (parsed, result) = assemble(file, &source);
+            let error = match result {
+                Ok(s) => {
+                    program = parsed;
+                    symbols = Some(s);
+                    break;
+                }
+                Err(e) => e
+            };
+            let size = parsed.as_ref()
+                .and_then(|p| p.nodes.iter().find(|n| Some(n.line) == error.line()))
+                .map(|n| n.size);
+            program = parsed.or(program);
+            match error.line() {
+                Some(n) if n < source.len() && !source[n].is_empty() => {
+                    if replaced[n] {
+                        source[n].clear();
+                        continue;
+                    }
+                    diagnostics.push(Diagnostic::error(&error));
+                    let size = size.unwrap_or_else(|| line_size(&source, n));
+                    source[n] = match size {
+                        0 => String::new(),
+                        size => format!(".space {}", size)
+                    };
+                    replaced[n] = true;
+                }
+                _ => {
+                    diagnostics.push(Diagnostic::error(&error));
+                    break;
+                }
+            }
+        }
+
+        // Space in place of a line changes the flow of the code, so warnings
+        // are checked with the replaced lines blanked instead
+        let blanked: Vec<String> = source.iter().zip(&replaced)
+            .map(|(l, r)| if *r { String::new() } else { l.clone() })
+            .collect();
+        let any_replaced = replaced.contains(&true);
+        let reassembled = match any_replaced {
+            true => match assemble(file, &blanked) {
+                (p, Ok(s)) => p.zip(Some(s)),
+                (_, Err(_)) => None
+            },
+            false => None
+        };
+        let checked = match any_replaced {
+            true => reassembled.as_ref().map(|(p, s)| (p, s)),
+            false => program.as_ref().zip(symbols.as_ref())
+        };
+        if let Some((p, s)) = checked {
+            let mut config = WarningConfig::default();
+            WarningKind::ALL.into_iter().for_each(|k| config.enable(k));
+            let entry = p.section_range(Section::Code).map_or(0, |c| c.start as u16);
+            let mut warnings = check_program(p, &config);
+            warnings.extend(lint_program(p, s, entry, &config));
+            diagnostics.extend(warnings.iter().map(|w| Diagnostic {
+                line: w.line(),
+                severity: Severity::Warning,
+                message: w.message(),
+                code: Some(w.kind().name())
+            }));
+        }
+        diagnostics.sort_by_key(|d| d.line);
+        Document { lines, program, symbols, diagnostics }
+    }
+
+    // Span of the bytes start to end of a line. The assembler counts the
+    // characters of a line in bytes and the protocol in UTF-16 code units
+    fn span(&self, line: usize, start: usize, end: usize) -> Span {
+        let text = self.lines.get(line).map_or("", |l| l.as_str());
+        let character = |byte: usize| text[..byte].encode_utf16().count();
+        Span { line, start: character(start), end: character(end) }
+    }
+
+    // Byte of a line at a character of the protocol, in UTF-16 code units.
+    // Characters past the end of the line are at its end
+    fn byte_offset(&self, line: usize, character: usize) -> usize {
+        let text = self.lines.get(line).map_or("", |l| l.as_str());
+        let mut units = 0;
+        for (idx, c) in text.char_indices() {
+            if units >= character {
+                return idx;
+            }
+            units += c.len_utf16();
+        }
+        text.len()
+    }
+
+    // Span of the code of a line, without indentation and trailing comment
+    pub fn line_span(&self, line: usize) -> Span {
+        let text = self.lines.get(line).map_or("", |l| l.as_str());
+        let code = comment_start(text).map_or(text, |i| &text[..i]).trim_end();
+        let start = code.len() - code.trim_start().len();
+        match code.len() > start {
+            true => self.span(line, start, code.len()),
+            false => self.span(line, 0, text.len())
+        }
+    }
+
+    // Identifier under the cursor (a label, register, mnemonic or
+    // directive name) and its span
+    pub fn word_at(&self, line: usize, character: usize) -> Option<(&str, Span)> {
+        let text = self.lines.get(line)?;
+        let is_word = |c: u8| c.is_ascii_alphanumeric() || c == b'_' || c == b'.';
+        let bytes = text.as_bytes();
+        let character = self.byte_offset(line, character);
+        let start = bytes[..character].iter().rposition(|c| !is_word(*c)).map_or(0, |i| i + 1);
+        let end = bytes[character..].iter().position(|c| !is_word(*c))
+                                    .map_or(bytes.len(), |i| character + i);
+        match start < end {
+            true => Some((&text[start..end], self.span(line, start, end))),
+            false => None
+        }
+    }
+
+    // Span of the declaration of a label
+    pub fn definition(&self, label: &str) -> Option<Span> {
+        let node = self.program.as_ref()?.nodes.iter()
+            .find(|n| matches!(&n.stmt, Statement::Label(l) if l == label))?;
+        let start = node.column - 1;
+        Some(self.span(node.line, start, start + label.len()))
+    }
+
+    // Spans of every use of a label, as an operand or in an expression
+    pub fn references(&self, label: &str) -> Vec<Span> {
+        let Some(program) = &self.program else { return Vec::new() };
+        program.nodes.iter()
+            .filter(|n| match &n.stmt {
+                Statement::Instruction(_, args) => args.contains(&Operand::Label(label.to_string())),
+                Statement::Directive(d) => d.expr().labels().contains(&label),
+                _ => false
+            })
+            .filter_map(|n| find_word(&self.lines[n.line], label).map(|start| {
+                self.span(n.line, start, start + label.len())
+            }))
+            .collect()
+    }
+
+    // Markdown describing what is under the cursor: labels with their
+    // address, registers, constants with their value, and instructions
+    // with their address and encoding
+    pub fn hover(&self, line: usize, character: usize) -> Option<String> {
+        let (word, _) = self.word_at(line, character)?;
+        let program = self.program.as_ref()?;
+        let addr = |a: u16| format!("`0x{:04X}`", a);
+
+        if let Some(reg) = register_number(word) {
+            return Some(format!("register **{}** (r{})", REG_NAMES[reg as usize], reg));
+        }
+        if let Some(value) = program.constants.get(word) {
+            return Some(format!("constant **{}** = {}", word, value));
+        }
+        if let Some(def) = self.definition(word) {
+            let node = program.nodes.iter().find(|n| n.line == def.line)?;
+            let kind = match node.section {
+                Some(Section::Data) => "data label",
+                _ => "code label"
+            };
+            let at = self.symbols.as_ref().and_then(|s| s.labels.get(word))
+                         .map_or(String::new(), |a| format!(" at {}", addr(*a)));
+            return Some(format!("{} **{}**{}", kind, word, at));
+        }
+
+        let node = program.nodes.iter().find(|n| n.line == line)?;
+        let syms = self.symbols.as_ref()?;
+        match &node.stmt {
+            Statement::Instruction(m, args) => {
+                let def = select_def(m, args)?;
+                let word = match encode_instruction(m, args, syms, line)? {
+                    Ok(bytes) => u16::from_be_bytes(bytes),
+                    Err(_) => return None
+                };
+                let bits: Vec<String> = (0..4).rev().map(|i| format!("{:04b}", word >> (i * 4) & 0xF))
+                                              .collect();
+                Some(format!("{} **{}**: {}\n\nencoding `0x{:04X}` = `{}`",
+                             addr(node.addr), def.mnemonic, def.description, word, bits.join(" ")))
+            }
+            Statement::Data(_) | Statement::Directive(_) => {
+                Some(format!("{} {} words", addr(node.addr), node.size))
+            }
+            _ => None
+        }
+    }
+
+    // Everything that may be typed: mnemonics, registers, directives and
+    // the labels of the document
+    pub fn completions(&self) -> Vec<Completion> {
+        let mut items: Vec<Completion> = isa::mnemonics()
+            .map(|m| Completion {
+                label: m.to_string(),
+                kind: CompletionKind::Mnemonic,
+                detail: isa::defs_for(m).next().map_or("", |d| d.description).to_string()
+            })
+            .collect();
+        items.extend(REG_ALIASES.iter().map(|(name, r)| Completion {
+            label: name.to_string(),
+            kind: CompletionKind::Register,
+            detail: format!("register r{}", r)
+        }));
+        let directives = [".section[code]", ".section[data]", ".org", ".align", ".space",
+                          ".include", ".incbin", ".packed", ".packed_le", ".struct", ".field",
+                          ".ends", ".enum"];
+        items.extend(directives.iter().map(|d| Completion {
+            label: d.to_string(), kind: CompletionKind::Directive, detail: "directive".to_string()
+        }));
+        items.extend(self.document_symbols().into_iter().map(|s| Completion {
+            detail: s.addr.map_or("label".to_string(), |a| format!("label at 0x{:04X}", a)),
+            label: s.name,
+            kind: CompletionKind::Label
+        }));
+        items
+    }
+
+    // Labels declared in a section, in source order
+    pub fn document_symbols(&self) -> Vec<DocumentSymbol> {
+        let Some(program) = &self.program else { return Vec::new() };
+        program.nodes.iter()
+            .filter_map(|n| match (&n.stmt, n.section) {
+                (Statement::Label(l), Some(section)) => Some(DocumentSymbol {
+                    name: l.clone(),
+                    section,
+                    addr: self.symbols.as_ref().and_then(|s| s.labels.get(l)).copied(),
+                    span: self.definition(l)?
+                }),
+                _ => None
+            })
+            .collect()
+    }
+}
+
+impl Diagnostic {
+    fn error(e: &LineError) -> Diagnostic {
+        Diagnostic {
+            line: e.line().unwrap_or(0),
+            severity: Severity::Error,
+            message: e.message(),
+            code: None
+        }
+    }
+}
+
+// Parses, lays out and assembles the lines of a source. The program is
+// returned whenever it could be parsed, along with its symbols or the
+// error that stopped the assembler
+fn assemble(file: &str, source: &[String]) -> (Option<Program>, Result<Symbols, LineError>) {
+    let mut program = match parse_source(file, &source.join("\n")) {
+        Ok(program) => program,
+        Err(e) => return (None, Err(e))
+    };
+    let result = parse_symbols(&mut program)
+        .and_then(|s| assemble_program(&program, &s, &mut sink()).map(|_| s));
+    (Some(program), result)
+}
+
+// Words a line that could not be assembled would take: one for anything
+// that looks like an instruction in the code section and one per value of
+// data in the data section. Labels, directives and strings are not counted
+fn line_size(source: &[String], line: usize) -> usize {
+    let text = &source[line];
+    let code = comment_start(text).map_or(text.as_str(), |i| &text[..i]).trim();
+    if code.is_empty() || code.ends_with(':') || code.starts_with(['.', '"']) {
+        return 0;
+    }
+    let in_data = source[..line].iter().rev()
+        .map(|l| l.trim_start())
+        .find(|l| l.starts_with(".section"))
+        .is_some_and(|l| l.contains("data"));
+    match in_data {
+        true => code.split(',').count(),
+        false => 1
+    }
+}
+
+// Start of the first whole-word occurrence of word in the code of a line
+fn find_word(line: &str, word: &str) -> Option<usize> {
+    let code = comment_start(line).map_or(line, |i| &line[..i]);
+    let is_word = |c: Option<u8>| c.is_some_and(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'.');
+    code.match_indices(word).map(|(i, _)| i).find(|&i| {
+        !is_word(i.checked_sub(1).map(|p| code.as_bytes()[p]))
+            && !is_word(code.as_bytes().get(i + word.len()).copied())
+    })
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = ".section[code]\nstart:\n    lda arr\n    mov r2 mbr\n    bogus r1\n\
+                          loop:\n    cmp r1 #5\n    beq loop\n    halt\n\
+                          .section[data]\narr:\n    1, 2\n";
+
+    #[test]
+    // Errors do not hide the rest of the document, and the lines with errors
+    // keep the words they take, so the labels after them keep their address
+    fn diagnostics_and_labels() {
+        let doc = Document::analyze("test.s", SOURCE);
+        assert_eq!(doc.diagnostics.iter().map(|d| (d.line, d.severity)).collect::<Vec<_>>(),
+                   [(4, Severity::Error), (6, Severity::Warning)]);
+        assert_eq!(doc.diagnostics[1].code, Some("uninit-read"));
+        assert_eq!(doc.definition("loop"), Some(Span { line: 5, start: 0, end: 4 }));
+        assert_eq!(doc.references("loop"), [Span { line: 7, start: 8, end: 12 }]);
+        assert_eq!(doc.word_at(2, 10).map(|(w, _)| w), Some("arr"));
+        assert_eq!(doc.document_symbols().iter().map(|s| (s.name.as_str(), s.addr))
+                      .collect::<Vec<_>>(),
+                   [("start", Some(0)), ("loop", Some(3)), ("arr", Some(6))]);
+    }
+
+    #[test]
+    fn hover() {
+        let doc = Document::analyze("test.s", SOURCE);
+        assert_eq!(doc.hover(6, 5).unwrap(),
+                   "`0x0003` **cmp**: CMP Immediate\n\n\
+                    encoding `0xA105` = `1010 0001 0000 0101`");
+        assert_eq!(doc.hover(2, 9).unwrap(), "data label **arr** at `0x0006`");
+        assert_eq!(doc.hover(3, 8).unwrap(), "register **r2** (r2)");
+
+        let doc = Document::analyze("test.s", ".enum kind leaf, branch\n.section[code]\n\
+                                               \x20   mov r1 #kind.branch\n    halt\n");
+        assert_eq!(doc.hover(2, 14).unwrap(), "constant **kind.branch** = 1");
+    }
+
+    #[test]
+    // Characters are UTF-16 code units: 'é' takes one and '😀' two
+    fn utf16_positions() {
+        let source = ".section[code]\nloop:\n    /* é😀 */ jmp loop\n    halt\n";
+        let doc = Document::analyze("test.s", source);
+        assert_eq!(doc.references("loop"), [Span { line: 2, start: 18, end: 22 }]);
+        assert_eq!(doc.word_at(2, 19), Some(("loop", Span { line: 2, start: 18, end: 22 })));
+        assert_eq!(doc.word_at(2, 14).map(|(w, _)| w), Some("jmp"));
+        assert_eq!(doc.line_span(2), Span { line: 2, start: 4, end: 22 });
+    }
+}
@@ -0,0 +1,4 @@
+pub mod json;
+pub mod protocol;
+pub mod analysis;
+pub mod server;
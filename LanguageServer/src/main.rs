@@ -0,0 +1,41 @@
+use std::io::{self, BufReader};
+use std::process::exit;
+use langserver::protocol::{error_response, read_message, write_message, PARSE_ERROR};
+use langserver::json::Json;
+use langserver::server::Server;
+
+// Language server for the assembly language of the CPU. Editors start it
+// and talk to it over stdin and stdout with the language server protocol
+fn main() {
+    // Messages of the assembler are sent to the editor as plain text
+    colored::control::set_override(false);
+
+    let mut input = BufReader::new(io::stdin().lock());
+    let mut output = io::stdout().lock();
+    let mut server = Server::default();
+    let mut outgoing = Vec::new();
+
+    loop {
+        let msg = match read_message(&mut input) {
+            Ok(Some(Ok(msg))) => msg,
+            Ok(Some(Err(e))) => {
+                outgoing.push(error_response(&Json::Null, PARSE_ERROR, &e));
+                Json::Null
+            }
+            // The editor closed the connection without asking to exit
+            Ok(None) | Err(_) => exit(1)
+        };
+        let status = match msg {
+            Json::Null => None,
+            msg => server.handle(&msg, &mut outgoing)
+        };
+        for msg in outgoing.drain(..) {
+            if write_message(&mut output, &msg).is_err() {
+                exit(1);
+            }
+        }
+        if let Some(code) = status {
+            exit(code);
+        }
+    }
+}
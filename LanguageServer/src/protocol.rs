@@ -0,0 +1,56 @@
+use std::io::{self, BufRead, Write};
+use crate::json::{object, Json};
+
+// Error codes of JSON-RPC
+pub const PARSE_ERROR: i32 = -32700;
+pub const METHOD_NOT_FOUND: i32 = -32601;
+
+// ****************************** MESSAGE I/O ****************************** //
+
+// Reads the next message: headers, a blank line and a JSON body of
+// Content-Length bytes. Returns None at the end of the input
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Result<Json, String>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let Some(length) = length else {
+        return Ok(Some(Err("Message without Content-Length".to_string())));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(Json::parse(&String::from_utf8_lossy(&body))))
+}
+
+pub fn write_message(output: &mut impl Write, msg: &Json) -> io::Result<()> {
+    let body = msg.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+// ******************************** MESSAGES ******************************* //
+
+pub fn response(id: &Json, result: Json) -> Json {
+    object([("jsonrpc", "2.0".into()), ("id", id.clone()), ("result", result)])
+}
+
+pub fn error_response(id: &Json, code: i32, message: &str) -> Json {
+    let error = object([("code", Json::Number(code as f64)), ("message", message.into())]);
+    object([("jsonrpc", "2.0".into()), ("id", id.clone()), ("error", error)])
+}
+
+pub fn notification(method: &str, params: Json) -> Json {
+    object([("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)])
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use assembler::symbols::Section;
+use crate::analysis::{CompletionKind, Document, Severity, Span};
+use crate::json::{object, Json};
+use crate::protocol::{error_response, notification, response, METHOD_NOT_FOUND};
+
+// Kinds of the protocol for completion items and document symbols
+const COMPLETION_FUNCTION: usize = 3;
+const COMPLETION_VARIABLE: usize = 6;
+const COMPLETION_KEYWORD: usize = 14;
+const COMPLETION_REFERENCE: usize = 18;
+const SYMBOL_FUNCTION: usize = 12;
+const SYMBOL_VARIABLE: usize = 13;
+
+// Open documents by URI, analyzed again after every change
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shutdown: bool
+}
+
+// ****************************** DISPATCHING ****************************** //
+
+impl Server {
+    // Handles a request or notification and pushes the messages to send
+    // back to the client onto out. Returns the exit code once the client
+    // asks the server to exit
+    pub fn handle(&mut self, msg: &Json, out: &mut Vec<Json>) -> Option<i32> {
+        let method = msg.get("method").as_str().unwrap_or("");
+        let params = msg.get("params");
+        let id = msg.get("id");
+
+        // Notifications carry no id and get no response
+        if *id == Json::Null {
+            match method {
+                "textDocument/didOpen" => {
+                    let doc = params.get("textDocument");
+                    self.update(doc.get("uri"), doc.get("text").as_str().unwrap_or(""), out);
+                }
+                "textDocument/didChange" => {
+                    // Documents are always synchronized in full
+                    let text = params.get("contentChanges").as_array().last()
+                                     .and_then(|c| c.get("text").as_str());
+                    if let Some(text) = text {
+                        self.update(params.get("textDocument").get("uri"), text, out);
+                    }
+                }
+                "textDocument/didClose" => {
+                    let uri = params.get("textDocument").get("uri");
+                    self.documents.remove(uri.as_str().unwrap_or(""));
+                    out.push(notification("textDocument/publishDiagnostics",
+                        object([("uri", uri.clone()), ("diagnostics", Json::Array(vec![]))])));
+                }
+                "exit" => return Some(if self.shutdown { 0 } else { 1 }),
+                _ => ()
+            }
+            return None;
+        }
+
+        let result = match method {
+            "initialize" => Some(capabilities()),
+            "shutdown" => {
+                self.shutdown = true;
+                Some(Json::Null)
+            }
+            "textDocument/definition" => Some(self.definition(params)),
+            "textDocument/references" => Some(self.references(params)),
+            "textDocument/hover" => Some(self.hover(params)),
+            "textDocument/completion" => Some(self.completion(params)),
+            "textDocument/documentSymbol" => Some(self.document_symbols(params)),
+            _ => None
+        };
+        out.push(match result {
+            Some(result) => response(id, result),
+            None => error_response(id, METHOD_NOT_FOUND, &format!("Unknown method '{}'", method))
+        });
+        None
+    }
+
+    // Analyzes the new text of a document and publishes its diagnostics
+    fn update(&mut self, uri: &Json, text: &str, out: &mut Vec<Json>) {
+        let Some(uri_str) = uri.as_str() else { return };
+        let file = uri_str.strip_prefix("file://").unwrap_or(uri_str);
+        let doc = Document::analyze(file, text);
+        let diagnostics = doc.diagnostics.iter()
+            .map(|d| {
+                let severity = match d.severity {
+                    Severity::Error => 1,
+                    Severity::Warning => 2
+                };
+                let mut fields = vec![
+                    ("range".to_string(), range(doc.line_span(d.line))),
+                    ("severity".to_string(), Json::from(severity as usize)),
+                    ("source".to_string(), "assembler".into()),
+                    ("message".to_string(), d.message.clone().into())
+                ];
+                if let Some(code) = d.code {
+                    fields.push(("code".to_string(), format!("-W{}", code).into()));
+                }
+                Json::Object(fields)
+            })
+            .collect();
+        out.push(notification("textDocument/publishDiagnostics",
+            object([("uri", uri.clone()), ("diagnostics", Json::Array(diagnostics))])));
+        self.documents.insert(uri_str.to_string(), doc);
+    }
+}
+
+// ******************************** REQUESTS ******************************* //
+
+impl Server {
+    // Document of a request and the word at its position, if any
+    fn word_at(&self, params: &Json) -> Option<(&Document, String)> {
+        let doc = self.documents.get(params.get("textDocument").get("uri").as_str()?)?;
+        let pos = params.get("position");
+        let (word, _) = doc.word_at(pos.get("line").as_usize()?, pos.get("character").as_usize()?)?;
+        Some((doc, word.to_string()))
+    }
+
+    fn definition(&self, params: &Json) -> Json {
+        let uri = params.get("textDocument").get("uri");
+        self.word_at(params)
+            .and_then(|(doc, word)| doc.definition(&word))
+            .map_or(Json::Null, |span| location(uri, span))
+    }
+
+    fn references(&self, params: &Json) -> Json {
+        let uri = params.get("textDocument").get("uri");
+        let Some((doc, word)) = self.word_at(params) else { return Json::Array(vec![]) };
+        let mut spans = Vec::new();
+        let with_declaration = params.get("context").get("includeDeclaration").as_bool();
+        if with_declaration.unwrap_or(false) {
+            spans.extend(doc.definition(&word));
+        }
+        spans.extend(doc.references(&word));
+        Json::Array(spans.into_iter().map(|s| location(uri, s)).collect())
+    }
+
+    fn hover(&self, params: &Json) -> Json {
+        let Some(doc) = params.get("textDocument").get("uri").as_str()
+                              .and_then(|uri| self.documents.get(uri)) else { return Json::Null };
+        let pos = params.get("position");
+        let (Some(line), Some(character)) = (pos.get("line").as_usize(),
+                                             pos.get("character").as_usize()) else {
+            return Json::Null;
+        };
+        match doc.hover(line, character) {
+            Some(text) => object([
+                ("contents", object([("kind", "markdown".into()), ("value", text.into())])),
+                ("range", range(doc.word_at(line, character).map_or(doc.line_span(line), |w| w.1)))
+            ]),
+            None => Json::Null
+        }
+    }
+
+    fn completion(&self, params: &Json) -> Json {
+        let Some(doc) = params.get("textDocument").get("uri").as_str()
+                              .and_then(|uri| self.documents.get(uri)) else {
+            return Json::Array(vec![]);
+        };
+        Json::Array(doc.completions().into_iter().map(|c| {
+            let kind = match c.kind {
+                CompletionKind::Mnemonic => COMPLETION_FUNCTION,
+                CompletionKind::Register => COMPLETION_VARIABLE,
+                CompletionKind::Directive => COMPLETION_KEYWORD,
+                CompletionKind::Label => COMPLETION_REFERENCE
+            };
+            object([("label", c.label.into()), ("kind", kind.into()), ("detail", c.detail.into())])
+        }).collect())
+    }
+
+    fn document_symbols(&self, params: &Json) -> Json {
+        let Some(doc) = params.get("textDocument").get("uri").as_str()
+                              .and_then(|uri| self.documents.get(uri)) else {
+            return Json::Array(vec![]);
+        };
+        Json::Array(doc.document_symbols().into_iter().map(|s| {
+            let kind = match s.section {
+                Section::Code => SYMBOL_FUNCTION,
+                Section::Data => SYMBOL_VARIABLE
+            };
+            object([
+                ("name", s.name.into()),
+                ("detail", s.addr.map_or(String::new(), |a| format!("0x{:04X}", a)).into()),
+                ("kind", kind.into()),
+                ("range", range(s.span)),
+                ("selectionRange", range(s.span))
+            ])
+        }).collect())
+    }
+}
+
+// ******************************** HELPERS ******************************** //
+
+// What the server supports, answered to 'initialize'
+fn capabilities() -> Json {
+    object([
+        ("capabilities", object([
+            // Full text of the document on every change
+            ("textDocumentSync", 1.into()),
+            ("definitionProvider", true.into()),
+            ("referencesProvider", true.into()),
+            ("hoverProvider", true.into()),
+            ("completionProvider", object([])),
+            ("documentSymbolProvider", true.into())
+        ])),
+        ("serverInfo", object([("name", "langserver".into())]))
+    ])
+}
+
+fn range(span: Span) -> Json {
+    let position = |character: usize| {
+        object([("line", span.line.into()), ("character", character.into())])
+    };
+    object([("start", position(span.start)), ("end", position(span.end))])
+}
+
+fn location(uri: &Json, span: Span) -> Json {
+    object([("uri", uri.clone()), ("range", range(span))])
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(id: usize, method: &str, params: Json) -> Json {
+        object([("jsonrpc", "2.0".into()), ("id", id.into()), ("method", method.into()),
+                ("params", params)])
+    }
+
+    #[test]
+    // A short session: open a document, jump to a label, shut down
+    fn session() {
+        let mut server = Server::default();
+        let mut out = Vec::new();
+        server.handle(&request(1, "initialize", object([])), &mut out);
+        assert_eq!(out[0].get("result").get("capabilities").get("hoverProvider"), &Json::Bool(true));
+
+        let doc = object([("uri", "file:///t.s".into()),
+                          ("text", ".section[code]\nloop:\n    jmp loop\n".into())]);
+        server.handle(&notification("textDocument/didOpen", object([("textDocument", doc)])),
+                      &mut out);
+        assert_eq!(out[1].get("method").as_str(), Some("textDocument/publishDiagnostics"));
+        assert_eq!(out[1].get("params").get("diagnostics").as_array().len(), 0);
+
+        let position = object([("line", 2.into()), ("character", 9.into())]);
+        let params = object([("textDocument", object([("uri", "file:///t.s".into())])),
+                             ("position", position)]);
+        server.handle(&request(2, "textDocument/definition", params), &mut out);
+        assert_eq!(out[2].get("result").get("range").get("start").get("line").as_usize(), Some(1));
+
+        server.handle(&request(3, "shutdown", Json::Null), &mut out);
+        assert_eq!(server.handle(&notification("exit", Json::Null), &mut out), Some(0));
+    }
+}
@@ -0,0 +1,136 @@
+// Build information: identifies the build a program came from, so two
+// images can be told apart or shown to be the same. It is written by the
+// assembler with '--build-id' into the build area, BUILD_WORDS words right
+// after the end of the program, away from the stack at the top of memory.
+// As it is part of the loaded words, every format carries it:
+//
+//     word 0        BUILD_MAGIC
+//     word 1        CRC-16 of the words before the build area
+//     words 2-5     64-bit FNV-1a hash of the source files, high word first
+//     word 6        length of the build ID in bytes
+//     words 7-31    build ID, two bytes per word, zero-padded
+//
+// The CRC is taken over the words from address 0 up to the build area, as
+// the loader places them, so the emulator can check it against the memory
+// it loaded
+pub const BUILD_WORDS: usize = 32;
+pub const BUILD_MAGIC: u16 = 0xB1D0;
+pub const BUILD_ID_MAX: usize = (BUILD_WORDS - 7) * 2;
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub id: String,
+    pub source_hash: u64,
+    pub crc: u16
+}
+
+// ************************** READING AND WRITING ************************** //
+
+impl BuildInfo {
+    // Words of the build area. The ID must be at most BUILD_ID_MAX bytes
+    pub fn to_words(&self) -> Vec<u16> {
+        let mut words = vec![BUILD_MAGIC, self.crc];
+        words.extend((0..4).rev().map(|i| (self.source_hash >> (i * 16)) as u16));
+        words.push(self.id.len() as u16);
+        words.extend(self.id.as_bytes().chunks(2).map(|p| match p {
+            [hi, lo] => u16::from_be_bytes([*hi, *lo]),
+            _ => (p[0] as u16) << 8
+        }));
+        words.resize(BUILD_WORDS, 0);
+        words
+    }
+
+    // Reads a build area. Returns None when the words do not start with the
+    // magic number, and a message when they do but the ID is malformed
+    pub fn from_words(words: &[u16]) -> Option<Result<BuildInfo, String>> {
+        if words.len() != BUILD_WORDS || words[0] != BUILD_MAGIC {
+            return None;
+        }
+        let source_hash = words[2..6].iter().fold(0, |hash, w| hash << 16 | *w as u64);
+        let len = words[6] as usize;
+        if len == 0 || len > BUILD_ID_MAX {
+            return Some(Err(format!("Invalid build ID length {}", len)));
+        }
+        let bytes: Vec<u8> = words[7..].iter().flat_map(|w| w.to_be_bytes()).take(len).collect();
+        Some(match String::from_utf8(bytes) {
+            Ok(id) => Ok(BuildInfo { id, source_hash, crc: words[1] }),
+            Err(_) => Err("Build ID is not valid UTF-8".to_string())
+        })
+    }
+}
+
+// ****************************** CHECKSUMS ******************************** //
+
+// CRC-16/CCITT-FALSE (polynomial 0x1021, starting at 0xFFFF) of the words,
+// each taken as its two big-endian bytes
+pub fn crc16(words: &[u16]) -> u16 {
+    crc16_bytes(words.iter().flat_map(|w| w.to_be_bytes()))
+}
+
+fn crc16_bytes(bytes: impl Iterator<Item = u8>) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in bytes {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = match crc & 0x8000 {
+                0 => crc << 1,
+                _ => (crc << 1) ^ 0x1021
+            };
+        }
+    }
+    crc
+}
+
+// FNV-1a hash of the contents of every file, in order. The length of each
+// file is hashed before it, so moving bytes from one file to the next
+// changes the hash
+pub fn source_hash<T: AsRef<[u8]>>(files: &[T]) -> u64 {
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for file in files {
+        let file = file.as_ref();
+        for byte in (file.len() as u64).to_be_bytes().iter().chain(file) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+    hash
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        // Check value of CRC-16/CCITT-FALSE over "123456789"
+        assert_eq!(crc16_bytes(b"123456789".iter().copied()), 0x29B1);
+        assert_eq!(crc16(&[0x3132]), crc16_bytes(b"12".iter().copied()));
+        assert_eq!(crc16(&[]), 0xFFFF);
+        assert_ne!(source_hash(&["ab", "c"]), source_hash(&["a", "bc"]));
+        assert_eq!(source_hash(&["ab", "c"]), source_hash(&[b"ab".to_vec(), b"c".to_vec()]));
+    }
+
+    #[test]
+    fn words_round_trip() {
+        let info = BuildInfo {
+            id: "v1.2 nightly".to_string(), source_hash: 0x0123_4567_89AB_CDEF, crc: 0x29B1
+        };
+        let words = info.to_words();
+        assert_eq!(words.len(), BUILD_WORDS);
+        assert_eq!(words[..9], [BUILD_MAGIC, 0x29B1, 0x0123, 0x4567, 0x89AB, 0xCDEF, 12,
+                                0x7631, 0x2E32]);
+        assert_eq!(BuildInfo::from_words(&words), Some(Ok(info)));
+
+        // Odd lengths keep their last byte, and areas without the magic
+        // number or with a bad length are not build information
+        let odd = BuildInfo { id: "abc".to_string(), source_hash: 0, crc: 0 };
+        assert_eq!(BuildInfo::from_words(&odd.to_words()), Some(Ok(odd)));
+        assert_eq!(BuildInfo::from_words(&[0; BUILD_WORDS]), None);
+        let mut words = words;
+        words[6] = BUILD_ID_MAX as u16 + 1;
+        assert!(matches!(BuildInfo::from_words(&words), Some(Err(_))));
+    }
+}
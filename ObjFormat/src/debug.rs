@@ -0,0 +1,173 @@
+// Debug information: maps the words of a program back to the source it
+// was assembled from. It is written by the assembler with '-g', both as a
+// file next to the program and as the debug section of executables.
+// The format is plain text, one entry per line, with fields separated by
+// spaces and addresses written as hex:
+//
+//     version 1
+//     file <index> <path>                     source file of the program
+//     line <addr> <file> <line> <column>      source position of a word
+//     scope <label> <start> <end>             code from a label up to the
+//                                             next one (end not included)
+//     data <label> <addr> <words>             data object and its size
+//
+// Lines and columns start at 1. Empty lines and lines starting with '//'
+// are ignored, so tools may add comments
+use std::fmt::Write;
+
+pub const DEBUG_VERSION: u32 = 1;
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineEntry {
+    pub addr: u16,
+    pub file: usize,
+    pub line: usize,
+    pub column: usize
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scope {
+    pub label: String,
+    pub start: u16,
+    pub end: u16
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataObject {
+    pub label: String,
+    pub addr: u16,
+    pub words: usize
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    pub files: Vec<String>,
+    pub lines: Vec<LineEntry>,
+    pub scopes: Vec<Scope>,
+    pub objects: Vec<DataObject>
+}
+
+// ************************** READING AND WRITING ************************** //
+
+impl DebugInfo {
+    pub fn to_text(&self) -> String {
+        let mut out = String::from("// Debug information generated by the assembler\n");
+        writeln!(out, "version {}", DEBUG_VERSION).unwrap();
+        for (idx, path) in self.files.iter().enumerate() {
+            writeln!(out, "file {} {}", idx, path).unwrap();
+        }
+        for l in &self.lines {
+            writeln!(out, "line 0x{:03X} {} {} {}", l.addr, l.file, l.line, l.column).unwrap();
+        }
+        for s in &self.scopes {
+            writeln!(out, "scope {} 0x{:03X} 0x{:03X}", s.label, s.start, s.end).unwrap();
+        }
+        for o in &self.objects {
+            writeln!(out, "data {} 0x{:03X} {}", o.label, o.addr, o.words).unwrap();
+        }
+        out
+    }
+
+    // Parses debug information, returning a message naming the line of the
+    // first malformed entry
+    pub fn parse(text: &str) -> Result<DebugInfo, String> {
+        let mut info = DebugInfo::default();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            let bad_line = || format!("Invalid debug information '{}' on line {}", line, idx + 1);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let addr = |i: usize| fields.get(i).and_then(|f| parse_addr(f)).ok_or_else(bad_line);
+            let num = |i: usize| {
+                fields.get(i).and_then(|f| f.parse::<usize>().ok()).ok_or_else(bad_line)
+            };
+            match fields[0] {
+                "version" if num(1)? as u32 == DEBUG_VERSION => (),
+                "version" => {
+                    return Err(format!("Unsupported debug information version {}", fields[1]));
+                }
+                "file" if fields.len() >= 3 && num(1)? == info.files.len() => {
+                    // Paths may contain spaces
+                    let path = line.splitn(3, ' ').nth(2).ok_or_else(bad_line)?;
+                    info.files.push(path.trim().to_string());
+                }
+                "line" if fields.len() == 5 => info.lines.push(LineEntry {
+                    addr: addr(1)?, file: num(2)?, line: num(3)?, column: num(4)?
+                }),
+                "scope" if fields.len() == 4 => info.scopes.push(Scope {
+                    label: fields[1].to_string(), start: addr(2)?, end: addr(3)?
+                }),
+                "data" if fields.len() == 4 => info.objects.push(DataObject {
+                    label: fields[1].to_string(), addr: addr(2)?, words: num(3)?
+                }),
+                _ => return Err(bad_line())
+            }
+        }
+        Ok(info)
+    }
+}
+
+// *************************** LOOK-UP FUNCTIONS *************************** //
+
+impl DebugInfo {
+    // Source position of the word at addr
+    pub fn line_at(&self, addr: u16) -> Option<&LineEntry> {
+        self.lines.iter().find(|l| l.addr == addr)
+    }
+
+    // Label whose code contains addr
+    pub fn scope_at(&self, addr: u16) -> Option<&Scope> {
+        self.scopes.iter().find(|s| (s.start..s.end).contains(&addr))
+    }
+
+    // Data object that contains addr
+    pub fn object_at(&self, addr: u16) -> Option<&DataObject> {
+        let addr = addr as usize;
+        self.objects.iter().find(|o| (o.addr as usize..o.addr as usize + o.words).contains(&addr))
+    }
+}
+
+fn parse_addr(field: &str) -> Option<u16> {
+    match field.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => field.parse::<u16>().ok()
+    }
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> DebugInfo {
+        DebugInfo {
+            files: vec!["/home/user/my programs/sum.s".to_string()],
+            lines: vec![LineEntry { addr: 0, file: 0, line: 10, column: 5 },
+                        LineEntry { addr: 1, file: 0, line: 11, column: 5 }],
+            scopes: vec![Scope { label: "start".to_string(), start: 0, end: 2 }],
+            objects: vec![DataObject { label: "arr".to_string(), addr: 2, words: 5 }]
+        }
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let text = info().to_text();
+        assert!(text.contains("\nline 0x001 0 11 5\nscope start 0x000 0x002\ndata arr 0x002 5\n"));
+        assert_eq!(DebugInfo::parse(&text).unwrap(), info());
+        assert!(DebugInfo::parse("version 2").is_err());
+        assert!(DebugInfo::parse("line 0x001 0 11").is_err());
+    }
+
+    #[test]
+    fn look_up() {
+        let info = info();
+        assert_eq!(info.line_at(1).unwrap().line, 11);
+        assert_eq!(info.scope_at(1).unwrap().label, "start");
+        assert_eq!(info.object_at(6).unwrap().label, "arr");
+        assert!(info.object_at(7).is_none());
+    }
+}
@@ -0,0 +1,155 @@
+// Executable format of the CPU. All fields are big-endian:
+//
+//     offset  size  field
+//     0       4     magic number "SCPU"
+//     4       2     format version
+//     6       2     entry point (word address)
+//     8       2     number of sections
+//     10      2     reserved, 0
+//     12      4     checksum: wrapping sum of every byte of the file, taken
+//                   with this field set to 0
+//     16      12*n  section table, one entry per section:
+//                       kind (1), flags (1), load address (2),
+//                       size in bytes (4), offset in the file (4)
+//     ...           contents of the sections
+//
+// Code and data sections hold big-endian words loaded at their address.
+// The symbol section holds the symbol map written by the assembler with
+// '-m' and the debug section is not loaded into memory
+use crate::{FormatError, Image, Section, SectionKind, to_words};
+
+pub const MAGIC: &[u8; 4] = b"SCPU";
+pub const VERSION: u16 = 1;
+
+const HEADER_SIZE: usize = 16;
+const ENTRY_SIZE: usize = 12;
+const CHECKSUM_OFFSET: usize = 12;
+
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().enumerate()
+        .filter(|(i, _)| !(CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4).contains(i))
+        .fold(0u32, |sum, (_, b)| sum.wrapping_add(*b as u32))
+}
+
+fn section_bytes(section: &Section) -> Vec<u8> {
+    match section.kind {
+        SectionKind::Code | SectionKind::Data => {
+            section.words.iter().flat_map(|w| w.to_be_bytes()).collect()
+        }
+        SectionKind::Symbols | SectionKind::Debug => section.bytes.clone()
+    }
+}
+
+pub fn write(image: &Image) -> Vec<u8> {
+    let contents: Vec<Vec<u8>> = image.sections.iter().map(section_bytes).collect();
+    let mut out = Vec::new();
+    out.extend(MAGIC);
+    out.extend(VERSION.to_be_bytes());
+    out.extend(image.entry.to_be_bytes());
+    out.extend((image.sections.len() as u16).to_be_bytes());
+    out.extend(0u16.to_be_bytes());
+    out.extend(0u32.to_be_bytes()); // Checksum, filled in last
+
+    let mut offset = HEADER_SIZE + ENTRY_SIZE * image.sections.len();
+    for (section, bytes) in image.sections.iter().zip(&contents) {
+        out.push(section.kind as u8);
+        out.push(section.flags);
+        out.extend(section.addr.to_be_bytes());
+        out.extend((bytes.len() as u32).to_be_bytes());
+        out.extend((offset as u32).to_be_bytes());
+        offset += bytes.len();
+    }
+    contents.iter().for_each(|b| out.extend(b));
+
+    let sum = checksum(&out);
+    out[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4].copy_from_slice(&sum.to_be_bytes());
+    out
+}
+
+// Reads and validates an executable. Only the file itself is checked here;
+// whether its sections fit in memory is checked when loading them
+pub fn read(bytes: &[u8]) -> Result<Image, FormatError> {
+    let u16_at = |i: usize| u16::from_be_bytes([bytes[i], bytes[i + 1]]);
+    let u32_at = |i: usize| u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+
+    if bytes.len() < HEADER_SIZE {
+        return Err(FormatError::Truncated);
+    }
+    if &bytes[..4] != MAGIC {
+        return Err(FormatError::BadMagic);
+    }
+    if u16_at(4) != VERSION {
+        return Err(FormatError::Version(u16_at(4)));
+    }
+    if u32_at(CHECKSUM_OFFSET) != checksum(bytes) {
+        return Err(FormatError::ImageChecksum);
+    }
+    let entry = u16_at(6);
+    let count = u16_at(8) as usize;
+    if bytes.len() < HEADER_SIZE + ENTRY_SIZE * count {
+        return Err(FormatError::Truncated);
+    }
+
+    let mut sections = Vec::new();
+    for i in 0..count {
+        let e = HEADER_SIZE + ENTRY_SIZE * i;
+        let kind = SectionKind::from_u8(bytes[e]).ok_or(FormatError::SectionKind(bytes[e]))?;
+        let size = u32_at(e + 4) as usize;
+        let offset = u32_at(e + 8) as usize;
+        let contents = bytes.get(offset..offset + size).ok_or(FormatError::Truncated)?;
+        let mut section = Section::new(kind, u16_at(e + 2), Vec::new());
+        section.flags = bytes[e + 1];
+        match kind {
+            SectionKind::Code | SectionKind::Data => {
+                if !size.is_multiple_of(2) {
+                    return Err(FormatError::OddLength);
+                }
+                section.words = to_words(contents);
+            }
+            SectionKind::Symbols | SectionKind::Debug => section.bytes = contents.to_vec()
+        }
+        sections.push(section);
+    }
+    Ok(Image { entry, sections })
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> Image {
+        Image {
+            entry: 1,
+            sections: vec![
+                Section::new(SectionKind::Code, 0, vec![0x0100, 0xE000]),
+                Section::new(SectionKind::Data, 2, vec![0xFFFF]),
+                Section::symbols("0x0001 start\n")
+            ]
+        }
+    }
+
+    #[test]
+    fn header_and_sections() {
+        let bytes = write(&image());
+        assert_eq!(&bytes[..12], b"SCPU\x00\x01\x00\x01\x00\x03\x00\x00");
+        // Code section entry: kind 1, flags load|exec, address 0, 4 bytes at 52
+        assert_eq!(&bytes[16..28], &[1, 3, 0, 0, 0, 0, 0, 4, 0, 0, 0, 52]);
+        assert_eq!(&bytes[52..58], &[0x01, 0x00, 0xE0, 0x00, 0xFF, 0xFF]);
+        assert_eq!(read(&bytes).unwrap(), image());
+    }
+
+    #[test]
+    fn validation() {
+        let bytes = write(&image());
+        let mut corrupt = bytes.clone();
+        corrupt[53] ^= 1;
+        assert_eq!(read(&corrupt), Err(FormatError::ImageChecksum));
+        assert_eq!(read(&bytes[..40]), Err(FormatError::ImageChecksum));
+        assert_eq!(read(&bytes[..10]), Err(FormatError::Truncated));
+
+        let mut version = bytes.clone();
+        version[5] = 2;
+        assert_eq!(read(&version), Err(FormatError::Version(2)));
+    }
+}
@@ -0,0 +1,98 @@
+// Intel HEX: one record per line, ':LLAAAATT<data>CC', where LL counts
+// the data bytes, AAAA is the load address, TT the record type and CC the
+// two's complement of the sum of every other byte of the record.
+// Addresses are word addresses and every word is written msb first, which
+// is how programmers for 16-bit wide memories expect them
+use crate::{FormatError, hex_bytes, to_words};
+
+// Record types used by the CPU
+const DATA: u8 = 0x00;
+const END_OF_FILE: u8 = 0x01;
+const EXT_LINEAR_ADDR: u8 = 0x04;
+
+// Words written in each data record (16 bytes, as most tools do)
+const WORDS_PER_RECORD: usize = 8;
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)).wrapping_neg()
+}
+
+fn record(addr: u16, rtype: u8, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend(addr.to_be_bytes());
+    bytes.push(rtype);
+    bytes.extend(data);
+    bytes.push(checksum(&bytes));
+    let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!(":{}\n", hex)
+}
+
+// Writes words loaded from address 0. Addresses past 0xFFFF are reached
+// with extended linear address records
+pub fn write(words: &[u16]) -> String {
+    let mut out = String::new();
+    let mut upper = 0;
+    for (idx, chunk) in words.chunks(WORDS_PER_RECORD).enumerate() {
+        let addr = (idx * WORDS_PER_RECORD) as u32;
+        if addr >> 16 != upper {
+            upper = addr >> 16;
+            out.push_str(&record(0, EXT_LINEAR_ADDR, &(upper as u16).to_be_bytes()));
+        }
+        let data: Vec<u8> = chunk.iter().flat_map(|w| w.to_be_bytes()).collect();
+        out.push_str(&record(addr as u16, DATA, &data));
+    }
+    out.push_str(&record(0, END_OF_FILE, &[]));
+    out
+}
+
+// Reads every data record as its word address and words
+pub fn read(text: &str) -> Result<Vec<(u32, Vec<u16>)>, FormatError> {
+    let mut records = Vec::new();
+    let mut upper: u32 = 0;
+    for (idx, line) in text.lines().enumerate() {
+        let line_num = idx + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let bytes = line.strip_prefix(':').and_then(hex_bytes)
+            .filter(|b| b.len() >= 5 && b.len() == b[0] as usize + 5)
+            .ok_or(FormatError::BadRecord(line_num))?;
+        if checksum(&bytes) != 0 {
+            return Err(FormatError::Checksum(line_num));
+        }
+        let addr = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+        let data = &bytes[4..bytes.len() - 1];
+        match bytes[3] {
+            DATA if !data.len().is_multiple_of(2) => return Err(FormatError::OddByteCount(line_num)),
+            DATA => records.push(((upper << 16) + addr, to_words(data))),
+            END_OF_FILE => return Ok(records),
+            EXT_LINEAR_ADDR if data.len() == 2 => {
+                upper = u16::from_be_bytes([data[0], data[1]]) as u32;
+            }
+            _ => return Err(FormatError::BadRecord(line_num))
+        }
+    }
+    Err(FormatError::MissingEnd)
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_and_checksums() {
+        let text = write(&[0x0100, 0x0300, 0xE000]);
+        assert_eq!(text, ":0600000001000300E00016\n:00000001FF\n");
+        assert_eq!(read(&text).unwrap(), vec![(0, vec![0x0100, 0x0300, 0xE000])]);
+
+        // Second record starts at word 8, not byte 16
+        let text = write(&[0; 9]);
+        assert!(text.contains("\n:020008000000F6\n"));
+
+        assert_eq!(read(":0600000001000300E00017\n"), Err(FormatError::Checksum(1)));
+        assert_eq!(read(":0600000001000300E00016\n"), Err(FormatError::MissingEnd));
+        assert_eq!(read(":0100000001FE\n:00000001FF"), Err(FormatError::OddByteCount(1)));
+    }
+}
@@ -0,0 +1,456 @@
+// Output formats of assembled programs, written by the assembler and
+// read back by the emulator's loader. Memory is word addressed, so every
+// address in these formats counts 16-bit words, not bytes
+use std::fmt;
+use std::path::Path;
+
+pub mod build;
+pub mod debug;
+pub mod exe;
+pub mod ihex;
+pub mod srec;
+pub mod meminit;
+
+// ********************* VARIABLES AND TYPE DEFINITIONS ******************** //
+
+// Words of the CPU memory: 11-bit addresses reach 2K words, as laid out
+// in the emulator's Config.map
+pub const MEMORY_DEPTH: usize = 2048;
+
+// Format: Every way a program image can be stored in a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Exe,  // Executable with header, entry point and section table
+    Bin,  // Raw big-endian words, starting at address 0
+    IHex, // Intel HEX
+    SRec, // Motorola S-record
+    MemH, // Verilog $readmemh text
+    MemB, // Verilog $readmemb text
+    Mif,  // Intel (Altera) Memory Initialization File
+    Coe   // Xilinx coefficient file
+}
+
+// Format Error: Problems found while reading a program image, with the
+// line of the file (starting at 1) they were found on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    OddLength,
+    BadRecord(usize),
+    Checksum(usize),
+    OddByteCount(usize),
+    MissingEnd,
+    TooLarge(usize, usize), // Words of the program, depth of the memory
+    BadMagic,
+    Version(u16),
+    Truncated,
+    ImageChecksum,
+    SectionKind(u8),
+    Overlap(u16),
+    BadEntry(u16),
+    Unreadable(Format),
+    BuildInfo(String),
+    BuildCrc(u16, u16) // CRC stored in the build area, CRC of the loaded words
+}
+
+// Section Kind: What the contents of a section of an executable are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+    Code = 1,
+    Data = 2,
+    Symbols = 3,
+    Debug = 4
+}
+
+// Section flags: whether a section is loaded into memory, and whether its
+// words may be executed or written by the program
+pub const FLAG_LOAD: u8  = 0b001;
+pub const FLAG_EXEC: u8  = 0b010;
+pub const FLAG_WRITE: u8 = 0b100;
+
+// Section: A block of the program loaded at a word address (code and
+// data, as words), or information about it that is not loaded (symbols
+// and debug information, as bytes)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub kind: SectionKind,
+    pub flags: u8,
+    pub addr: u16,
+    pub words: Vec<u16>,
+    pub bytes: Vec<u8>
+}
+
+// Image: A whole program as the loader sees it. Formats without an entry
+// point or sections (binary, Intel HEX and S-record) start at address 0
+// and load every word as code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub entry: u16,
+    pub sections: Vec<Section>
+}
+
+impl Format {
+    pub const ALL: [Format; 8] = [Format::Exe, Format::Bin, Format::IHex, Format::SRec,
+                                  Format::MemH, Format::MemB, Format::Mif, Format::Coe];
+
+    // Name of the format as given on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Exe  => "exe",
+            Format::Bin  => "bin",
+            Format::IHex => "ihex",
+            Format::SRec => "srec",
+            Format::MemH => "memh",
+            Format::MemB => "memb",
+            Format::Mif  => "mif",
+            Format::Coe  => "coe"
+        }
+    }
+
+    // Conventional file extension of the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Exe  => "exe",
+            Format::Bin  => "bin",
+            Format::IHex => "hex",
+            Format::SRec => "srec",
+            Format::MemH => "memh",
+            Format::MemB => "memb",
+            Format::Mif  => "mif",
+            Format::Coe  => "coe"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        Format::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    // Whether the format describes a whole memory of a given depth, for
+    // initializing the block RAM of an FPGA
+    pub fn is_memory_init(&self) -> bool {
+        matches!(self, Format::MemH | Format::MemB | Format::Mif | Format::Coe)
+    }
+
+    // Format of a file to load. Only executables are told apart by their
+    // contents: a raw binary may start with any word, ':' (a pop) and 'S1'
+    // (a sub) included, so the other formats go by the extension of the
+    // file. Files with an unknown extension, or none, are raw binaries
+    pub fn detect(bytes: &[u8], path: &str) -> Format {
+        if bytes.starts_with(exe::MAGIC) {
+            return Format::Exe;
+        }
+        let ext = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("");
+        match ext.to_ascii_lowercase().as_str() {
+            "ihex" => Format::IHex,
+            "s19" | "s28" | "s37" | "mot" => Format::SRec,
+            ext => Format::ALL.into_iter().find(|f| f.extension() == ext).unwrap_or(Format::Bin)
+        }
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatError::OddLength => {
+                write!(f, "Binary image has an odd number of bytes")
+            }
+            FormatError::BadRecord(n) => write!(f, "Malformed record on line {}", n),
+            FormatError::Checksum(n) => write!(f, "Wrong checksum on line {}", n),
+            FormatError::OddByteCount(n) => {
+                write!(f, "Record on line {} does not hold whole 16-bit words", n)
+            }
+            FormatError::MissingEnd => write!(f, "File ends without an end of file record"),
+            FormatError::TooLarge(words, depth) => {
+                write!(f, "Program of {} words does not fit in a memory of {} words", words, depth)
+            }
+            FormatError::BadMagic => write!(f, "File is not an executable of this CPU"),
+            FormatError::Version(v) => write!(f, "Unsupported executable version {}", v),
+            FormatError::Truncated => write!(f, "Executable is truncated"),
+            FormatError::ImageChecksum => {
+                write!(f, "Executable checksum does not match, the file is corrupted")
+            }
+            FormatError::SectionKind(k) => write!(f, "Unknown section kind {}", k),
+            FormatError::Overlap(addr) => {
+                write!(f, "Sections overlap at address 0x{:03X}", addr)
+            }
+            FormatError::BadEntry(addr) => {
+                write!(f, "Entry point 0x{:03X} is not in a code section", addr)
+            }
+            FormatError::Unreadable(format) => {
+                write!(f, "Programs in the {} format can not be loaded", format.name())
+            }
+            FormatError::BuildInfo(msg) => write!(f, "{}", msg),
+            FormatError::BuildCrc(stored, loaded) => {
+                write!(f, "Program CRC is 0x{:04X} but the build area says 0x{:04X}, \
+                           the program was modified after it was built", loaded, stored)
+            }
+        }
+    }
+}
+
+impl SectionKind {
+    pub fn from_u8(kind: u8) -> Option<SectionKind> {
+        [SectionKind::Code, SectionKind::Data, SectionKind::Symbols, SectionKind::Debug]
+            .into_iter().find(|k| *k as u8 == kind)
+    }
+}
+
+impl Section {
+    // Section of words loaded at addr, with the flags of its kind
+    pub fn new(kind: SectionKind, addr: u16, words: Vec<u16>) -> Section {
+        let flags = match kind {
+            SectionKind::Code => FLAG_LOAD | FLAG_EXEC,
+            SectionKind::Data => FLAG_LOAD | FLAG_WRITE,
+            SectionKind::Symbols | SectionKind::Debug => 0
+        };
+        Section { kind, flags, addr, words, bytes: Vec::new() }
+    }
+
+    // Symbol map, as written by the assembler with '-m'
+    pub fn symbols(map: &str) -> Section {
+        Section { bytes: map.as_bytes().to_vec(),
+                  ..Section::new(SectionKind::Symbols, 0, Vec::new()) }
+    }
+
+    // Debug information, as written by the assembler with '-g'
+    pub fn debug(info: &debug::DebugInfo) -> Section {
+        Section { bytes: info.to_text().into_bytes(),
+                  ..Section::new(SectionKind::Debug, 0, Vec::new()) }
+    }
+
+    // Build area at addr, as written by the assembler with '--build-id'
+    // after the end of the program. It is loaded but neither executed nor
+    // written
+    pub fn build(info: &build::BuildInfo, addr: u16) -> Section {
+        Section { flags: FLAG_LOAD, ..Section::new(SectionKind::Data, addr, info.to_words()) }
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.flags & FLAG_LOAD != 0
+    }
+
+    // Address one past the last word of the section
+    fn end(&self) -> usize {
+        self.addr as usize + self.words.len()
+    }
+}
+
+impl Image {
+    // Program of words starting at address 0, which is also its entry
+    pub fn flat(words: Vec<u16>) -> Image {
+        Image { entry: 0, sections: vec![Section::new(SectionKind::Code, 0, words)] }
+    }
+
+    // Symbol map of the program, if the image has one
+    pub fn symbols(&self) -> Option<String> {
+        self.sections.iter()
+            .find(|s| s.kind == SectionKind::Symbols)
+            .map(|s| String::from_utf8_lossy(&s.bytes).into_owned())
+    }
+
+    // Debug information of the program, if the image has any
+    pub fn debug_info(&self) -> Option<Result<debug::DebugInfo, String>> {
+        self.sections.iter()
+            .find(|s| s.kind == SectionKind::Debug)
+            .map(|s| debug::DebugInfo::parse(&String::from_utf8_lossy(&s.bytes)))
+    }
+
+    // Build information of the program, if its last loaded words are a
+    // build area, once its CRC is checked against the words before it
+    pub fn build_info(&self) -> Result<Option<build::BuildInfo>, FormatError> {
+        let words = self.flatten();
+        let Some(start) = words.len().checked_sub(build::BUILD_WORDS) else {
+            return Ok(None);
+        };
+        let info = match build::BuildInfo::from_words(&words[start..]) {
+            Some(info) => info.map_err(FormatError::BuildInfo)?,
+            None => return Ok(None)
+        };
+        let crc = build::crc16(&words[..start]);
+        match crc == info.crc {
+            true => Ok(Some(info)),
+            false => Err(FormatError::BuildCrc(info.crc, crc))
+        }
+    }
+
+    // Words from address 0 to the end of the last loaded section, where
+    // words not in any section are 0
+    pub fn flatten(&self) -> Vec<u16> {
+        let mut words = Vec::new();
+        for s in self.sections.iter().filter(|s| s.is_loaded()) {
+            if words.len() < s.end() {
+                words.resize(s.end(), 0);
+            }
+            words[s.addr as usize..s.end()].copy_from_slice(&s.words);
+        }
+        words
+    }
+
+    // Loads the program into a memory of depth words, checking that every
+    // section fits, that no two sections overlap and that the entry point
+    // is in a section that can be executed
+    pub fn memory(&self, depth: usize) -> Result<Vec<u16>, FormatError> {
+        let loaded: Vec<&Section> = self.sections.iter().filter(|s| s.is_loaded()).collect();
+        let mut mem = vec![0; depth];
+        let mut used = vec![false; depth];
+        for s in &loaded {
+            if s.end() > depth {
+                return Err(FormatError::TooLarge(s.end(), depth));
+            }
+            if let Some(i) = used[s.addr as usize..s.end()].iter().position(|u| *u) {
+                return Err(FormatError::Overlap(s.addr + i as u16));
+            }
+            used[s.addr as usize..s.end()].iter_mut().for_each(|u| *u = true);
+            mem[s.addr as usize..s.end()].copy_from_slice(&s.words);
+        }
+        let entry = self.entry as usize;
+        if !loaded.iter().any(|s| s.flags & FLAG_EXEC != 0 && (s.addr as usize..s.end()).contains(&entry)) {
+            return Err(FormatError::BadEntry(self.entry));
+        }
+        Ok(mem)
+    }
+}
+
+// *********************** WRITING AND READING IMAGES ********************** //
+
+// Writes a program in a format. Every format but the executable only keeps
+// the loaded words, from address 0. Memory initialization formats are
+// zero-filled up to depth words
+pub fn write_image(image: &Image, format: Format, depth: usize) -> Result<Vec<u8>, FormatError> {
+    image.memory(depth)?;
+    let words = image.flatten();
+    Ok(match format {
+        Format::Exe  => exe::write(image),
+        Format::Bin  => words.iter().flat_map(|w| w.to_be_bytes()).collect(),
+        Format::IHex => ihex::write(&words).into_bytes(),
+        Format::SRec => srec::write(&words).into_bytes(),
+        _ => meminit::write(&words, depth, format).into_bytes()
+    })
+}
+
+// Reads a program in the executable, binary, Intel HEX or S-record
+// format. Memory initialization files can not be read back
+pub fn read_image(bytes: &[u8], format: Format) -> Result<Image, FormatError> {
+    let records = match format {
+        Format::Exe  => return exe::read(bytes),
+        Format::IHex => ihex::read(&String::from_utf8_lossy(bytes))?,
+        Format::SRec => srec::read(&String::from_utf8_lossy(bytes))?,
+        f if f.is_memory_init() => return Err(FormatError::Unreadable(f)),
+        _ => {
+            if !bytes.len().is_multiple_of(2) {
+                return Err(FormatError::OddLength);
+            }
+            return Ok(Image::flat(to_words(bytes)));
+        }
+    };
+
+    // Every record is loaded as code where its address says
+    let mut sections = Vec::new();
+    for (addr, data) in records {
+        let end = addr as usize + data.len();
+        let addr = u16::try_from(addr).map_err(|_| FormatError::TooLarge(end, 1 << 16))?;
+        sections.push(Section::new(SectionKind::Code, addr, data));
+    }
+    Ok(Image { entry: 0, sections })
+}
+
+// *************************** HELPER FUNCTIONS **************************** //
+
+// Parses a string of hex digit pairs into bytes
+fn hex_bytes(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+// Joins big-endian byte pairs into words
+fn to_words(bytes: &[u8]) -> Vec<u16> {
+    bytes.chunks_exact(2).map(|p| u16::from_be_bytes([p[0], p[1]])).collect()
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Words of test file 1 of the assembler (sum of an array)
+    const FILE1: [u16; 18] = [0x0100, 0x0300, 0x100D, 0x0AE0, 0xA105, 0xB00B,
+                              0x1C40, 0x4B70, 0x4241, 0x4121, 0x8804, 0x0860,
+                              0xE000, 0x0011, 0x0016, 0xFFFF, 0x0004, 0x0026];
+
+    #[test]
+    // Every format reads back the words it was written with
+    fn write_read_all_formats() {
+        let image = Image::flat(FILE1.to_vec());
+        for format in [Format::Exe, Format::Bin, Format::IHex, Format::SRec] {
+            let bytes = write_image(&image, format, MEMORY_DEPTH).unwrap();
+            let path = format!("file1.{}", format.extension());
+            assert_eq!(Format::detect(&bytes, &path), format);
+            assert_eq!(read_image(&bytes, format).unwrap().flatten(), FILE1.to_vec(),
+                       "{}", format.name());
+        }
+        assert_eq!(read_image(&[0x01], Format::Bin), Err(FormatError::OddLength));
+        assert_eq!(read_image(b"0100\n", Format::MemH),
+                   Err(FormatError::Unreadable(Format::MemH)));
+        assert_eq!(write_image(&image, Format::Coe, 16), Err(FormatError::TooLarge(18, 16)));
+    }
+
+    #[test]
+    // Raw binaries whose first word looks like a record mark: a pop starts
+    // with ':' and 'sub r3 r1 #17' with 'S1'
+    fn detect_raw_binaries() {
+        for bytes in [[0x3A, 0x00, 0xE0, 0x00], [0x53, 0x31, 0xE0, 0x00]] {
+            assert_eq!(Format::detect(&bytes, "out.bin"), Format::Bin);
+            assert_eq!(Format::detect(&bytes, "program"), Format::Bin);
+            let words = read_image(&bytes, Format::detect(&bytes, "out.bin")).unwrap().flatten();
+            assert_eq!(words, [u16::from_be_bytes([bytes[0], bytes[1]]), 0xE000]);
+        }
+        assert_eq!(Format::detect(b"SCPU\x00\x01", "out.bin"), Format::Exe);
+        assert_eq!(Format::detect(b":00000001FF", "OUT.HEX"), Format::IHex);
+        assert_eq!(Format::detect(b"S9030000FC", "out.s19"), Format::SRec);
+    }
+
+    #[test]
+    fn load_sections() {
+        let mut image = Image {
+            entry: 2,
+            sections: vec![Section::new(SectionKind::Code, 0, vec![1, 2, 3]),
+                           Section::new(SectionKind::Data, 3, vec![4]),
+                           Section::symbols("0x0002 start\n")]
+        };
+        assert_eq!(image.memory(6).unwrap(), vec![1, 2, 3, 4, 0, 0]);
+        assert_eq!(image.symbols().unwrap(), "0x0002 start\n");
+
+        image.entry = 3; // Data can not be executed
+        assert_eq!(image.memory(6), Err(FormatError::BadEntry(3)));
+        image.entry = 0;
+        image.sections[1].addr = 2;
+        assert_eq!(image.memory(6), Err(FormatError::Overlap(2)));
+        assert_eq!(image.memory(2), Err(FormatError::TooLarge(3, 2)));
+    }
+
+    #[test]
+    // The build area holds the CRC of the words before it, as they are
+    // loaded, and every format that can be read back keeps it
+    fn build_crc() {
+        let mut image = Image::flat(FILE1.to_vec());
+        assert_eq!(image.build_info(), Ok(None));
+        let info = build::BuildInfo {
+            id: "test".to_string(), source_hash: 1, crc: build::crc16(&FILE1)
+        };
+        image.sections.push(Section::build(&info, FILE1.len() as u16));
+        for format in [Format::Exe, Format::Bin, Format::IHex, Format::SRec] {
+            let bytes = write_image(&image, format, 64).unwrap();
+            assert_eq!(read_image(&bytes, format).unwrap().build_info(), Ok(Some(info.clone())));
+        }
+        let memh = String::from_utf8(write_image(&image, Format::MemH, 64).unwrap()).unwrap();
+        let area: Vec<String> = info.to_words().iter().map(|w| format!("{:04X}", w)).collect();
+        assert!(memh.contains(&(area.join("\n") + "\n")));
+
+        image.sections[0].words[3] ^= 1;
+        assert!(matches!(image.build_info(),
+                         Err(FormatError::BuildCrc(crc, _)) if crc == info.crc));
+    }
+}
@@ -0,0 +1,100 @@
+// Memory initialization files for block RAM of FPGAs. Unlike the other
+// formats they describe the whole memory, so every word up to the depth
+// of the memory is given, with the words after the program set to 0
+use crate::Format;
+
+// Words per line of the data vector of .coe files
+const COE_WORDS_PER_LINE: usize = 8;
+
+// Writes a program, loaded from address 0, as a memory of depth words.
+// The caller guarantees the program fits in the memory
+pub fn write(words: &[u16], depth: usize, format: Format) -> String {
+    let mem: Vec<u16> = words.iter().copied()
+        .chain(std::iter::repeat(0))
+        .take(depth)
+        .collect();
+    match format {
+        Format::MemH => readmem(&mem, |w| format!("{:04X}", w)),
+        Format::MemB => readmem(&mem, |w| format!("{:016b}", w)),
+        Format::Mif => mif(&mem, words.len()),
+        Format::Coe => coe(&mem),
+        _ => unreachable!("{} is not a memory initialization format", format.name())
+    }
+}
+
+// Verilog $readmemh/$readmemb: one word per line, in order from address 0
+fn readmem(mem: &[u16], word: impl Fn(u16) -> String) -> String {
+    let mut out = format!("// SoftcoreCPU memory image: {} words of 16 bits\n", mem.len());
+    for w in mem {
+        out.push_str(&word(*w));
+        out.push('\n');
+    }
+    out
+}
+
+// Intel (Altera) Memory Initialization File. The zero-filled words after
+// the program are written as a single address range
+fn mif(mem: &[u16], used: usize) -> String {
+    let digits = format!("{:X}", mem.len().saturating_sub(1)).len();
+    let mut out = format!("-- SoftcoreCPU memory image\n\
+                           WIDTH=16;\nDEPTH={};\n\n\
+                           ADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\n\n\
+                           CONTENT BEGIN\n", mem.len());
+    for (addr, w) in mem.iter().enumerate().take(used) {
+        out.push_str(&format!("    {:0d$X} : {:04X};\n", addr, w, d = digits));
+    }
+    match mem.len() - used.min(mem.len()) {
+        0 => (),
+        1 => out.push_str(&format!("    {:0d$X} : 0000;\n", used, d = digits)),
+        _ => out.push_str(&format!("    [{:0d$X}..{:0d$X}] : 0000;\n",
+                                   used, mem.len() - 1, d = digits))
+    }
+    out.push_str("END;\n");
+    out
+}
+
+// Xilinx coefficient file: the whole memory as a comma separated vector
+fn coe(mem: &[u16]) -> String {
+    let mut out = String::from("; SoftcoreCPU memory image\n\
+                                memory_initialization_radix=16;\n\
+                                memory_initialization_vector=\n");
+    let lines: Vec<String> = mem.chunks(COE_WORDS_PER_LINE)
+        .map(|c| c.iter().map(|w| format!("{:04X}", w)).collect::<Vec<_>>().join(", "))
+        .collect();
+    out.push_str(&lines.join(",\n"));
+    out.push_str(";\n");
+    out
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM: [u16; 3] = [0x0100, 0x0300, 0xE000];
+
+    #[test]
+    fn readmem_zero_fill() {
+        let text = write(&PROGRAM, 5, Format::MemH);
+        assert_eq!(text.lines().skip(1).collect::<Vec<_>>(),
+                   ["0100", "0300", "E000", "0000", "0000"]);
+        let text = write(&PROGRAM, 4, Format::MemB);
+        assert_eq!(text.lines().nth(3), Some("1110000000000000"));
+        assert_eq!(text.lines().count(), 5);
+    }
+
+    #[test]
+    fn mif_ranges() {
+        let text = write(&PROGRAM, 2048, Format::Mif);
+        assert!(text.contains("DEPTH=2048;"));
+        assert!(text.contains("    002 : E000;\n    [003..7FF] : 0000;\nEND;\n"));
+        assert!(write(&PROGRAM, 3, Format::Mif).contains("2 : E000;\nEND;"));
+    }
+
+    #[test]
+    fn coe_vector() {
+        let text = write(&PROGRAM, 10, Format::Coe);
+        assert!(text.ends_with("=\n0100, 0300, E000, 0000, 0000, 0000, 0000, 0000,\n\
+                                0000, 0000;\n"));
+    }
+}
@@ -0,0 +1,105 @@
+// Motorola S-record: one record per line, 'STLL<address><data>CC', where
+// T is the record type, LL counts the address, data and checksum bytes and
+// CC is the ones' complement of the sum of every byte after the type.
+// Addresses are word addresses, 16 bits wide (S1/S9) since the CPU
+// addresses 2K words, and every word is written msb first
+use crate::{FormatError, hex_bytes, to_words};
+
+// Words written in each data record
+const WORDS_PER_RECORD: usize = 8;
+
+// Module name written in the header record
+const HEADER: &[u8] = b"SoftcoreCPU";
+
+fn checksum(bytes: &[u8]) -> u8 {
+    !bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+fn record(rtype: char, addr: u16, data: &[u8]) -> String {
+    let mut bytes = vec![(data.len() + 3) as u8];
+    bytes.extend(addr.to_be_bytes());
+    bytes.extend(data);
+    bytes.push(checksum(&bytes));
+    let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!("S{}{}\n", rtype, hex)
+}
+
+// Writes words loaded from address 0 between a header record and a record
+// count (S5), ending with the entry address (S9), which is always 0
+pub fn write(words: &[u16]) -> String {
+    let mut out = record('0', 0, HEADER);
+    let chunks = words.chunks(WORDS_PER_RECORD);
+    let count = chunks.len();
+    for (idx, chunk) in chunks.enumerate() {
+        let data: Vec<u8> = chunk.iter().flat_map(|w| w.to_be_bytes()).collect();
+        out.push_str(&record('1', (idx * WORDS_PER_RECORD) as u16, &data));
+    }
+    out.push_str(&record('5', count as u16, &[]));
+    out.push_str(&record('9', 0, &[]));
+    out
+}
+
+// Reads every data record as its word address and words
+pub fn read(text: &str) -> Result<Vec<(u32, Vec<u16>)>, FormatError> {
+    let mut records = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line_num = idx + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let bad_record = FormatError::BadRecord(line_num);
+        let record = line.strip_prefix('S').ok_or(bad_record.clone())?;
+        let (rtype, hex) = (record.get(..1), record.get(1..));
+        let (Some(rtype), Some(bytes)) = (rtype, hex.and_then(hex_bytes)) else {
+            return Err(bad_record);
+        };
+        if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+            return Err(bad_record);
+        }
+        if checksum(&bytes[..bytes.len() - 1]) != bytes[bytes.len() - 1] {
+            return Err(FormatError::Checksum(line_num));
+        }
+        // Address width in bytes of each record type
+        let addr_size = match rtype {
+            "0" | "1" | "5" | "9" => 2,
+            "2" | "6" | "8" => 3,
+            "3" | "7" => 4,
+            _ => return Err(bad_record)
+        };
+        if bytes.len() < addr_size + 2 {
+            return Err(bad_record);
+        }
+        let addr = bytes[1..=addr_size].iter().fold(0u32, |a, b| (a << 8) | *b as u32);
+        let data = &bytes[addr_size + 1..bytes.len() - 1];
+        match rtype {
+            "1" | "2" | "3" if !data.len().is_multiple_of(2) => {
+                return Err(FormatError::OddByteCount(line_num));
+            }
+            "1" | "2" | "3" => records.push((addr, to_words(data))),
+            "7" | "8" | "9" => return Ok(records),
+            _ => () // Header and record counts carry no memory contents
+        }
+    }
+    Err(FormatError::MissingEnd)
+}
+
+// ***************************** TESTING MODULE ***************************** //
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_and_checksums() {
+        let text = write(&[0x0100, 0x0300, 0xE000]);
+        assert_eq!(text.lines().collect::<Vec<_>>()[1..],
+                   ["S109000001000300E00012", "S5030001FB", "S9030000FC"]);
+        assert_eq!(read(&text).unwrap(), vec![(0, vec![0x0100, 0x0300, 0xE000])]);
+
+        // Second record starts at word 8, not byte 16
+        assert!(write(&[0; 9]).contains("\nS10500080000F2\n"));
+
+        assert_eq!(read("S109000001000300E00013\n"), Err(FormatError::Checksum(1)));
+        assert_eq!(read("S109000001000300E00012\n"), Err(FormatError::MissingEnd));
+    }
+}